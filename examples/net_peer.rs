@@ -0,0 +1,235 @@
+#![allow(clippy::too_many_arguments)]
+
+use std::{env, time::Duration};
+
+use bevy::{app::AppExit, app::ScheduleRunnerSettings, log::LogPlugin, prelude::*};
+
+use bevy_card::game::{
+    self,
+    components::{
+        Ability, Card, CardDataId, CardInstance, Cell, Deck, LocalPlayer, Player, PlayerController,
+        Position, PositionType, Unit,
+    },
+    events::{PlayerAction, PlayerActionEvent},
+    Battle, BattleFlow, BattleResult, NetSession,
+};
+use bevy_card::AppState;
+
+/// Decks the peer plays with, the host switches to the next one every battle
+const DECKS: [&[CardDataId]; 2] = [
+    &[CardDataId::Solider, CardDataId::Solider, CardDataId::Archer],
+    &[
+        CardDataId::Solider,
+        CardDataId::Solider,
+        CardDataId::Archer,
+        CardDataId::Spare,
+    ],
+];
+/// Frames the host waits after a battle so the guest can finish it too
+const NEXT_BATTLE_FRAMES: u32 = 120;
+/// Frames the bot thinks before each action, like a player would
+const THINK_FRAMES: u32 = 5;
+
+/// Online peer without a window, summons what it can and ends its turns
+struct Bot {
+    battles: u32,
+    reported: u32,
+    wait: u32,
+}
+
+/// `net_peer host|join [address] [battles]`, prints a line per battle for the tests
+fn main() {
+    let mut args = env::args().skip(1);
+    let host = match args.next().as_deref() {
+        Some("host") => true,
+        Some("join") => false,
+        _ => {
+            eprintln!("Usage: net_peer host|join [address] [battles]");
+            std::process::exit(1);
+        }
+    };
+    let mut net = NetSession::default();
+    if let Some(address) = args.next() {
+        net.address = address;
+    }
+    if host {
+        if let Err(e) = net.host() {
+            eprintln!("Failed to listen on {}: {}", net.address, e);
+            std::process::exit(1);
+        }
+    } else {
+        net.join();
+    }
+    let battles = args.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+
+    App::build()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin::default())
+        .add_plugin(game::BattleLogicPlugin)
+        .insert_resource(net)
+        .insert_resource(Bot {
+            battles,
+            reported: 0,
+            wait: 0,
+        })
+        .add_state(AppState::Online)
+        .add_startup_system(spawn_player.system())
+        .add_system_set(
+            SystemSet::on_enter(AppState::Battle).with_system(game::start_battle.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Battle).with_system(game::cleanup_battle.system()),
+        )
+        .add_system(game::net_connection_system.system())
+        .add_system(game::net_battle_system.system())
+        .add_system(bot_action_system.system())
+        .add_system(bot_battle_system.system())
+        .run();
+}
+
+fn spawn_player(mut commands: Commands) {
+    let decks = DECKS
+        .iter()
+        .enumerate()
+        .map(|(i, cards)| Deck {
+            name: format!("Deck {}", i + 1),
+            cards: cards
+                .iter()
+                .map(|&card| game::spawn_card(&mut commands, card))
+                .collect(),
+            character_card: game::spawn_card(&mut commands, CardDataId::You),
+        })
+        .collect::<Vec<_>>();
+    let characters = decks.iter().map(|deck| deck.character_card).collect();
+    commands
+        .spawn()
+        .insert(Player {
+            name: "Bot".into(),
+            money: 0,
+            decks,
+            selected_deck: 0,
+            cards: vec![],
+            characters,
+        })
+        .insert(LocalPlayer);
+}
+
+/// One action at a time, the next once the battle took the last one
+fn bot_action_system(
+    battle: Res<Option<Battle>>,
+    controller_query: Query<(Entity, &PlayerController)>,
+    card_query: Query<(Entity, &Position), With<CardInstance>>,
+    cell_query: Query<(&Cell, &Position)>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+    mut sent: Local<Option<usize>>,
+    mut think: Local<u32>,
+) {
+    let battle = match battle.as_ref() {
+        Some(battle) => battle,
+        None => return,
+    };
+    let me = match battle.human_player(&controller_query) {
+        Some(me) => me,
+        None => return,
+    };
+    if *sent == Some(battle.actions.len()) {
+        return;
+    }
+    let action = if battle.response.map(|window| window.player) == Some(me) {
+        PlayerAction::Pass
+    } else if battle.response.is_none()
+        && battle.stacks.is_empty()
+        && battle.stage == BattleFlow::PlayerAction
+        && battle.players[battle.current_player_index] == me
+    {
+        let card = card_query
+            .iter()
+            .find(|(_, pos)| pos.player_id == me && pos.position_type == PositionType::Hand)
+            .map(|(card, _)| card);
+        let cell = cell_query
+            .iter()
+            .find(|(cell, pos)| {
+                pos.player_id == me
+                    && cell.cell_type.is_open()
+                    && !card_query.iter().any(|(_, card)| card.same_cell(pos))
+            })
+            .map(|(_, &pos)| pos);
+        match (card, cell) {
+            (Some(card), Some(position)) => PlayerAction::SummonUnit { card, position },
+            _ => PlayerAction::EndTurn,
+        }
+    } else {
+        *think = 0;
+        return;
+    };
+    *think += 1;
+    if *think < THINK_FRAMES {
+        return;
+    }
+    *think = 0;
+    *sent = Some(battle.actions.len());
+    ev_player.send(PlayerActionEvent {
+        player_id: me,
+        action,
+    });
+}
+
+/// Report each battle, the host starts the next one and both quit after the last
+fn bot_battle_system(
+    mut bot: ResMut<Bot>,
+    mut net: ResMut<NetSession>,
+    app_state: Res<State<AppState>>,
+    result: Res<Option<BattleResult>>,
+    mut player_query: Query<&mut Player, With<LocalPlayer>>,
+    peer_query: Query<&Player, Without<LocalPlayer>>,
+    card_query: Query<&Card>,
+    unit_query: Query<&Unit>,
+    ability_query: Query<&Ability>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let net = &mut *net;
+    // cleaning up a battle leaves its result
+    if let (true, Some(result)) = (result.is_changed(), result.as_ref()) {
+        bot.reported += 1;
+        println!(
+            "result {} {:?} {:?} {} {:?}",
+            bot.reported, result.winner, result.reason, result.turns, net.desync
+        );
+        if let Some(peer) = net.peer.and_then(|peer| peer_query.get(peer).ok()) {
+            let cards: Vec<_> = peer
+                .deck()
+                .iter()
+                .flat_map(|deck| deck.cards.iter())
+                .filter_map(|&card| card_query.get(card).ok())
+                .map(|card| card.name.clone())
+                .collect();
+            println!("opponent {} {}", bot.reported, cards.join(","));
+        }
+        if bot.reported >= bot.battles {
+            exit.send(AppExit);
+        }
+    }
+    let idle = match app_state.current() {
+        AppState::Online => net.peer.is_some(),
+        AppState::BattleEnd => bot.reported == net.battles,
+        _ => false,
+    };
+    if !net.is_host() || net.is_starting() || !idle || net.battles >= bot.battles {
+        return;
+    }
+    if net.battles > 0 && bot.wait < NEXT_BATTLE_FRAMES {
+        bot.wait += 1;
+        return;
+    }
+    bot.wait = 0;
+    if let Ok(mut player) = player_query.single_mut() {
+        player.selected_deck = net.battles as usize % player.decks.len();
+        if let Err(e) = net.start_battle(&player, &card_query, &unit_query, &ability_query) {
+            eprintln!("Failed to start: {}", e);
+            exit.send(AppExit);
+        }
+    }
+}
@@ -14,7 +14,6 @@ impl Plugin for UiAnimationPlugin {
 struct EffectAnimation {
     timer: Timer,
     index: usize,
-    inited: bool,
 }
 
 impl EffectAnimation {
@@ -23,7 +22,6 @@ impl EffectAnimation {
             // timer: Timer::from_seconds(0.04, true),
             timer: Timer::from_seconds(0.08, true),
             index: 0,
-            inited: false,
         }
     }
 }
@@ -37,7 +35,7 @@ fn init_animation(
     mut battle: ResMut<Option<Battle>>,
 ) {
     if let Some(battle) = battle.as_mut() {
-        for e in ev.iter() {
+        for _ in ev.iter() {
             let entity = commands
                 .spawn_bundle(TextBundle {
                     style: Style {
@@ -81,7 +79,6 @@ fn init_animation(
 fn animation_system(
     time: Res<Time>,
     mut query: Query<(Entity, &mut EffectAnimation, &mut Text)>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
     mut commands: Commands,
     mut battle: ResMut<Option<Battle>>,
 ) {
@@ -0,0 +1,42 @@
+use std::{env, time::Duration};
+
+use bevy::{app::ScheduleRunnerSettings, log::LogPlugin, prelude::*};
+
+use bevy_card::game::{self, BattleLogicPlugin, BattleServer};
+use bevy_card::AppState;
+
+/// `battle-server [address] [seconds per turn]`
+fn main() {
+    let mut args = env::args().skip(1);
+    let address = args
+        .next()
+        .unwrap_or_else(|| game::SERVER_ADDRESS.to_string());
+    let turn_time = args.next().and_then(|seconds| seconds.parse().ok());
+    let server = match BattleServer::bind(&address, turn_time) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", address, e);
+            std::process::exit(1);
+        }
+    };
+    println!("Battle server listening on {}", address);
+
+    App::build()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(LogPlugin::default())
+        .add_plugin(BattleLogicPlugin)
+        .insert_resource(server)
+        .add_state(AppState::Lobby)
+        .add_system_set(
+            SystemSet::on_enter(AppState::Battle).with_system(game::start_battle.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::BattleEnd).with_system(game::cleanup_battle.system()),
+        )
+        .add_system(game::server_connection_system.system())
+        .add_system(game::server_stream_system.system())
+        .run();
+}
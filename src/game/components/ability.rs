@@ -1,15 +1,23 @@
 use bevy::prelude::Entity;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
 use crate::game::events::BattleEvent;
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq, Inspectable)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
 pub enum AbilityDataId {
     Attack,
     Regeneration,
     Killer,
     Vampire,
     Phoenix,
+    Recall,
+    Banish,
+    Sentry,
+    Counter,
+    Cleave,
+    HealingSpring,
+    Trap,
 }
 
 impl Default for AbilityDataId {
@@ -26,9 +34,11 @@ impl AbilityDataId {
                 priority: 0,
                 name: "Attack".into(),
                 description: "Basic Attack".into(),
-                ability_type: AbilityType::Active(TargetSelection::Any),
+                ability_type: AbilityType::Active(TargetSelection::Reach),
                 effects: vec![(EffectTarget::Target, AbilityEffect::Attack)],
                 ap: 1,
+                level_bonus: vec![0],
+                upgrade_price: 0,
             },
             AbilityDataId::Regeneration => AbilityData {
                 id: AbilityDataId::Regeneration,
@@ -41,6 +51,8 @@ impl AbilityDataId {
                 }),
                 effects: vec![(EffectTarget::Target, AbilityEffect::Heal(1))],
                 ap: 0,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
             },
             AbilityDataId::Killer => AbilityData {
                 id: AbilityDataId::Killer,
@@ -56,6 +68,8 @@ impl AbilityDataId {
                 }),
                 effects: vec![(EffectTarget::Target, AbilityEffect::Heal(1))],
                 ap: 0,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
             },
             AbilityDataId::Vampire => AbilityData {
                 id: AbilityDataId::Vampire,
@@ -71,6 +85,8 @@ impl AbilityDataId {
                 }),
                 effects: vec![(EffectTarget::Target, AbilityEffect::Heal(1))],
                 ap: 0,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
             },
             AbilityDataId::Phoenix => AbilityData {
                 id: AbilityDataId::Phoenix,
@@ -78,14 +94,121 @@ impl AbilityDataId {
                 name: "Phoenix".into(),
                 description: "Phoenix".into(),
                 ability_type: AbilityType::Trigger(Trigger {
-                    event: OnBattleEvent::OnUnitDie,
+                    event: OnBattleEvent::OnCardEnterGrave,
                     conditions: vec![TriggerCondition::UnitEq(
                         UnitVar::TriggerUnit,
                         UnitVar::AbilityUnit,
                     )],
                 }),
-                effects: vec![(EffectTarget::Target, AbilityEffect::Heal(1))],
+                effects: vec![(EffectTarget::Target, AbilityEffect::Revive(1))],
+                ap: 0,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
+            },
+            AbilityDataId::Recall => AbilityData {
+                id: AbilityDataId::Recall,
+                priority: 0,
+                name: "Recall".into(),
+                description: "Return to hand when it enters the grave".into(),
+                ability_type: AbilityType::Trigger(Trigger {
+                    event: OnBattleEvent::OnCardEnterGrave,
+                    conditions: vec![TriggerCondition::UnitEq(
+                        UnitVar::TriggerUnit,
+                        UnitVar::AbilityUnit,
+                    )],
+                }),
+                effects: vec![(EffectTarget::Target, AbilityEffect::ReturnToHand)],
+                ap: 0,
+                level_bonus: vec![0],
+                upgrade_price: 0,
+            },
+            AbilityDataId::Banish => AbilityData {
+                id: AbilityDataId::Banish,
+                priority: 0,
+                name: "Banish".into(),
+                description: "Exile target card".into(),
+                ability_type: AbilityType::Active(TargetSelection::Any),
+                effects: vec![(EffectTarget::Target, AbilityEffect::Exile)],
+                ap: 2,
+                level_bonus: vec![0],
+                upgrade_price: 0,
+            },
+            AbilityDataId::Sentry => AbilityData {
+                id: AbilityDataId::Sentry,
+                priority: 0,
+                name: "Sentry".into(),
+                description: "Damage enemy units entering the board".into(),
+                ability_type: AbilityType::Trigger(Trigger {
+                    // after the unit is placed, it is a valid target by then
+                    event: OnBattleEvent::OnUnitEnterCell,
+                    conditions: vec![TriggerCondition::OtherTeam(
+                        UnitVar::TriggerPlayer,
+                        UnitVar::AbilityPlayer,
+                    )],
+                }),
+                effects: vec![(EffectTarget::Target, AbilityEffect::Damage(1))],
+                ap: 0,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
+            },
+            AbilityDataId::Counter => AbilityData {
+                id: AbilityDataId::Counter,
+                priority: 0,
+                name: "Counter".into(),
+                description: "Strike back before an enemy ability resolves".into(),
+                ability_type: AbilityType::Instant(TargetSelection::Enemy),
+                effects: vec![(EffectTarget::Target, AbilityEffect::Damage(1))],
+                ap: 1,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
+            },
+            AbilityDataId::Cleave => AbilityData {
+                id: AbilityDataId::Cleave,
+                priority: 0,
+                name: "Cleave".into(),
+                description: "Attack and splash the units next to the target".into(),
+                ability_type: AbilityType::Active(TargetSelection::Reach),
+                effects: vec![
+                    (EffectTarget::Target, AbilityEffect::Attack),
+                    (EffectTarget::TargetNeighbours, AbilityEffect::Damage(1)),
+                ],
+                ap: 1,
+                level_bonus: vec![0, 1, 2],
+                upgrade_price: 50,
+            },
+            AbilityDataId::HealingSpring => AbilityData {
+                id: AbilityDataId::HealingSpring,
+                priority: 0,
+                name: "Healing Spring".into(),
+                description: "Heal the unit standing here at the start of its turn".into(),
+                ability_type: AbilityType::Trigger(Trigger {
+                    event: OnBattleEvent::OnTurnStart,
+                    conditions: vec![TriggerCondition::UnitEq(
+                        UnitVar::TriggerPlayer,
+                        UnitVar::AbilityPlayer,
+                    )],
+                }),
+                effects: vec![(EffectTarget::Source, AbilityEffect::Heal(2))],
+                ap: 0,
+                level_bonus: vec![0],
+                upgrade_price: 0,
+            },
+            AbilityDataId::Trap => AbilityData {
+                id: AbilityDataId::Trap,
+                priority: 0,
+                name: "Trap".into(),
+                description: "Damage the unit entering here".into(),
+                ability_type: AbilityType::Trigger(Trigger {
+                    event: OnBattleEvent::OnUnitEnterCell,
+                    conditions: vec![TriggerCondition::UnitEq(
+                        UnitVar::TriggerUnit,
+                        UnitVar::AbilityUnit,
+                    )],
+                }),
+                effects: vec![(EffectTarget::Source, AbilityEffect::Damage(2))],
                 ap: 0,
+                level_bonus: vec![0],
+                upgrade_price: 0,
             },
         }
     }
@@ -100,6 +223,10 @@ pub struct AbilityData {
     pub ability_type: AbilityType,
     pub effects: Vec<(EffectTarget, AbilityEffect)>,
     pub ap: i32,
+    /// Added to the effect values at each level, the length is the max level
+    pub level_bonus: Vec<i32>,
+    /// Money to reach level 2, multiplied by the current level after that
+    pub upgrade_price: i32,
 }
 
 #[derive(Clone, Debug, Default, Inspectable)]
@@ -113,6 +240,8 @@ pub struct Ability {
     pub ap: i32,
     // Ability
     pub ability_data_id: AbilityDataId,
+    pub level: i32,
+    pub use_count: i32,
 }
 
 impl Ability {
@@ -126,6 +255,33 @@ impl Ability {
             effects: data.effects,
             ap: data.ap,
             ability_data_id,
+            level: 1,
+            use_count: 0,
+        }
+    }
+
+    pub fn max_level(&self) -> i32 {
+        self.ability_data_id.ability_data().level_bonus.len() as i32
+    }
+
+    /// Effects are the data effects scaled by the bonus of the level
+    pub fn set_level(&mut self, level: i32) {
+        let data = self.ability_data_id.ability_data();
+        self.level = level.clamp(1, self.max_level());
+        let bonus = data.level_bonus[self.level as usize - 1];
+        self.effects = data
+            .effects
+            .into_iter()
+            .map(|(target, effect)| (target, effect.scaled(bonus)))
+            .collect();
+    }
+
+    /// `None` at max level
+    pub fn upgrade_price(&self) -> Option<i32> {
+        if self.level < self.max_level() {
+            Some(self.ability_data_id.ability_data().upgrade_price * self.level)
+        } else {
+            None
         }
     }
 }
@@ -163,9 +319,15 @@ impl AbilityInstance {
 #[derive(Clone, Debug, Inspectable)]
 pub struct UnitInstanceRef(pub Entity);
 
+/// Terrain ability of a cell, used by the unit standing on it
+#[derive(Clone, Debug, Inspectable)]
+pub struct CellRef(pub Entity);
+
 #[derive(Clone, Debug, Inspectable)]
 pub enum AbilityType {
     Active(TargetSelection),
+    /// Active ability that can also be used in response to an enemy ability
+    Instant(TargetSelection),
     Trigger(Trigger),
 }
 
@@ -180,7 +342,11 @@ pub enum TargetSelection {
     None,
     Alliance,
     Enemy,
+    /// Any unit but a character
     Any,
+    /// Enemy in range, melee units cannot reach covered enemies
+    Reach,
+    AdjacentAlliance,
 }
 
 impl Default for TargetSelection {
@@ -195,16 +361,31 @@ pub struct Trigger {
     pub conditions: Vec<TriggerCondition>,
 }
 
+/// `TriggerLoop` and `TurnTimeout` have no trigger, they are only logged and never resolve on the stack
 #[derive(Clone, Debug, Inspectable)]
 pub enum OnBattleEvent {
+    OnPreTurnStart,
     OnTurnStart,
     OnTurnEnd,
+    OnPostTurnEnd,
     OnPlayerChange,
     OnPlayerDraw,
+    OnPlayerEndTurn,
     OnUnitHurt,
     OnUnitDie,
+    OnUnitStartAbility,
+    OnUnitCombat,
     OnAbilityStart,
-    // SummonUnit,s
+    OnSummonUnit,
+    OnUnitRevive,
+    OnCardReturnToHand,
+    OnCardExile,
+    OnCardEnterGrave,
+    OnCardLeaveGrave,
+    OnUnitMove,
+    OnUnitHeal,
+    OnUnitEnterCell,
+    OnPlayerEliminated,
 }
 
 impl Default for OnBattleEvent {
@@ -216,34 +397,28 @@ impl Default for OnBattleEvent {
 impl OnBattleEvent {
     pub fn match_event(&self, event: &BattleEvent) -> bool {
         match self {
-            OnBattleEvent::OnTurnStart => match event {
-                BattleEvent::TurnStart(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnTurnEnd => match event {
-                BattleEvent::TurnEnd(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnPlayerChange => match event {
-                BattleEvent::PlayerChange(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnPlayerDraw => match event {
-                BattleEvent::PlayerDraw(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnUnitHurt => match event {
-                BattleEvent::UnitHurt(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnUnitDie => match event {
-                BattleEvent::UnitDie(_) => true,
-                _ => false,
-            },
-            OnBattleEvent::OnAbilityStart => match event {
-                BattleEvent::AbilityStart(_) => true,
-                _ => false,
-            },
+            OnBattleEvent::OnPreTurnStart => matches!(event, BattleEvent::PreTurnStart(_)),
+            OnBattleEvent::OnTurnStart => matches!(event, BattleEvent::TurnStart(_)),
+            OnBattleEvent::OnTurnEnd => matches!(event, BattleEvent::TurnEnd(_)),
+            OnBattleEvent::OnPostTurnEnd => matches!(event, BattleEvent::PostTurnEnd(_)),
+            OnBattleEvent::OnPlayerChange => matches!(event, BattleEvent::PlayerChange(_)),
+            OnBattleEvent::OnPlayerDraw => matches!(event, BattleEvent::PlayerDraw(_)),
+            OnBattleEvent::OnPlayerEndTurn => matches!(event, BattleEvent::PlayerEndTurn(_)),
+            OnBattleEvent::OnUnitHurt => matches!(event, BattleEvent::UnitHurt(_)),
+            OnBattleEvent::OnUnitDie => matches!(event, BattleEvent::UnitDie(_)),
+            OnBattleEvent::OnUnitStartAbility => matches!(event, BattleEvent::UnitStartAbility(_)),
+            OnBattleEvent::OnUnitCombat => matches!(event, BattleEvent::UnitCombat(_)),
+            OnBattleEvent::OnAbilityStart => matches!(event, BattleEvent::AbilityStart(_)),
+            OnBattleEvent::OnSummonUnit => matches!(event, BattleEvent::SummonUnit(_)),
+            OnBattleEvent::OnUnitRevive => matches!(event, BattleEvent::UnitRevive(_)),
+            OnBattleEvent::OnCardReturnToHand => matches!(event, BattleEvent::CardReturnToHand(_)),
+            OnBattleEvent::OnCardExile => matches!(event, BattleEvent::CardExile(_)),
+            OnBattleEvent::OnCardEnterGrave => matches!(event, BattleEvent::CardEnterGrave(_)),
+            OnBattleEvent::OnCardLeaveGrave => matches!(event, BattleEvent::CardLeaveGrave(_)),
+            OnBattleEvent::OnUnitMove => matches!(event, BattleEvent::UnitMove(_)),
+            OnBattleEvent::OnUnitHeal => matches!(event, BattleEvent::UnitHeal(_)),
+            OnBattleEvent::OnUnitEnterCell => matches!(event, BattleEvent::UnitEnterCell(_)),
+            OnBattleEvent::OnPlayerEliminated => matches!(event, BattleEvent::PlayerEliminated(_)),
         }
     }
 }
@@ -251,6 +426,10 @@ impl OnBattleEvent {
 #[derive(Clone, Debug, Inspectable)]
 pub enum TriggerCondition {
     UnitEq(UnitVar, UnitVar),
+    UnitNe(UnitVar, UnitVar),
+    /// Units or players of the same team
+    SameTeam(UnitVar, UnitVar),
+    OtherTeam(UnitVar, UnitVar),
 }
 
 impl Default for TriggerCondition {
@@ -264,6 +443,9 @@ pub enum UnitVar {
     TriggerUnit,
     ActionUnit,
     AbilityUnit,
+    TriggerPlayer,
+    ActionPlayer,
+    AbilityPlayer,
 }
 
 impl Default for UnitVar {
@@ -280,6 +462,8 @@ pub enum EffectTarget {
     AllAlliance,
     AllEnemyExceptTarget,
     AllAllianceExceptTarget,
+    /// Units next to the target on its board
+    TargetNeighbours,
 }
 
 impl Default for EffectTarget {
@@ -297,6 +481,25 @@ pub enum AbilityEffect {
     Reflection,
     Curse(i32),
     Charm(i32),
+    /// Bring a unit back from the grave to a free cell with the given hp
+    Revive(i32),
+    ReturnToHand,
+    Exile,
+}
+
+impl AbilityEffect {
+    /// Add to the value of the effect, effects without a value are unchanged
+    pub fn scaled(self, bonus: i32) -> Self {
+        match self {
+            AbilityEffect::Damage(value) => AbilityEffect::Damage(value + bonus),
+            AbilityEffect::Heal(value) => AbilityEffect::Heal(value + bonus),
+            AbilityEffect::SoulDrain(value) => AbilityEffect::SoulDrain(value + bonus),
+            AbilityEffect::Curse(value) => AbilityEffect::Curse(value + bonus),
+            AbilityEffect::Charm(value) => AbilityEffect::Charm(value + bonus),
+            AbilityEffect::Revive(value) => AbilityEffect::Revive(value + bonus),
+            effect => effect,
+        }
+    }
 }
 
 impl Default for AbilityEffect {
@@ -322,3 +525,49 @@ impl Default for DamageType {
         Self::Attack
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damage(ability: &Ability) -> i32 {
+        match ability.effects[..] {
+            [(_, AbilityEffect::Damage(value))] => value,
+            _ => panic!("not a single damage effect"),
+        }
+    }
+
+    #[test]
+    fn level_bonus_scales_the_effects() {
+        let mut counter = Ability::new(AbilityDataId::Counter);
+        assert_eq!(damage(&counter), 1);
+        counter.set_level(3);
+        assert_eq!(counter.level, 3);
+        assert_eq!(damage(&counter), 3);
+        counter.set_level(2);
+        assert_eq!(damage(&counter), 2);
+    }
+
+    #[test]
+    fn level_is_clamped_to_the_data() {
+        let mut counter = Ability::new(AbilityDataId::Counter);
+        counter.set_level(10);
+        assert_eq!(counter.level, counter.max_level());
+        assert_eq!(damage(&counter), 3);
+        counter.set_level(0);
+        assert_eq!(counter.level, 1);
+        assert_eq!(damage(&counter), 1);
+    }
+
+    #[test]
+    fn upgrades_cost_more_each_level_until_the_max() {
+        let price = AbilityDataId::Counter.ability_data().upgrade_price;
+        let mut counter = Ability::new(AbilityDataId::Counter);
+        assert_eq!(counter.upgrade_price(), Some(price));
+        counter.set_level(2);
+        assert_eq!(counter.upgrade_price(), Some(price * 2));
+        counter.set_level(3);
+        assert_eq!(counter.upgrade_price(), None);
+        assert_eq!(Ability::new(AbilityDataId::Attack).upgrade_price(), None);
+    }
+}
@@ -0,0 +1,111 @@
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use super::AbilityDataId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum BoardDataId {
+    Square,
+    Wide,
+    Broken,
+}
+
+impl BoardDataId {
+    pub fn board_data(&self) -> BoardData {
+        match self {
+            BoardDataId::Square => BoardData {
+                id: BoardDataId::Square,
+                name: "Square".into(),
+                width: 3,
+                height: 3,
+                character: (1, 1),
+                cells: vec![],
+            },
+            BoardDataId::Wide => BoardData {
+                id: BoardDataId::Wide,
+                name: "Wide".into(),
+                width: 5,
+                height: 2,
+                character: (2, 1),
+                cells: vec![],
+            },
+            BoardDataId::Broken => BoardData {
+                id: BoardDataId::Broken,
+                name: "Broken".into(),
+                width: 4,
+                height: 3,
+                character: (1, 2),
+                cells: vec![
+                    ((0, 0), CellType::Blocked),
+                    ((3, 2), CellType::Blocked),
+                    ((1, 0), CellType::Fortified),
+                    ((3, 0), CellType::Trap),
+                    ((2, 1), CellType::HealingSpring),
+                ],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoardData {
+    pub id: BoardDataId,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    /// Cell of the character card, row 0 is the front
+    pub character: (i32, i32),
+    /// Cells that are not `CellType::Normal`
+    pub cells: Vec<((i32, i32), CellType)>,
+}
+
+impl BoardData {
+    pub fn cell_type(&self, x: i32, y: i32) -> CellType {
+        self.cells
+            .iter()
+            .find(|(pos, _)| *pos == (x, y))
+            .map(|(_, cell_type)| *cell_type)
+            .unwrap_or(CellType::Normal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
+pub enum CellType {
+    Normal,
+    Blocked,
+    /// Heals the occupant at the start of its turn
+    HealingSpring,
+    /// Damages units entering it
+    Trap,
+    /// Occupant gets extra DEF in combat
+    Fortified,
+}
+
+impl Default for CellType {
+    fn default() -> Self {
+        CellType::Normal
+    }
+}
+
+impl CellType {
+    /// Units can be summoned, moved or revived into this cell
+    pub fn is_open(&self) -> bool {
+        !matches!(self, CellType::Blocked)
+    }
+
+    /// Trigger abilities of the terrain, spawned on the cell
+    pub fn abilities(&self) -> Vec<AbilityDataId> {
+        match self {
+            CellType::HealingSpring => vec![AbilityDataId::HealingSpring],
+            CellType::Trap => vec![AbilityDataId::Trap],
+            CellType::Normal | CellType::Blocked | CellType::Fortified => vec![],
+        }
+    }
+
+    pub fn def_bonus(&self) -> i32 {
+        match self {
+            CellType::Fortified => 1,
+            _ => 0,
+        }
+    }
+}
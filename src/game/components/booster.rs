@@ -0,0 +1,52 @@
+use super::Rarity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoosterDataId {
+    Basic,
+    Premium,
+}
+
+impl BoosterDataId {
+    pub fn all() -> Vec<BoosterDataId> {
+        vec![BoosterDataId::Basic, BoosterDataId::Premium]
+    }
+
+    pub fn booster_data(&self) -> BoosterData {
+        match self {
+            BoosterDataId::Basic => BoosterData {
+                id: BoosterDataId::Basic,
+                name: "Basic Booster".into(),
+                price: 60,
+                size: 3,
+                weights: vec![
+                    (Rarity::Common, 70),
+                    (Rarity::Uncommon, 25),
+                    (Rarity::Rare, 5),
+                ],
+            },
+            BoosterDataId::Premium => BoosterData {
+                id: BoosterDataId::Premium,
+                name: "Premium Booster".into(),
+                price: 200,
+                size: 5,
+                weights: vec![
+                    (Rarity::Common, 40),
+                    (Rarity::Uncommon, 35),
+                    (Rarity::Rare, 20),
+                    (Rarity::Legendary, 5),
+                ],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoosterData {
+    pub id: BoosterDataId,
+    pub name: String,
+    pub price: i32,
+    /// Cards per pack
+    pub size: usize,
+    /// Chance of each rarity per pull, relative to the sum
+    pub weights: Vec<(Rarity, u32)>,
+}
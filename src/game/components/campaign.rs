@@ -0,0 +1,89 @@
+use super::EncounterDataId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CampaignDataId {
+    Main,
+}
+
+impl CampaignDataId {
+    pub fn campaign_data(&self) -> CampaignData {
+        match self {
+            CampaignDataId::Main => CampaignData {
+                id: CampaignDataId::Main,
+                name: "The Warlord".into(),
+                nodes: vec![
+                    CampaignNode {
+                        name: "Bandit Camp".into(),
+                        node_type: CampaignNodeType::Battle(EncounterDataId::BanditCamp),
+                        next: vec![1, 2],
+                        reward: 30,
+                    },
+                    CampaignNode {
+                        name: "Merchant".into(),
+                        node_type: CampaignNodeType::Shop,
+                        next: vec![3],
+                        reward: 0,
+                    },
+                    CampaignNode {
+                        name: "Campfire".into(),
+                        node_type: CampaignNodeType::Rest,
+                        next: vec![3],
+                        reward: 0,
+                    },
+                    CampaignNode {
+                        name: "Cult Shrine".into(),
+                        node_type: CampaignNodeType::Battle(EncounterDataId::CultShrine),
+                        next: vec![4, 5],
+                        reward: 50,
+                    },
+                    CampaignNode {
+                        name: "Campfire".into(),
+                        node_type: CampaignNodeType::Rest,
+                        next: vec![6],
+                        reward: 0,
+                    },
+                    CampaignNode {
+                        name: "Ruins".into(),
+                        node_type: CampaignNodeType::Battle(EncounterDataId::Ruins),
+                        next: vec![6],
+                        reward: 50,
+                    },
+                    CampaignNode {
+                        name: "Warlord Keep".into(),
+                        node_type: CampaignNodeType::Boss(EncounterDataId::WarlordKeep),
+                        next: vec![],
+                        reward: 200,
+                    },
+                ],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CampaignData {
+    pub id: CampaignDataId,
+    pub name: String,
+    /// The first node is the start, the campaign ends at a node without `next`
+    pub nodes: Vec<CampaignNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CampaignNode {
+    pub name: String,
+    pub node_type: CampaignNodeType,
+    /// Indices of the nodes reachable after this one
+    pub next: Vec<usize>,
+    /// Money for clearing the node
+    pub reward: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CampaignNodeType {
+    Battle(EncounterDataId),
+    Shop,
+    /// Units of the selected deck gain exp
+    Rest,
+    /// Clearing it finishes the campaign
+    Boss(EncounterDataId),
+}
@@ -1,15 +1,23 @@
 use bevy::prelude::Entity;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
 use super::UnitDataId;
 
-#[derive(Debug, Clone, Inspectable)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
 pub enum CardDataId {
     Solider,
     Spare,
     You,
     Demon,
     Enemy,
+    Archer,
+    Ally,
+    Rival,
+    Phoenix,
+    Wisp,
+    Exorcist,
+    Sentinel,
 }
 
 impl CardDataId {
@@ -20,33 +28,133 @@ impl CardDataId {
                 name: "Soldier".into(),
                 cost: 1,
                 card_type: CardDataType::Unit(UnitDataId::Solider),
+                rarity: Rarity::Common,
             },
             CardDataId::Spare => CardData {
                 id: CardDataId::Spare,
                 name: "Spare".into(),
                 cost: 1,
                 card_type: CardDataType::Unit(UnitDataId::Spare),
+                rarity: Rarity::Common,
             },
             CardDataId::You => CardData {
                 id: CardDataId::You,
                 name: "You".into(),
                 cost: 1,
                 card_type: CardDataType::Unit(UnitDataId::You),
+                rarity: Rarity::Common,
             },
             CardDataId::Demon => CardData {
                 id: CardDataId::Demon,
                 name: "Demon".into(),
                 cost: 1,
                 card_type: CardDataType::Unit(UnitDataId::Demon),
+                rarity: Rarity::Rare,
             },
             CardDataId::Enemy => CardData {
                 id: CardDataId::Enemy,
                 name: "Enemy".into(),
                 cost: 1,
                 card_type: CardDataType::Unit(UnitDataId::Enemy),
+                rarity: Rarity::Common,
+            },
+            CardDataId::Ally => CardData {
+                id: CardDataId::Ally,
+                name: "Ally".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Ally),
+                rarity: Rarity::Common,
+            },
+            CardDataId::Rival => CardData {
+                id: CardDataId::Rival,
+                name: "Rival".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Rival),
+                rarity: Rarity::Common,
+            },
+            CardDataId::Archer => CardData {
+                id: CardDataId::Archer,
+                name: "Archer".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Archer),
+                rarity: Rarity::Uncommon,
+            },
+            CardDataId::Phoenix => CardData {
+                id: CardDataId::Phoenix,
+                name: "Phoenix".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Phoenix),
+                rarity: Rarity::Rare,
+            },
+            CardDataId::Wisp => CardData {
+                id: CardDataId::Wisp,
+                name: "Wisp".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Wisp),
+                rarity: Rarity::Uncommon,
+            },
+            CardDataId::Exorcist => CardData {
+                id: CardDataId::Exorcist,
+                name: "Exorcist".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Exorcist),
+                rarity: Rarity::Rare,
+            },
+            CardDataId::Sentinel => CardData {
+                id: CardDataId::Sentinel,
+                name: "Sentinel".into(),
+                cost: 1,
+                card_type: CardDataType::Unit(UnitDataId::Sentinel),
+                rarity: Rarity::Uncommon,
             },
         }
     }
+
+    /// Cards that lead a deck as the player's character
+    pub fn is_character(&self) -> bool {
+        matches!(
+            self,
+            CardDataId::You | CardDataId::Enemy | CardDataId::Ally | CardDataId::Rival
+        )
+    }
+
+    /// Cards sold in the shop and pulled from boosters, character cards are not
+    pub fn shop() -> Vec<CardDataId> {
+        vec![
+            CardDataId::Solider,
+            CardDataId::Spare,
+            CardDataId::Archer,
+            CardDataId::Demon,
+            CardDataId::Phoenix,
+            CardDataId::Wisp,
+            CardDataId::Exorcist,
+            CardDataId::Sentinel,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn price(&self) -> i32 {
+        match self {
+            Rarity::Common => 20,
+            Rarity::Uncommon => 50,
+            Rarity::Rare => 120,
+            Rarity::Legendary => 300,
+        }
+    }
+
+    /// Selling gives back half the price
+    pub fn sell_price(&self) -> i32 {
+        self.price() / 2
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +163,7 @@ pub struct CardData {
     pub name: String,
     pub cost: i32,
     pub card_type: CardDataType,
+    pub rarity: Rarity,
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +187,10 @@ pub struct Card {
     pub card_type: CardType,
     // Card
     pub card_data_id: CardDataId,
+    /// Battles the card was played in
+    pub use_count: i32,
+    pub wins: i32,
+    pub loses: i32,
 }
 
 impl Card {
@@ -88,6 +201,9 @@ impl Card {
             name: card_data.name,
             cost: card_data.cost,
             card_type,
+            use_count: 0,
+            wins: 0,
+            loses: 0,
         }
     }
 }
@@ -0,0 +1,302 @@
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use super::{AiProfile, BoardDataId, EnemyDataId, PlayerController, PlayerSlot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
+pub enum EncounterDataId {
+    Training,
+    Outpost,
+    Ruins,
+    Skirmish,
+    TagTeam,
+    Hotseat,
+    Online,
+    Server,
+    BanditCamp,
+    CultShrine,
+    WarlordKeep,
+}
+
+impl EncounterDataId {
+    /// Encounters picked in the lobby, the others are reached through the campaign
+    pub fn all() -> Vec<EncounterDataId> {
+        vec![
+            EncounterDataId::Training,
+            EncounterDataId::Outpost,
+            EncounterDataId::Ruins,
+            EncounterDataId::Skirmish,
+            EncounterDataId::TagTeam,
+            EncounterDataId::Hotseat,
+        ]
+    }
+
+    pub fn encounter_data(&self) -> EncounterData {
+        match self {
+            EncounterDataId::Training => EncounterData {
+                id: EncounterDataId::Training,
+                name: "Training".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Opponent, 1, BoardDataId::Square),
+                ],
+                win_conditions: WinCondition::standard(),
+                response_window: false,
+            },
+            EncounterDataId::Outpost => EncounterData {
+                id: EncounterDataId::Outpost,
+                name: "Outpost".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Opponent, 1, BoardDataId::Wide),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::TurnLimit(20),
+                ],
+                response_window: true,
+            },
+            EncounterDataId::Ruins => EncounterData {
+                id: EncounterDataId::Ruins,
+                name: "Ruins".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Broken),
+                    ParticipantData::ai(PlayerSlot::Opponent, 1, BoardDataId::Broken),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DeckOut,
+                ],
+                response_window: true,
+            },
+            // free-for-all
+            EncounterDataId::Skirmish => EncounterData {
+                id: EncounterDataId::Skirmish,
+                name: "Skirmish".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Opponent, 1, BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Rival, 2, BoardDataId::Square),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DestroyUnits(4),
+                ],
+                response_window: true,
+            },
+            // 2v2
+            EncounterDataId::TagTeam => EncounterData {
+                id: EncounterDataId::TagTeam,
+                name: "Tag Team".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Ally, 0, BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Opponent, 1, BoardDataId::Square),
+                    ParticipantData::ai(PlayerSlot::Rival, 1, BoardDataId::Square),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::TurnLimit(40),
+                ],
+                response_window: true,
+            },
+            // two humans on one screen
+            EncounterDataId::Hotseat => EncounterData {
+                id: EncounterDataId::Hotseat,
+                name: "Hotseat".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::human(
+                        ParticipantPlayer::Slot(PlayerSlot::SecondHuman),
+                        1,
+                        BoardDataId::Square,
+                    ),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DeckOut,
+                ],
+                response_window: true,
+            },
+            // host against guest, started from the online screen
+            EncounterDataId::Online => EncounterData {
+                id: EncounterDataId::Online,
+                name: "Online".into(),
+                participants: vec![
+                    ParticipantData::online(ParticipantPlayer::Host, 0, BoardDataId::Square),
+                    ParticipantData::online(ParticipantPlayer::Guest, 1, BoardDataId::Square),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DeckOut,
+                ],
+                response_window: true,
+            },
+            // the two clients of the battle server, in the order they joined
+            EncounterDataId::Server => EncounterData {
+                id: EncounterDataId::Server,
+                name: "Server".into(),
+                participants: vec![
+                    ParticipantData::human(ParticipantPlayer::Joined, 0, BoardDataId::Square),
+                    ParticipantData::human(ParticipantPlayer::Joined, 1, BoardDataId::Square),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DeckOut,
+                ],
+                response_window: true,
+            },
+            EncounterDataId::BanditCamp => EncounterData {
+                id: EncounterDataId::BanditCamp,
+                name: "Bandit Camp".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::enemy(EnemyDataId::Bandit, 1, BoardDataId::Square),
+                ],
+                win_conditions: WinCondition::standard(),
+                response_window: true,
+            },
+            EncounterDataId::CultShrine => EncounterData {
+                id: EncounterDataId::CultShrine,
+                name: "Cult Shrine".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Broken),
+                    ParticipantData::enemy(EnemyDataId::Cultist, 1, BoardDataId::Broken),
+                ],
+                win_conditions: WinCondition::standard(),
+                response_window: true,
+            },
+            EncounterDataId::WarlordKeep => EncounterData {
+                id: EncounterDataId::WarlordKeep,
+                name: "Warlord Keep".into(),
+                participants: vec![
+                    ParticipantData::you(BoardDataId::Square),
+                    ParticipantData::enemy(EnemyDataId::Warlord, 1, BoardDataId::Wide),
+                ],
+                win_conditions: vec![
+                    WinCondition::CharacterDeath,
+                    WinCondition::Surrender,
+                    WinCondition::DeckOut,
+                ],
+                response_window: true,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncounterData {
+    pub id: EncounterDataId,
+    pub name: String,
+    /// In turn order, the first one is shown at the bottom
+    pub participants: Vec<ParticipantData>,
+    pub win_conditions: Vec<WinCondition>,
+    /// Let the non-active player respond to abilities on the stack
+    pub response_window: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParticipantData {
+    pub player: ParticipantPlayer,
+    pub team: usize,
+    pub controller: PlayerController,
+    pub profile: AiProfile,
+    pub board: BoardDataId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticipantPlayer {
+    /// The `LocalPlayer`, or `BattleConfig::you` when set
+    You,
+    /// The built-in `Player` in this slot
+    Slot(PlayerSlot),
+    /// The `Player` marked with `EnemyPlayer`
+    Enemy(EnemyDataId),
+    /// Taken from `BattleConfig::players`, the clients of the battle server
+    Joined,
+    /// Online battles, the local or the remote `Player` depending on who hosts
+    Host,
+    Guest,
+}
+
+impl ParticipantData {
+    pub fn you(board: BoardDataId) -> Self {
+        Self {
+            player: ParticipantPlayer::You,
+            team: 0,
+            controller: PlayerController::Player,
+            profile: Default::default(),
+            board,
+        }
+    }
+
+    pub fn ai(player: PlayerSlot, team: usize, board: BoardDataId) -> Self {
+        Self {
+            player: ParticipantPlayer::Slot(player),
+            team,
+            controller: PlayerController::Ai,
+            profile: Default::default(),
+            board,
+        }
+    }
+
+    /// Another human on the same screen
+    pub fn human(player: ParticipantPlayer, team: usize, board: BoardDataId) -> Self {
+        Self {
+            player,
+            team,
+            controller: PlayerController::Player,
+            profile: Default::default(),
+            board,
+        }
+    }
+
+    /// The controller is picked when the battle starts, `Player` on this side
+    pub fn online(player: ParticipantPlayer, team: usize, board: BoardDataId) -> Self {
+        Self {
+            player,
+            team,
+            controller: PlayerController::Remote,
+            profile: Default::default(),
+            board,
+        }
+    }
+
+    pub fn enemy(enemy: EnemyDataId, team: usize, board: BoardDataId) -> Self {
+        Self {
+            player: ParticipantPlayer::Enemy(enemy),
+            team,
+            controller: PlayerController::Ai,
+            profile: enemy.enemy_data().profile,
+            board,
+        }
+    }
+}
+
+/// Ways a battle can end, checked while the battle runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WinCondition {
+    /// A player whose character card dies is eliminated
+    CharacterDeath,
+    /// A player who has to draw from an empty deck is eliminated
+    DeckOut,
+    /// Draw once this many turns have been played
+    TurnLimit(u32),
+    /// A player who surrenders is eliminated
+    Surrender,
+    /// The first team to destroy this many enemy units wins
+    DestroyUnits(usize),
+}
+
+impl WinCondition {
+    pub fn standard() -> Vec<WinCondition> {
+        vec![WinCondition::CharacterDeath, WinCondition::Surrender]
+    }
+}
@@ -0,0 +1,67 @@
+use bevy_inspector_egui::Inspectable;
+
+use super::{AiProfile, CardDataId};
+
+/// Opponents of the campaign, spawned as `Player`s with an `EnemyPlayer` marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum EnemyDataId {
+    Bandit,
+    Cultist,
+    Warlord,
+}
+
+impl EnemyDataId {
+    pub fn all() -> Vec<EnemyDataId> {
+        vec![
+            EnemyDataId::Bandit,
+            EnemyDataId::Cultist,
+            EnemyDataId::Warlord,
+        ]
+    }
+
+    pub fn enemy_data(&self) -> EnemyData {
+        match self {
+            EnemyDataId::Bandit => EnemyData {
+                id: EnemyDataId::Bandit,
+                name: "Bandit".into(),
+                character: CardDataId::Enemy,
+                deck: vec![CardDataId::Solider, CardDataId::Solider, CardDataId::Archer],
+                profile: AiProfile::Standard,
+            },
+            EnemyDataId::Cultist => EnemyData {
+                id: EnemyDataId::Cultist,
+                name: "Cultist".into(),
+                character: CardDataId::Enemy,
+                deck: vec![CardDataId::Demon, CardDataId::Demon, CardDataId::Demon],
+                profile: AiProfile::Aggressive,
+            },
+            EnemyDataId::Warlord => EnemyData {
+                id: EnemyDataId::Warlord,
+                name: "Warlord".into(),
+                character: CardDataId::Rival,
+                deck: vec![
+                    CardDataId::Demon,
+                    CardDataId::Demon,
+                    CardDataId::Solider,
+                    CardDataId::Solider,
+                    CardDataId::Archer,
+                    CardDataId::Archer,
+                ],
+                profile: AiProfile::Aggressive,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnemyData {
+    pub id: EnemyDataId,
+    pub name: String,
+    pub character: CardDataId,
+    pub deck: Vec<CardDataId>,
+    pub profile: AiProfile,
+}
+
+/// Marks the `Player` of a campaign enemy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub struct EnemyPlayer(pub EnemyDataId);
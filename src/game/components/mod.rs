@@ -2,7 +2,12 @@ use bevy::prelude::Entity;
 use bevy_inspector_egui::Inspectable;
 
 mod ability;
+mod board;
+mod booster;
+mod campaign;
 mod card;
+mod encounter;
+mod enemy;
 mod player;
 mod position;
 mod selectable;
@@ -13,11 +18,22 @@ pub use ability::AbilityDataId;
 pub use ability::AbilityEffect;
 pub use ability::AbilityInstance;
 pub use ability::AbilityType;
+pub use ability::CellRef;
 pub use ability::EffectTarget;
 pub use ability::OnBattleEvent;
+pub use ability::TargetSelection;
 pub use ability::TriggerCondition;
 pub use ability::UnitInstanceRef;
 pub use ability::UnitVar;
+pub use board::BoardData;
+pub use board::BoardDataId;
+pub use board::CellType;
+pub use booster::BoosterData;
+pub use booster::BoosterDataId;
+pub use campaign::CampaignData;
+pub use campaign::CampaignDataId;
+pub use campaign::CampaignNode;
+pub use campaign::CampaignNodeType;
 pub use card::Card;
 pub use card::CardData;
 pub use card::CardDataId;
@@ -25,17 +41,34 @@ pub use card::CardDataType;
 pub use card::CardInstance;
 pub use card::CardInstanceType;
 pub use card::CardType;
+pub use card::Rarity;
+pub use encounter::EncounterData;
+pub use encounter::EncounterDataId;
+pub use encounter::ParticipantData;
+pub use encounter::ParticipantPlayer;
+pub use encounter::WinCondition;
+pub use enemy::EnemyData;
+pub use enemy::EnemyDataId;
+pub use enemy::EnemyPlayer;
+pub use player::AiProfile;
+pub use player::Deck;
+pub use player::LocalPlayer;
 pub use player::Player;
 pub use player::PlayerController;
 pub use player::PlayerInstance;
+pub use player::PlayerSlot;
+pub use player::RemotePlayer;
+pub use player::RunPlayer;
 pub use position::Position;
 pub use position::PositionType;
 pub use selectable::SelectType;
 pub use selectable::Selectable;
+pub use unit::AttackRange;
 pub use unit::Status;
 pub use unit::Unit;
 pub use unit::UnitData;
 pub use unit::UnitDataId;
+pub use unit::UnitGrowth;
 pub use unit::UnitInstance;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
@@ -52,11 +85,26 @@ pub struct DefText(pub Entity);
 pub struct CardSprite(pub Entity);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
 pub struct HighlightSprite(pub Entity);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub struct CardBack(pub Entity);
+
+/// Side of the card the local screen shows
+#[derive(Debug, Clone, Copy, PartialEq, Inspectable)]
+pub struct CardFace {
+    pub shown: bool,
+    /// Seconds left of turning over
+    pub flip: f32,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
-pub struct Cell;
+pub struct Cell {
+    pub cell_type: CellType,
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
-pub struct Board;
+pub struct Board {
+    pub width: i32,
+    pub height: i32,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
 pub struct PlayerInstanceRef(pub Entity);
@@ -9,22 +9,87 @@ use bevy_inspector_egui::Inspectable;
 pub struct Player {
     pub name: String,
     pub money: i32,
-    pub decks: Vec<Entity>,
+    pub decks: Vec<Deck>,
+    /// Index into `decks`, the deck taken into the next battle
+    pub selected_deck: usize,
+    /// Cards not in any deck
     pub cards: Vec<Entity>,
-    pub character_card: Entity,
+    /// Character cards a deck can be led by
+    pub characters: Vec<Entity>,
     // pub exp: i32,
 }
 
+impl Player {
+    /// The selected deck, the last one when the index is past the end
+    pub fn deck(&self) -> Option<&Deck> {
+        self.decks
+            .get(self.selected_deck)
+            .or_else(|| self.decks.last())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Deck {
+    pub name: String,
+    pub cards: Vec<Entity>,
+    pub character_card: Entity,
+}
+
+/// The `Player` using this game, the others are opponents and allies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub struct LocalPlayer;
+
+/// Built-in `Player`s spawned when the game loads, picked by encounters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum PlayerSlot {
+    Opponent,
+    Ally,
+    Rival,
+    /// The other human of a hotseat battle
+    SecondHuman,
+}
+
+/// The `Player` of a run, its cards are dropped when the run ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub struct RunPlayer;
+
+/// The `Player` on the other side of an online battle, built from the deck it sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub struct RemotePlayer;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
 pub enum PlayerController {
     Player,
     Ai,
+    /// The other side of an online battle, its actions come over the network
+    Remote,
+}
+
+/// How an AI controlled player takes its turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum AiProfile {
+    /// Only ends its turn, still responds with instants
+    Passive,
+    /// Attacks with one unit per turn
+    Standard,
+    /// Attacks with every unit that has AP
+    Aggressive,
+}
+
+impl Default for AiProfile {
+    fn default() -> Self {
+        Self::Standard
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct PlayerInstance {
+    /// The `Player` taking part in the battle
+    pub player_id: Entity,
     pub name: String,
     pub mp: i32,
+    /// Players of the same team are allies
+    pub team: usize,
     // pub hands: Vec<Entity>,
     // pub decks: Vec<Entity>,
     // pub graves: Vec<Entity>,
@@ -1,9 +1,10 @@
 use bevy::math::Vec3;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
-use super::Entity;
+use super::{Board, Entity};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -13,30 +14,57 @@ pub struct Position {
     pub position_type: PositionType,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
 pub enum PositionType {
     Deck,
     Hand,
     Board,
     Grave,
+    Exile,
 }
 
 impl Position {
-    pub fn xyz(&self) -> Vec3 {
+    /// One orthogonal step on the same board
+    pub fn is_adjacent(&self, other: &Position) -> bool {
+        self.position_type == PositionType::Board
+            && other.position_type == PositionType::Board
+            && self.player_id == other.player_id
+            && (self.x - other.x).abs() + (self.y - other.y).abs() == 1
+    }
+
+    /// Face up cards are seen by everyone, a hand also by its owner
+    pub fn is_visible_to(&self, viewer: Option<Entity>) -> bool {
+        self.face_up || (self.position_type == PositionType::Hand && viewer == Some(self.player_id))
+    }
+
+    /// Same cell of the same board
+    pub fn same_cell(&self, other: &Position) -> bool {
+        self.position_type == other.position_type
+            && self.player_id == other.player_id
+            && self.x == other.x
+            && self.y == other.y
+    }
+
+    /// Local translation on a board of the given size, centered on the cells
+    pub fn xyz(&self, board: &Board) -> Vec3 {
         let offset = 100.0;
+        let left = -(board.width - 1) as f32 / 2.0 * offset;
+        let right = (board.width - 1) as f32 / 2.0 * offset;
+        let top = (board.height - 1) as f32 / 2.0 * offset;
         match &self.position_type {
-            PositionType::Deck => Vec3::new(-3.0 * offset, 0.0, (self.z + 1) as f32),
+            PositionType::Deck => Vec3::new(left - 2.0 * offset, 0.0, (self.z + 1) as f32),
             PositionType::Hand => Vec3::new(
                 (self.z - 2) as f32 * offset,
-                2.0 * -offset,
+                -top - offset,
                 (self.z + 1) as f32,
             ),
             PositionType::Board => Vec3::new(
-                (self.x - 1) as f32 * offset,
-                (self.y - 1) as f32 * -offset,
+                left + self.x as f32 * offset,
+                top - self.y as f32 * offset,
                 (self.z + 1) as f32,
             ),
-            PositionType::Grave => Vec3::new(3.0 * offset, 0.0, (self.z + 1) as f32),
+            PositionType::Grave => Vec3::new(right + 2.0 * offset, 0.0, (self.z + 1) as f32),
+            PositionType::Exile => Vec3::new(right + 2.0 * offset, -offset, (self.z + 1) as f32),
         }
     }
 }
@@ -10,6 +10,13 @@ pub enum UnitDataId {
     You,
     Demon,
     Enemy,
+    Archer,
+    Ally,
+    Rival,
+    Phoenix,
+    Wisp,
+    Exorcist,
+    Sentinel,
 }
 
 impl UnitDataId {
@@ -22,7 +29,13 @@ impl UnitDataId {
                 hp: 10,
                 atk: 2,
                 def: 0,
-                abilities: vec![AbilityDataId::Attack],
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Counter],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
             },
             UnitDataId::Spare => UnitData {
                 id: UnitDataId::Spare,
@@ -31,7 +44,13 @@ impl UnitDataId {
                 hp: 0,
                 atk: 0,
                 def: 0,
+                range: AttackRange::Melee,
                 abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 0,
+                    atk: 0,
+                    def: 0,
+                },
             },
             UnitDataId::You => UnitData {
                 id: UnitDataId::You,
@@ -40,7 +59,13 @@ impl UnitDataId {
                 hp: 10,
                 atk: 5,
                 def: 0,
+                range: AttackRange::Melee,
                 abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
             },
             UnitDataId::Demon => UnitData {
                 id: UnitDataId::Demon,
@@ -49,7 +74,13 @@ impl UnitDataId {
                 hp: 10,
                 atk: 2,
                 def: 0,
-                abilities: vec![AbilityDataId::Attack],
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Counter],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
             },
             UnitDataId::Enemy => UnitData {
                 id: UnitDataId::Enemy,
@@ -58,10 +89,142 @@ impl UnitDataId {
                 hp: 10,
                 atk: 2,
                 def: 0,
+                range: AttackRange::Melee,
                 abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Ally => UnitData {
+                id: UnitDataId::Ally,
+                name: "Ally".into(),
+                description: "Fights by your side".into(),
+                hp: 10,
+                atk: 3,
+                def: 0,
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Rival => UnitData {
+                id: UnitDataId::Rival,
+                name: "Rival".into(),
+                description: "Another challenger".into(),
+                hp: 10,
+                atk: 3,
+                def: 0,
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Archer => UnitData {
+                id: UnitDataId::Archer,
+                name: "Archer".into(),
+                description: "Shoots over the front row".into(),
+                hp: 6,
+                atk: 2,
+                def: 0,
+                range: AttackRange::Ranged,
+                abilities: vec![AbilityDataId::Attack],
+                growth: UnitGrowth {
+                    hp: 1,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Phoenix => UnitData {
+                id: UnitDataId::Phoenix,
+                name: "Phoenix".into(),
+                description: "Rises from the grave".into(),
+                hp: 4,
+                atk: 2,
+                def: 0,
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Phoenix],
+                growth: UnitGrowth {
+                    hp: 1,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Wisp => UnitData {
+                id: UnitDataId::Wisp,
+                name: "Wisp".into(),
+                description: "Flees back to the hand".into(),
+                hp: 3,
+                atk: 1,
+                def: 0,
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Recall],
+                growth: UnitGrowth {
+                    hp: 1,
+                    atk: 1,
+                    def: 0,
+                },
+            },
+            UnitDataId::Exorcist => UnitData {
+                id: UnitDataId::Exorcist,
+                name: "Exorcist".into(),
+                description: "Banishes units for good".into(),
+                hp: 6,
+                atk: 1,
+                def: 0,
+                range: AttackRange::Ranged,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Banish],
+                growth: UnitGrowth {
+                    hp: 1,
+                    atk: 0,
+                    def: 0,
+                },
+            },
+            UnitDataId::Sentinel => UnitData {
+                id: UnitDataId::Sentinel,
+                name: "Sentinel".into(),
+                description: "Watches over the enemy board".into(),
+                hp: 8,
+                atk: 1,
+                def: 1,
+                range: AttackRange::Melee,
+                abilities: vec![AbilityDataId::Attack, AbilityDataId::Sentry],
+                growth: UnitGrowth {
+                    hp: 2,
+                    atk: 0,
+                    def: 1,
+                },
             },
         }
     }
+
+    /// Units of character cards, they lead a deck
+    pub fn is_character(&self) -> bool {
+        matches!(
+            self,
+            UnitDataId::You | UnitDataId::Enemy | UnitDataId::Ally | UnitDataId::Rival
+        )
+    }
+}
+
+/// Melee units can only reach enemies that are not covered by a unit in front of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum AttackRange {
+    Melee,
+    Ranged,
+}
+
+impl Default for AttackRange {
+    fn default() -> Self {
+        Self::Melee
+    }
 }
 
 #[derive(Debug, Clone, Inspectable)]
@@ -72,7 +235,17 @@ pub struct UnitData {
     pub hp: i32,
     pub atk: i32,
     pub def: i32,
+    pub range: AttackRange,
     pub abilities: Vec<AbilityDataId>,
+    /// Stats gained per level
+    pub growth: UnitGrowth,
+}
+
+#[derive(Debug, Clone, Copy, Default, Inspectable)]
+pub struct UnitGrowth {
+    pub hp: i32,
+    pub atk: i32,
+    pub def: i32,
 }
 
 #[derive(Debug, Clone)]
@@ -83,12 +256,14 @@ pub struct Unit {
     pub hp: i32,
     pub atk: i32,
     pub def: i32,
+    pub range: AttackRange,
     pub abilities: Vec<Entity>,
     // Unit
     pub unit_data_id: UnitDataId,
-    // pub exp: i32,
-    // pub level: i32,
-    // pub dead_count: i32,
+    /// Exp towards the next level
+    pub exp: i32,
+    pub level: i32,
+    pub dead_count: i32,
 }
 
 impl Unit {
@@ -100,9 +275,38 @@ impl Unit {
             hp: data.hp,
             atk: data.atk,
             def: data.def,
+            range: data.range,
             abilities,
             unit_data_id,
+            exp: 0,
+            level: 1,
+            dead_count: 0,
+        }
+    }
+
+    pub fn exp_to_level_up(&self) -> i32 {
+        self.level * 10
+    }
+
+    /// Stats are the data stats plus growth for every level after the first
+    pub fn set_level(&mut self, level: i32) {
+        let data = self.unit_data_id.unit_data();
+        let levels = level.max(1) - 1;
+        self.level = level.max(1);
+        self.hp = data.hp + data.growth.hp * levels;
+        self.atk = data.atk + data.growth.atk * levels;
+        self.def = data.def + data.growth.def * levels;
+    }
+
+    /// Returns the levels gained
+    pub fn gain_exp(&mut self, exp: i32) -> i32 {
+        let level = self.level;
+        self.exp += exp;
+        while self.exp >= self.exp_to_level_up() {
+            self.exp -= self.exp_to_level_up();
+            self.set_level(self.level + 1);
         }
+        self.level - level
     }
 }
 
@@ -114,18 +318,19 @@ pub struct UnitInstance {
     pub hp: i32,
     pub atk: i32,
     pub def: i32,
+    pub range: AttackRange,
     pub abilities: Vec<Entity>,
     // Unit
     pub unit_data_id: UnitDataId,
-    // pub exp: i32,
-    // pub level: i32,
-    // pub dead_count: i32,
+    pub level: i32,
     // Battle
     pub unit_id: Entity,
     pub ap: i32,
     pub states: Status,
     // pub is_dead: bool,
     pub owner: Entity,
+    /// Team of the owner
+    pub team: usize,
 }
 
 impl UnitInstance {
@@ -133,6 +338,7 @@ impl UnitInstance {
         unit_id: Entity,
         unit: Unit,
         player_instance_id: Entity,
+        team: usize,
         abilities: Vec<Entity>,
     ) -> Self {
         Self {
@@ -141,13 +347,16 @@ impl UnitInstance {
             hp: unit.hp,
             atk: unit.atk,
             def: unit.def,
+            range: unit.range,
             abilities,
             unit_data_id: unit.unit_data_id,
+            level: unit.level,
             // Battle
             unit_id,
             ap: 0,
             states: Default::default(),
             owner: player_instance_id,
+            team,
         }
     }
 }
@@ -165,3 +374,36 @@ pub struct Status {
     /// Mad
     pub curse: i8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exp_below_the_next_level_is_kept() {
+        let mut unit = Unit::new(UnitDataId::Solider, vec![]);
+        assert_eq!(unit.gain_exp(unit.exp_to_level_up() - 1), 0);
+        assert_eq!(unit.level, 1);
+        assert_eq!(unit.exp, 9);
+    }
+
+    #[test]
+    fn levels_carry_the_rest_and_grow_the_stats() {
+        let data = UnitDataId::Solider.unit_data();
+        let mut unit = Unit::new(UnitDataId::Solider, vec![]);
+        // 10 to reach level 2, 20 more for level 3, 5 left over
+        assert_eq!(unit.gain_exp(35), 2);
+        assert_eq!(unit.level, 3);
+        assert_eq!(unit.exp, 5);
+        assert_eq!(unit.hp, data.hp + data.growth.hp * 2);
+        assert_eq!(unit.atk, data.atk + data.growth.atk * 2);
+        assert_eq!(unit.def, data.def + data.growth.def * 2);
+    }
+
+    #[test]
+    fn no_exp_gains_nothing() {
+        let mut unit = Unit::new(UnitDataId::Solider, vec![]);
+        assert_eq!(unit.gain_exp(0), 0);
+        assert_eq!((unit.level, unit.exp), (1, 0));
+    }
+}
@@ -1,8 +1,9 @@
 use bevy::prelude::Entity;
+use serde::{Deserialize, Serialize};
 
-use crate::game::components::Position;
+use crate::game::components::{Position, PositionType, WinCondition};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum BattleEvent {
     PreTurnStart(PreTurnStart),
     TurnStart(TurnStart),
@@ -17,77 +18,190 @@ pub enum BattleEvent {
     UnitCombat(UnitCombat),
     AbilityStart(AbilityStart),
     SummonUnit(SummonUnit),
+    UnitRevive(UnitRevive),
+    CardReturnToHand(CardReturnToHand),
+    CardExile(CardExile),
+    CardEnterGrave(CardEnterGrave),
+    CardLeaveGrave(CardLeaveGrave),
+    TriggerLoop(TriggerLoop),
+    UnitMove(UnitMove),
+    UnitHeal(UnitHeal),
+    UnitEnterCell(UnitEnterCell),
+    PlayerEliminated(PlayerEliminated),
+    TurnTimeout(TurnTimeout),
 }
 
-#[derive(Clone, Copy, Debug)]
+impl BattleEvent {
+    /// Cards and units the event names, players and abilities left out
+    pub fn cards(&self) -> Vec<Entity> {
+        match *self {
+            BattleEvent::UnitHurt(UnitHurt { source, target, .. })
+            | BattleEvent::UnitStartAbility(UnitStartAbility { source, target, .. })
+            | BattleEvent::UnitCombat(UnitCombat { source, target })
+            | BattleEvent::AbilityStart(AbilityStart { source, target, .. })
+            | BattleEvent::UnitHeal(UnitHeal { source, target, .. }) => vec![source, target],
+            BattleEvent::UnitDie(UnitDie { dead, killer }) => vec![dead, killer],
+            BattleEvent::SummonUnit(SummonUnit { card, .. })
+            | BattleEvent::CardReturnToHand(CardReturnToHand { card })
+            | BattleEvent::CardExile(CardExile { card })
+            | BattleEvent::CardEnterGrave(CardEnterGrave { card })
+            | BattleEvent::CardLeaveGrave(CardLeaveGrave { card, .. })
+            | BattleEvent::UnitRevive(UnitRevive { unit: card, .. })
+            | BattleEvent::UnitMove(UnitMove { unit: card, .. })
+            | BattleEvent::UnitEnterCell(UnitEnterCell { unit: card, .. }) => vec![card],
+            BattleEvent::PreTurnStart(_)
+            | BattleEvent::TurnStart(_)
+            | BattleEvent::TurnEnd(_)
+            | BattleEvent::PostTurnEnd(_)
+            | BattleEvent::PlayerChange(_)
+            | BattleEvent::PlayerDraw(_)
+            | BattleEvent::PlayerEndTurn(_)
+            | BattleEvent::TriggerLoop(_)
+            | BattleEvent::PlayerEliminated(_)
+            | BattleEvent::TurnTimeout(_) => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PreTurnStart {
     pub player: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct TurnStart {
     pub player: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct TurnEnd {
     pub player: Entity,
 }
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PostTurnEnd {
     pub player: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PlayerChange {
+    pub prev_player: Entity,
     pub next_player: Entity,
     pub next_index: usize,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PlayerDraw {
     pub player: Entity,
     pub c: i32,
 }
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PlayerEndTurn {
     pub player: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct UnitHurt {
     pub source: Entity,
     pub target: Entity,
     pub value: i32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct UnitDie {
     pub dead: Entity,
     pub killer: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct UnitStartAbility {
     pub ability: Entity,
     pub source: Entity,
     pub target: Entity,
 }
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct UnitCombat {
     pub source: Entity,
     pub target: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct AbilityStart {
     pub ability: Entity,
     pub source: Entity,
     pub target: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct SummonUnit {
+    pub player: Entity,
     pub card: Entity,
     pub position: Position,
 }
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct UnitRevive {
+    pub unit: Entity,
+    pub hp: i32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CardReturnToHand {
+    pub card: Entity,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CardExile {
+    pub card: Entity,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CardEnterGrave {
+    pub card: Entity,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CardLeaveGrave {
+    pub card: Entity,
+    pub to: PositionType,
+}
+
+/// The stack was cleared because triggers kept feeding each other
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TriggerLoop {
+    pub depth: usize,
+    pub frames: usize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct UnitMove {
+    pub unit: Entity,
+    pub from: Position,
+    pub to: Position,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct UnitHeal {
+    pub source: Entity,
+    pub target: Entity,
+    pub value: i32,
+}
+
+/// A unit is placed on a cell by summon, move or revive
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct UnitEnterCell {
+    pub unit: Entity,
+    pub position: Position,
+}
+
+/// The character card of the player died, the player takes no more turns
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PlayerEliminated {
+    pub player: Entity,
+    pub reason: WinCondition,
+}
+
+/// The turn timer ran out and the turn was ended for the player, log only
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TurnTimeout {
+    pub player: Entity,
+}
@@ -1,6 +1,7 @@
 mod battle_event;
 
 use bevy::prelude::Entity;
+use serde::{Deserialize, Serialize};
 
 use super::components::Position;
 
@@ -15,7 +16,7 @@ pub struct PlayerActionEvent {
     pub player_id: Entity,
     pub action: PlayerAction,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PlayerAction {
     EndTurn,
     UseAbility {
@@ -31,6 +32,14 @@ pub enum PlayerAction {
         card: Entity,
         position: Position,
     },
+    Move {
+        unit: Entity,
+        to: Position,
+    },
+    /// Let a pending ability resolve without responding
+    Pass,
+    /// Give up the battle, allowed at any time
+    Surrender,
     // Use Item
     // Use Magic
 }
@@ -50,19 +59,34 @@ pub enum InputMappingEvent {
 #[derive(Debug)]
 pub struct SelectEntityEvent(pub Option<Entity>);
 
+/// Write the local player's profile to the save file
+#[derive(Debug)]
+pub struct SaveGameEvent;
+
 #[derive(Debug)]
 pub struct SelectionChangedEvent(pub Option<Entity>, pub Option<Entity>);
 
 pub use battle_event::AbilityStart;
 pub use battle_event::BattleEvent;
+pub use battle_event::CardEnterGrave;
+pub use battle_event::CardExile;
+pub use battle_event::CardLeaveGrave;
+pub use battle_event::CardReturnToHand;
 pub use battle_event::PlayerChange;
 pub use battle_event::PlayerDraw;
+pub use battle_event::PlayerEliminated;
 pub use battle_event::PlayerEndTurn;
 pub use battle_event::PreTurnStart;
 pub use battle_event::SummonUnit;
+pub use battle_event::TriggerLoop;
 pub use battle_event::TurnEnd;
 pub use battle_event::TurnStart;
+pub use battle_event::TurnTimeout;
 pub use battle_event::UnitCombat;
 pub use battle_event::UnitDie;
+pub use battle_event::UnitEnterCell;
+pub use battle_event::UnitHeal;
 pub use battle_event::UnitHurt;
+pub use battle_event::UnitMove;
+pub use battle_event::UnitRevive;
 pub use battle_event::UnitStartAbility;
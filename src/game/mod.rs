@@ -6,9 +6,40 @@ pub mod events;
 pub use bevy::ecs::entity::Entity;
 use bevy::prelude::*;
 
+pub use systems::battle_end_ui_system;
+pub use systems::campaign_battle_end;
+pub use systems::campaign_ui_system;
 pub use systems::cleanup_battle;
+pub use systems::close_spectator;
+pub use systems::deck_builder_ui_system;
+pub use systems::deck_select_ui_system;
+pub use systems::give_battle_rewards;
 pub use systems::load_game;
+pub use systems::net_battle_system;
+pub use systems::net_connection_system;
+pub use systems::online_ui_system;
+pub use systems::open_deck_builder;
+pub use systems::open_shop;
+pub use systems::run_battle_end;
+pub use systems::run_ui_system;
+pub use systems::save_replay;
+pub use systems::server_battle_ui_system;
+pub use systems::server_connection_system;
+pub use systems::server_stream_system;
+pub use systems::shop_ui_system;
+pub use systems::spawn_card;
+pub use systems::spectate_ui_system;
 pub use systems::start_battle;
+pub use systems::BattleConfig;
+pub use systems::BattleFlow;
+pub use systems::BattleServer;
+pub use systems::BattleView;
+pub use systems::CardSave;
+pub use systems::ClientMessage;
+pub use systems::NetSession;
+pub use systems::ServerMessage;
+pub use systems::SERVER_ADDRESS;
+pub use systems::SERVER_VERSION;
 
 pub use systems::handle_selection_change;
 pub use systems::InputState;
@@ -17,28 +48,68 @@ pub use systems::SelectedEntity;
 pub use systems::HoveringEntity;
 
 pub use self::systems::Battle;
+pub use self::systems::BattleResult;
+pub use self::systems::PlayerResult;
+pub use self::systems::UnitResult;
 
-pub struct GamePlugin;
-impl Plugin for GamePlugin {
+/// Battle rules only, no graphics, ui or input, runs headless on the battle server
+pub struct BattleLogicPlugin;
+impl Plugin for BattleLogicPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup_camera.system())
-            // battle
-            .init_resource::<Option<Battle>>()
+        app.init_resource::<Option<Battle>>()
+            .init_resource::<Option<BattleResult>>()
+            .init_resource::<BattleConfig>()
+            .init_resource::<systems::NetSession>()
             .add_event::<events::BattleEvent>()
             .insert_resource(systems::EventState::Init)
             .insert_resource(systems::BattleFlow::PreTurnStart)
             .add_system(systems::flow_systme.system())
             .add_system(systems::update.system())
+            .add_system(systems::turn_timer_system.system())
             .add_event::<events::PreTurnStart>()
             .add_system(systems::pre_turn_start_system.system())
             .add_event::<events::UnitHurt>()
             .add_system(systems::unit_hurt_system.system())
+            .add_event::<events::UnitHeal>()
+            .add_system(systems::unit_heal_system.system())
             .add_event::<events::PlayerDraw>()
             .add_system(systems::player_draw_system.system())
             .add_event::<events::SummonUnit>()
             .add_system(systems::summon_unit_system.system())
             .add_event::<events::UnitDie>()
             .add_system(systems::unit_die_system.system())
+            .add_event::<events::UnitRevive>()
+            .add_system(systems::unit_revive_system.system())
+            .add_event::<events::CardReturnToHand>()
+            .add_system(systems::card_return_to_hand_system.system())
+            .add_event::<events::CardExile>()
+            .add_system(systems::card_exile_system.system())
+            .add_event::<events::UnitMove>()
+            .add_system(systems::unit_move_system.system())
+            // player action
+            .add_event::<events::PlayerActionEvent>()
+            .add_system(systems::player_event_system.system())
+            .add_system(systems::ai_response_system.system());
+    }
+}
+
+pub struct GamePlugin;
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(BattleLogicPlugin)
+            .add_startup_system(setup_camera.system())
+            // deck builder
+            .init_resource::<systems::DeckBuilder>()
+            // campaign
+            .init_resource::<systems::CampaignProgress>()
+            // run
+            .init_resource::<systems::RunState>()
+            // shop
+            .init_resource::<systems::GameRng>()
+            .init_resource::<systems::Shop>()
+            // save
+            .add_event::<events::SaveGameEvent>()
+            .add_system(systems::save_game_system.system())
             // graphics
             .add_system(systems::attach_graphic_to_board.system())
             .add_system(systems::attach_graphic_to_card.system())
@@ -49,6 +120,7 @@ impl Plugin for GamePlugin {
             .add_system(systems::update_atk_text.system())
             .add_system(systems::update_def_text.system())
             .add_system(systems::update_transform_by_position.system())
+            .add_system(systems::card_face_system.system())
             // battle ui
             .add_startup_system(systems::setup_ui.system())
             .add_system(systems::update_ui_system.system())
@@ -62,10 +134,25 @@ impl Plugin for GamePlugin {
             .add_event::<events::InputMappingEvent>()
             .add_system(systems::mouse_click_system.system())
             .add_system(systems::keyboard_system.system())
-            .add_system(systems::input_event_system.system())
-            // player action
-            .add_event::<events::PlayerActionEvent>()
-            .add_system(systems::player_event_system.system())
+            .add_system(
+                systems::input_event_system
+                    .system()
+                    .with_run_criteria(systems::not_spectating.system()),
+            )
+            // response window
+            .add_system(systems::response_ui_system.system())
+            .init_resource::<systems::Hotseat>()
+            .add_system(systems::net_connection_system.system())
+            .add_system(systems::net_battle_system.system())
+            .add_system(systems::net_status_ui_system.system())
+            .init_resource::<systems::ServerClient>()
+            .add_system(systems::server_client_system.system())
+            .init_resource::<systems::Spectator>()
+            .add_system(systems::spectator_system.system())
+            .add_system(systems::spectator_ui_system.system())
+            .add_system(systems::hotseat_system.system())
+            .add_system(systems::hotseat_ui_system.system())
+            .add_system(systems::turn_timer_ui_system.system())
             // input state
             .add_system(systems::handle_selection_change.system())
             .add_event::<events::SelectEntityEvent>()
@@ -92,7 +179,7 @@ fn setup_camera(mut commands: Commands) {
 // Card => use_count, win/lose, etc...
 // Unit => dead_count, level, exp, etc...
 // Item => level, upgrade, etc...
-// Ability => level, exp, use_count, ... TODO!
+// Ability => level, use_count, ...
 
 // In Battle:
 // Player Instance => name, mp,  ...
@@ -119,11 +206,11 @@ fn setup_camera(mut commands: Commands) {
 // ApText Entity: Text2dBundle
 // HighlightSprite Entity: SpriteBundle
 
-// Ability Instance Entity: AbilityInstance, UnitInstanceRef(/Item/????)
+// Ability Instance Entity: AbilityInstance, UnitInstanceRef(/Item/????) or CellRef(terrain)
 
-// Cell Entity: Cell, Position, SpriteBundle, HighlightSprite, HighlightColor, Selectable
+// Cell Entity: Cell(CellType), Position, SpriteBundle, HighlightSprite, HighlightColor, Selectable
 // HighlightSprite Entity: SpriteBundle
 
-// Board Entity: Board, PlayerInstanceRef, Transform, GlobalTransform
+// Board Entity: Board(width, height), PlayerInstanceRef, Transform, GlobalTransform
 // Children: Player Instance Entity, Card Instance Entity(Unit Instance), Cell Entity
 // Children: MpText
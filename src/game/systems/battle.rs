@@ -1,52 +1,129 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 
+use super::battle_end::UnitResult;
+use super::battle_rule::{board_units, can_target, neighbours, BoardUnit};
+use super::spectator::Replay;
 use crate::{
     game::{
         components::{
-            AbilityDataId, AbilityEffect, AbilityInstance, AbilityType, CardInstance, EffectTarget,
-            PlayerController, PlayerInstance, Position, PositionType, TriggerCondition,
-            UnitInstance, UnitInstanceRef, UnitVar,
+            AbilityDataId, AbilityEffect, AbilityInstance, AbilityType, AiProfile, CardInstance,
+            Cell, CellRef, EffectTarget, PlayerController, PlayerInstance, Position, PositionType,
+            TargetSelection, TriggerCondition, Unit, UnitInstance, UnitInstanceRef, UnitVar,
+            WinCondition,
         },
         events::{
-            AbilityStart, BattleEvent, PlayerChange, PlayerDraw, PlayerEndTurn, PreTurnStart,
-            SummonUnit, TurnStart, UnitCombat, UnitDie, UnitHurt, UnitStartAbility,
+            AbilityStart, BattleEvent, CardEnterGrave, CardExile, CardLeaveGrave, CardReturnToHand,
+            PlayerAction, PlayerActionEvent, PlayerChange, PlayerDraw, PlayerEliminated,
+            PlayerEndTurn, PreTurnStart, SummonUnit, TriggerLoop, TurnStart, TurnTimeout,
+            UnitCombat, UnitDie, UnitEnterCell, UnitHeal, UnitHurt, UnitMove, UnitRevive,
+            UnitStartAbility,
         },
     },
     AppState,
 };
 
+pub const MOVE_AP_COST: i32 = 1;
+
 #[derive(Debug)]
 pub struct BattleFrame {
     pub event: BattleEvent,
     pub on_stacks: Vec<Entity>,
     pub started: bool,
+    /// The response window for this frame was already given
+    pub responded: bool,
+}
+
+impl BattleFrame {
+    pub fn new(event: BattleEvent) -> Self {
+        Self {
+            event,
+            on_stacks: Default::default(),
+            started: false,
+            responded: false,
+        }
+    }
+}
+
+/// The non-active player may answer a pending ability before it resolves
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseWindow {
+    pub player: Entity,
+    pub ability: UnitStartAbility,
+}
+
+impl ResponseWindow {
+    /// Target the AI picks for an instant used in response
+    pub fn target(&self, selection: &TargetSelection, source: Entity) -> Entity {
+        match selection {
+            TargetSelection::None => source,
+            TargetSelection::Alliance => self.ability.target,
+            TargetSelection::Enemy => self.ability.source,
+            TargetSelection::Any => self.ability.source,
+            TargetSelection::Reach => self.ability.source,
+            TargetSelection::AdjacentAlliance => self.ability.target,
+        }
+    }
 }
 
 pub fn update(
     mut battle: ResMut<Option<Battle>>,
     ability_query: Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
     mut unit_query: Query<(Entity, &mut UnitInstance)>,
+    player_query: Query<&PlayerInstance>,
+    position_query: Query<&Position, With<UnitInstance>>,
+    (cell_ability_query, cell_query): (
+        Query<(Entity, &AbilityInstance, &CellRef)>,
+        Query<(&Cell, &Position)>,
+    ),
     mut ev_pre_turn_start: EventWriter<PreTurnStart>,
-    mut ev_unit_hurt: EventWriter<UnitHurt>,
+    (mut ev_unit_hurt, mut ev_unit_heal): (EventWriter<UnitHurt>, EventWriter<UnitHeal>),
     mut ev_player_draw: EventWriter<PlayerDraw>,
     mut ev_summon_unit: EventWriter<SummonUnit>,
     mut ev_unit_die: EventWriter<UnitDie>,
+    mut ev_unit_revive: EventWriter<UnitRevive>,
+    mut ev_card_return_to_hand: EventWriter<CardReturnToHand>,
+    mut ev_card_exile: EventWriter<CardExile>,
+    mut ev_unit_move: EventWriter<UnitMove>,
 ) {
     if let Some(battle) = battle.as_mut() {
+        if battle.response.is_some() {
+            // wait for the response
+            return;
+        }
         let stacks = &mut battle.stacks;
         'outer: while !stacks.is_empty() {
+            if stacks.len() > battle.limits.max_stack_depth
+                || battle.frames_resolved > battle.limits.max_frames_per_action
+            {
+                // break the loop, the frame that started it still resolves without its triggers
+                let event = TriggerLoop {
+                    depth: stacks.len(),
+                    frames: battle.frames_resolved,
+                };
+                warn!("Trigger loop: {:?}", event);
+                stacks.truncate(1);
+                stacks[0].started = true;
+                stacks[0].on_stacks.clear();
+                battle.frames_resolved = 0;
+                battle.log.push(BattleEvent::TriggerLoop(event));
+                return;
+            }
             // check animation...
             let s = stacks.last_mut().unwrap();
             if !s.started {
                 let mut e: Vec<_> = ability_query
                     .iter()
-                    .filter(|(_, a, _)| match &a.ability_type {
-                        AbilityType::Active(_) => false,
+                    .map(|(id, a, _)| (id, a))
+                    .chain(cell_ability_query.iter().map(|(id, a, _)| (id, a)))
+                    .filter(|(_, a)| match &a.ability_type {
+                        AbilityType::Active(_) | AbilityType::Instant(_) => false,
                         AbilityType::Trigger(t) => t.event.match_event(&s.event),
                     })
                     .collect();
-                e.sort_by_key(|(_, a, _)| a.priority);
-                let mut ids = e.iter().map(|(id, _, _)| *id).collect();
+                e.sort_by_key(|(_, a)| a.priority);
+                let mut ids = e.iter().map(|(id, _)| *id).collect();
                 s.on_stacks.append(&mut ids);
                 s.started = true;
                 info!("Frame start: {:?}", s);
@@ -56,64 +133,134 @@ pub fn update(
             // on event
             while !s.on_stacks.is_empty() {
                 let ss = s.on_stacks.pop().unwrap();
-                let (entity, ability, unit_ref) = ability_query.get(ss).unwrap();
+                let (entity, ability, ability_unit) = match ability_query.get(ss) {
+                    Ok((entity, ability, unit_ref)) => (entity, ability, unit_ref.0),
+                    Err(_) => {
+                        // terrain, used by the unit on the cell
+                        let (entity, ability, cell_ref) = cell_ability_query.get(ss).unwrap();
+                        let (_, cell) = cell_query.get(cell_ref.0).unwrap();
+                        let occupant = match &s.event {
+                            BattleEvent::UnitEnterCell(event) if event.position.same_cell(cell) => {
+                                Some(event.unit)
+                            }
+                            _ => cell_occupant(&mut unit_query, &position_query, cell),
+                        };
+                        match occupant {
+                            Some(unit) => (entity, ability, unit),
+                            None => continue,
+                        }
+                    }
+                };
                 if let AbilityType::Trigger(t) = &ability.ability_type {
-                    let ability_unit = unit_ref.0; // ability unit ref
+                    let character =
+                        |player: Entity| player_query.get(player).unwrap().player_card_instance_id;
                     let trigger_unit = match &s.event {
                         BattleEvent::PreTurnStart(_) => ability_unit,
                         BattleEvent::TurnStart(_) => ability_unit,
                         BattleEvent::TurnEnd(_) => ability_unit,
                         BattleEvent::PostTurnEnd(_) => ability_unit,
-                        BattleEvent::PlayerChange(_) => todo!(),
-                        BattleEvent::PlayerDraw(_) => todo!(),
+                        BattleEvent::PlayerChange(event) => character(event.next_player),
+                        BattleEvent::PlayerDraw(event) => character(event.player),
                         BattleEvent::PlayerEndTurn(event) => event.player,
                         BattleEvent::UnitHurt(event) => event.target,
                         BattleEvent::UnitDie(event) => event.dead,
                         BattleEvent::UnitStartAbility(event) => event.source,
                         BattleEvent::UnitCombat(event) => event.source,
                         BattleEvent::AbilityStart(event) => event.source,
-                        BattleEvent::SummonUnit(_) => todo!(),
+                        BattleEvent::SummonUnit(event) => event.card,
+                        BattleEvent::UnitRevive(event) => event.unit,
+                        BattleEvent::CardReturnToHand(event) => event.card,
+                        BattleEvent::CardExile(event) => event.card,
+                        BattleEvent::CardEnterGrave(event) => event.card,
+                        BattleEvent::CardLeaveGrave(event) => event.card,
+                        BattleEvent::TriggerLoop(_) => ability_unit,
+                        BattleEvent::TurnTimeout(event) => character(event.player),
+                        BattleEvent::UnitMove(event) => event.unit,
+                        BattleEvent::UnitHeal(event) => event.target,
+                        BattleEvent::UnitEnterCell(event) => event.unit,
+                        BattleEvent::PlayerEliminated(event) => character(event.player),
                     };
                     let action_unit = match &s.event {
                         BattleEvent::PreTurnStart(_) => ability_unit,
                         BattleEvent::TurnStart(_) => ability_unit,
                         BattleEvent::TurnEnd(_) => ability_unit,
                         BattleEvent::PostTurnEnd(_) => ability_unit,
-                        BattleEvent::PlayerChange(_) => todo!(),
-                        BattleEvent::PlayerDraw(_) => todo!(),
+                        BattleEvent::PlayerChange(event) => character(event.prev_player),
+                        BattleEvent::PlayerDraw(event) => character(event.player),
                         BattleEvent::PlayerEndTurn(event) => event.player,
                         BattleEvent::UnitHurt(event) => event.source,
                         BattleEvent::UnitDie(event) => event.killer,
                         BattleEvent::UnitStartAbility(event) => event.source,
                         BattleEvent::UnitCombat(event) => event.source,
                         BattleEvent::AbilityStart(event) => event.source,
-                        BattleEvent::SummonUnit(_) => todo!(),
+                        BattleEvent::SummonUnit(event) => character(event.player),
+                        BattleEvent::UnitRevive(event) => event.unit,
+                        BattleEvent::CardReturnToHand(event) => event.card,
+                        BattleEvent::CardExile(event) => event.card,
+                        BattleEvent::CardEnterGrave(event) => event.card,
+                        BattleEvent::CardLeaveGrave(event) => event.card,
+                        BattleEvent::TriggerLoop(_) => ability_unit,
+                        BattleEvent::TurnTimeout(event) => character(event.player),
+                        BattleEvent::UnitMove(event) => event.unit,
+                        BattleEvent::UnitHeal(event) => event.source,
+                        BattleEvent::UnitEnterCell(event) => event.unit,
+                        BattleEvent::PlayerEliminated(event) => character(event.player),
+                    };
+                    let trigger_player = match &s.event {
+                        BattleEvent::PreTurnStart(event) => event.player,
+                        BattleEvent::TurnStart(event) => event.player,
+                        BattleEvent::TurnEnd(event) => event.player,
+                        BattleEvent::PostTurnEnd(event) => event.player,
+                        BattleEvent::PlayerChange(event) => event.next_player,
+                        BattleEvent::PlayerDraw(event) => event.player,
+                        BattleEvent::PlayerEndTurn(event) => event.player,
+                        BattleEvent::SummonUnit(event) => event.player,
+                        BattleEvent::PlayerEliminated(event) => event.player,
+                        BattleEvent::TurnTimeout(event) => event.player,
+                        _ => unit_owner(&mut unit_query, trigger_unit),
+                    };
+                    let action_player = match &s.event {
+                        BattleEvent::PreTurnStart(event) => event.player,
+                        BattleEvent::TurnStart(event) => event.player,
+                        BattleEvent::TurnEnd(event) => event.player,
+                        BattleEvent::PostTurnEnd(event) => event.player,
+                        BattleEvent::PlayerChange(event) => event.prev_player,
+                        BattleEvent::PlayerDraw(event) => event.player,
+                        BattleEvent::PlayerEndTurn(event) => event.player,
+                        BattleEvent::SummonUnit(event) => event.player,
+                        BattleEvent::PlayerEliminated(event) => event.player,
+                        BattleEvent::TurnTimeout(event) => event.player,
+                        _ => unit_owner(&mut unit_query, action_unit),
+                    };
+                    let ability_player = unit_owner(&mut unit_query, ability_unit);
+                    let var = |u: &UnitVar| match u {
+                        UnitVar::TriggerUnit => trigger_unit,
+                        UnitVar::ActionUnit => action_unit,
+                        UnitVar::AbilityUnit => ability_unit,
+                        UnitVar::TriggerPlayer => trigger_player,
+                        UnitVar::ActionPlayer => action_player,
+                        UnitVar::AbilityPlayer => ability_player,
                     };
                     if t.conditions.iter().all(|condition| match condition {
-                        TriggerCondition::UnitEq(u1, u2) => {
-                            let u1 = match u1 {
-                                UnitVar::TriggerUnit => trigger_unit,
-                                UnitVar::ActionUnit => action_unit,
-                                UnitVar::AbilityUnit => ability_unit,
-                            };
-                            let u2 = match u2 {
-                                UnitVar::TriggerUnit => trigger_unit,
-                                UnitVar::ActionUnit => action_unit,
-                                UnitVar::AbilityUnit => ability_unit,
-                            };
-                            u1 == u2
+                        TriggerCondition::UnitEq(u1, u2) => var(u1) == var(u2),
+                        TriggerCondition::UnitNe(u1, u2) => var(u1) != var(u2),
+                        TriggerCondition::SameTeam(u1, u2) => {
+                            team_of(&mut unit_query, &player_query, var(u1))
+                                == team_of(&mut unit_query, &player_query, var(u2))
+                        }
+                        TriggerCondition::OtherTeam(u1, u2) => {
+                            team_of(&mut unit_query, &player_query, var(u1))
+                                != team_of(&mut unit_query, &player_query, var(u2))
                         }
                     }) {
                         info!("Ability trigger: {:?}", ability);
-                        stacks.push(BattleFrame {
-                            event: BattleEvent::UnitStartAbility(UnitStartAbility {
+                        stacks.push(BattleFrame::new(BattleEvent::UnitStartAbility(
+                            UnitStartAbility {
                                 ability: entity,
                                 source: ability_unit,
                                 target: trigger_unit,
-                            }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
+                            },
+                        )));
                         // handle new frame
                         continue 'outer;
                     }
@@ -122,11 +269,43 @@ pub fn update(
                 }
             }
 
+            // give the other player a chance to respond
+            if battle.response_window && !s.responded {
+                if let BattleEvent::UnitStartAbility(event) = s.event {
+                    // terrain can not be responded to
+                    let terrain = ability_query.get(event.ability).is_err();
+                    let active = battle.players[battle.current_player_index];
+                    let active_team = player_query.get(active).map(|p| p.team).ok();
+                    // the next opponent in turn order answers
+                    let responder = (1..battle.players.len())
+                        .map(|i| {
+                            battle.players[(battle.current_player_index + i) % battle.players.len()]
+                        })
+                        .filter(|player| !battle.eliminated.contains(player))
+                        .find(|&player| {
+                            player_query.get(player).map(|p| p.team).ok() != active_team
+                        });
+                    if let (false, Some(responder)) = (terrain, responder) {
+                        if unit_owner(&mut unit_query, event.source) == active {
+                            s.responded = true;
+                            battle.response = Some(ResponseWindow {
+                                player: responder,
+                                ability: event,
+                            });
+                            info!("Response window: {:?}", battle.response);
+                            return;
+                        }
+                    }
+                }
+            }
+
             // start effect
             let s = stacks.pop().unwrap();
             let can_run = true;
             if can_run {
                 info!("Run Frame: {:?}", s);
+                battle.frames_resolved += 1;
+                battle.log.push(s.event);
                 // add animation
                 match s.event {
                     BattleEvent::PreTurnStart(event) => {
@@ -134,16 +313,14 @@ pub fn update(
                         ev_pre_turn_start.send(event);
                     }
                     BattleEvent::TurnStart(event) => {
+                        battle.turn += 1;
+                        battle.turn_time_left = battle.turn_time.unwrap_or_default();
                         // trigger ability
                         // Draw
-                        stacks.push(BattleFrame {
-                            event: BattleEvent::PlayerDraw(PlayerDraw {
-                                player: event.player,
-                                c: 1,
-                            }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
+                        stacks.push(BattleFrame::new(BattleEvent::PlayerDraw(PlayerDraw {
+                            player: event.player,
+                            c: 1,
+                        })));
                     }
                     BattleEvent::TurnEnd(_) => {
                         // trigger ability
@@ -154,12 +331,21 @@ pub fn update(
                     }
                     BattleEvent::PlayerChange(event) => {
                         battle.current_player_index = event.next_index;
+                        let limit = battle.win_conditions.iter().find_map(|c| match c {
+                            WinCondition::TurnLimit(limit) => Some(*limit),
+                            _ => None,
+                        });
+                        if let Some(limit) = limit.filter(|&limit| battle.turn >= limit) {
+                            battle.end(&player_query, None, WinCondition::TurnLimit(limit));
+                        }
                     }
                     BattleEvent::PlayerDraw(event) => {
                         ev_player_draw.send(event);
                     }
                     BattleEvent::PlayerEndTurn(_) => {
-                        battle.stage = BattleFlow::TurnEnd;
+                        if battle.stage != BattleFlow::BattleEnd {
+                            battle.stage = BattleFlow::TurnEnd;
+                        }
                     }
                     BattleEvent::UnitHurt(event) => {
                         // change hp
@@ -170,97 +356,203 @@ pub fn update(
                         // unit already die
                         // move it to grave
                         ev_unit_die.send(event);
+                        let killer = team_of(&mut unit_query, &player_query, event.killer);
+                        let dead = team_of(&mut unit_query, &player_query, event.dead);
+                        let target = battle.win_conditions.iter().find_map(|c| match c {
+                            WinCondition::DestroyUnits(target) => Some(*target),
+                            _ => None,
+                        });
+                        if let (Some(killer), Some(target)) = (killer, target) {
+                            if Some(killer) != dead {
+                                let kills = battle.kills.entry(killer).or_default();
+                                *kills += 1;
+                                if *kills >= target {
+                                    battle.end(
+                                        &player_query,
+                                        Some(killer),
+                                        WinCondition::DestroyUnits(target),
+                                    );
+                                }
+                            }
+                        }
                     }
                     BattleEvent::UnitCombat(event) => {
                         // reverse order
+                        let terrain_def = |unit: Entity| {
+                            position_query
+                                .get(unit)
+                                .ok()
+                                .and_then(|pos| {
+                                    cell_query.iter().find(|(_, cell)| cell.same_cell(pos))
+                                })
+                                .map(|(cell, _)| cell.cell_type.def_bonus())
+                                .unwrap_or(0)
+                        };
                         let (_, attacker) = unit_query.get_mut(event.source).unwrap();
                         let att_atk = attacker.atk;
-                        let att_def = attacker.def;
+                        let att_def = attacker.def + terrain_def(event.source);
                         let (_, victim) = unit_query.get_mut(event.target).unwrap();
                         let vic_atk = victim.atk;
-                        let vic_def = victim.def;
-                        stacks.push(BattleFrame {
-                            event: BattleEvent::UnitHurt(UnitHurt {
-                                source: event.target,
-                                target: event.source,
-                                value: (vic_atk - att_def).max(0),
-                            }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
-                        stacks.push(BattleFrame {
-                            event: BattleEvent::UnitHurt(UnitHurt {
-                                source: event.source,
-                                target: event.target,
-                                value: (att_atk - vic_def).max(0),
-                            }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
+                        let vic_def = victim.def + terrain_def(event.target);
+                        stacks.push(BattleFrame::new(BattleEvent::UnitHurt(UnitHurt {
+                            source: event.target,
+                            target: event.source,
+                            value: (vic_atk - att_def).max(0),
+                        })));
+                        stacks.push(BattleFrame::new(BattleEvent::UnitHurt(UnitHurt {
+                            source: event.source,
+                            target: event.target,
+                            value: (att_atk - vic_def).max(0),
+                        })));
                     }
                     BattleEvent::UnitStartAbility(event) => {
                         // cost AP
-                        let (_, a, _) = ability_query.get(event.ability).unwrap();
+                        let a =
+                            ability_instance(&ability_query, &cell_ability_query, event.ability);
                         let (_, mut u) = unit_query.get_mut(event.source).unwrap();
                         u.ap -= a.ap;
                         // to ability start
-                        stacks.push(BattleFrame {
-                            event: BattleEvent::AbilityStart(AbilityStart {
-                                ability: event.ability,
-                                source: event.source,
-                                target: event.target,
-                            }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
+                        stacks.push(BattleFrame::new(BattleEvent::AbilityStart(AbilityStart {
+                            ability: event.ability,
+                            source: event.source,
+                            target: event.target,
+                        })));
                     }
                     BattleEvent::AbilityStart(event) => {
-                        let (_, ability, _) = ability_query.get(event.ability).unwrap();
+                        let ability =
+                            ability_instance(&ability_query, &cell_ability_query, event.ability);
+                        let team = team_of(&mut unit_query, &player_query, event.source)
+                            .unwrap_or_default();
+                        let units: Vec<_> = unit_query
+                            .iter_mut()
+                            .filter_map(|(entity, unit)| {
+                                position_query
+                                    .get(entity)
+                                    .ok()
+                                    .filter(|pos| pos.position_type == PositionType::Board)
+                                    .map(|pos| BoardUnit::new(entity, &unit, pos))
+                            })
+                            .collect();
                         let mut events: Vec<_> = ability
                             .effects
                             .iter()
-                            .map(|(t, e)| match e {
-                                AbilityEffect::Attack => BattleEvent::UnitCombat(UnitCombat {
-                                    source: event.source,
-                                    target: event.target,
-                                }),
-                                AbilityEffect::Damage(damage) => BattleEvent::UnitHurt(UnitHurt {
-                                    source: match t {
-                                        EffectTarget::Target => event.target,
-                                        EffectTarget::Source => event.source,
-                                        EffectTarget::AllEnemy => todo!(),
-                                        EffectTarget::AllAlliance => todo!(),
-                                        EffectTarget::AllEnemyExceptTarget => todo!(),
-                                        EffectTarget::AllAllianceExceptTarget => todo!(),
+                            .flat_map(|(t, e)| {
+                                effect_targets(t, &event, team, &units).into_iter().map(
+                                    move |target| match e {
+                                        AbilityEffect::Attack => {
+                                            BattleEvent::UnitCombat(UnitCombat {
+                                                source: event.source,
+                                                target,
+                                            })
+                                        }
+                                        AbilityEffect::Damage(damage) => {
+                                            BattleEvent::UnitHurt(UnitHurt {
+                                                source: event.source,
+                                                target,
+                                                value: *damage,
+                                            })
+                                        }
+                                        AbilityEffect::Heal(value) => {
+                                            BattleEvent::UnitHeal(UnitHeal {
+                                                source: event.source,
+                                                target,
+                                                value: *value,
+                                            })
+                                        }
+                                        AbilityEffect::SoulDrain(_) => todo!(),
+                                        AbilityEffect::Reflection => todo!(),
+                                        AbilityEffect::Curse(_) => todo!(),
+                                        AbilityEffect::Charm(_) => todo!(),
+                                        AbilityEffect::Revive(hp) => {
+                                            BattleEvent::UnitRevive(UnitRevive {
+                                                unit: target,
+                                                hp: *hp,
+                                            })
+                                        }
+                                        AbilityEffect::ReturnToHand => {
+                                            BattleEvent::CardReturnToHand(CardReturnToHand {
+                                                card: target,
+                                            })
+                                        }
+                                        AbilityEffect::Exile => {
+                                            BattleEvent::CardExile(CardExile { card: target })
+                                        }
                                     },
-                                    target: match t {
-                                        EffectTarget::Target => event.target,
-                                        EffectTarget::Source => event.source,
-                                        EffectTarget::AllEnemy => todo!(),
-                                        EffectTarget::AllAlliance => todo!(),
-                                        EffectTarget::AllEnemyExceptTarget => todo!(),
-                                        EffectTarget::AllAllianceExceptTarget => todo!(),
-                                    },
-                                    value: *damage,
-                                }),
-                                AbilityEffect::Heal(_) => todo!(),
-                                AbilityEffect::SoulDrain(_) => todo!(),
-                                AbilityEffect::Reflection => todo!(),
-                                AbilityEffect::Curse(_) => todo!(),
-                                AbilityEffect::Charm(_) => todo!(),
-                            })
-                            .map(|event| BattleFrame {
-                                event,
-                                on_stacks: Default::default(),
-                                started: false,
+                                )
                             })
+                            .map(BattleFrame::new)
                             .collect();
                         events.reverse();
                         stacks.append(&mut events);
                     }
                     BattleEvent::SummonUnit(event) => {
+                        stacks.push(BattleFrame::new(BattleEvent::UnitEnterCell(
+                            UnitEnterCell {
+                                unit: event.card,
+                                position: event.position,
+                            },
+                        )));
                         ev_summon_unit.send(event);
                     }
+                    BattleEvent::UnitRevive(event) => {
+                        ev_unit_revive.send(event);
+                    }
+                    BattleEvent::CardReturnToHand(event) => {
+                        ev_card_return_to_hand.send(event);
+                    }
+                    BattleEvent::CardExile(event) => {
+                        ev_card_exile.send(event);
+                    }
+                    BattleEvent::CardEnterGrave(_) => {
+                        // trigger ability
+                    }
+                    BattleEvent::CardLeaveGrave(_) => {
+                        // trigger ability
+                    }
+                    BattleEvent::TriggerLoop(_) | BattleEvent::TurnTimeout(_) => {
+                        // log only
+                    }
+                    BattleEvent::UnitMove(event) => {
+                        // cost AP
+                        let (_, mut u) = unit_query.get_mut(event.unit).unwrap();
+                        u.ap -= MOVE_AP_COST;
+                        stacks.push(BattleFrame::new(BattleEvent::UnitEnterCell(
+                            UnitEnterCell {
+                                unit: event.unit,
+                                position: event.to,
+                            },
+                        )));
+                        ev_unit_move.send(event);
+                    }
+                    BattleEvent::UnitHeal(event) => {
+                        ev_unit_heal.send(event);
+                    }
+                    BattleEvent::UnitEnterCell(_) => {
+                        // trigger terrain
+                    }
+                    BattleEvent::PlayerEliminated(event) => {
+                        battle.eliminated.push(event.player);
+                        let mut teams: Vec<_> = battle
+                            .players
+                            .iter()
+                            .filter(|player| !battle.eliminated.contains(player))
+                            .filter_map(|&player| player_query.get(player).ok())
+                            .map(|player| player.team)
+                            .collect();
+                        teams.sort_unstable();
+                        teams.dedup();
+                        if teams.len() <= 1 {
+                            // nobody left is a draw
+                            battle.end(&player_query, teams.first().copied(), event.reason);
+                        } else if battle.players[battle.current_player_index] == event.player
+                            && battle.stage != BattleFlow::BattleEnd
+                        {
+                            battle.stage = BattleFlow::TurnEnd;
+                        }
+                    }
+                }
+                if battle.stacks.is_empty() {
+                    battle.frames_resolved = 0;
                 }
                 // limit 1 event pre frame
                 return;
@@ -269,16 +561,257 @@ pub fn update(
     }
 }
 
+/// Owner of a unit, or the entity itself when it is a player
+fn unit_owner(unit_query: &mut Query<(Entity, &mut UnitInstance)>, entity: Entity) -> Entity {
+    unit_query
+        .get_mut(entity)
+        .map(|(_, unit)| unit.owner)
+        .unwrap_or(entity)
+}
+
+/// Team of a unit or a player
+fn team_of(
+    unit_query: &mut Query<(Entity, &mut UnitInstance)>,
+    player_query: &Query<&PlayerInstance>,
+    entity: Entity,
+) -> Option<usize> {
+    unit_query
+        .get_mut(entity)
+        .map(|(_, unit)| unit.team)
+        .or_else(|_| player_query.get(entity).map(|player| player.team))
+        .ok()
+}
+
+/// Unit or terrain ability instance
+fn ability_instance<'a>(
+    ability_query: &'a Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
+    cell_ability_query: &'a Query<(Entity, &AbilityInstance, &CellRef)>,
+    entity: Entity,
+) -> &'a AbilityInstance {
+    ability_query
+        .get(entity)
+        .map(|(_, ability, _)| ability)
+        .or_else(|_| {
+            cell_ability_query
+                .get(entity)
+                .map(|(_, ability, _)| ability)
+        })
+        .unwrap()
+}
+
+/// Unit standing on a board cell
+fn cell_occupant(
+    unit_query: &mut Query<(Entity, &mut UnitInstance)>,
+    position_query: &Query<&Position, With<UnitInstance>>,
+    cell: &Position,
+) -> Option<Entity> {
+    unit_query
+        .iter_mut()
+        .map(|(entity, _)| entity)
+        .find(|&entity| {
+            position_query
+                .get(entity)
+                .map(|pos| pos.same_cell(cell))
+                .unwrap_or_default()
+        })
+}
+
+/// Units hit by one effect of an ability, `team` is the team of the source
+fn effect_targets(
+    t: &EffectTarget,
+    event: &AbilityStart,
+    team: usize,
+    units: &[BoardUnit],
+) -> Vec<Entity> {
+    let select = |f: &dyn Fn(&BoardUnit) -> bool| -> Vec<Entity> {
+        units
+            .iter()
+            .filter(|unit| f(unit))
+            .map(|unit| unit.entity)
+            .collect()
+    };
+    match t {
+        EffectTarget::Target => vec![event.target],
+        EffectTarget::Source => vec![event.source],
+        EffectTarget::AllEnemy => select(&|unit| unit.team != team),
+        EffectTarget::AllAlliance => select(&|unit| unit.team == team),
+        EffectTarget::AllEnemyExceptTarget => {
+            select(&|unit| unit.team != team && unit.entity != event.target)
+        }
+        EffectTarget::AllAllianceExceptTarget => {
+            select(&|unit| unit.team == team && unit.entity != event.target)
+        }
+        EffectTarget::TargetNeighbours => neighbours(event.target, units),
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Battle {
     pub stage: BattleFlow,
     pub stacks: Vec<BattleFrame>,
     pub event_state: EventState,
     pub animations: Vec<Entity>,
+    /// Let the non-active player respond to abilities on the stack
+    pub response_window: bool,
+    pub response: Option<ResponseWindow>,
 
     pub players: Vec<Entity>,
     pub boards: Vec<Entity>,
     pub current_player_index: usize,
+    /// Players whose character card died, their turns are skipped
+    pub eliminated: Vec<Entity>,
+    pub win_conditions: Vec<WinCondition>,
+    /// Turns started so far, over all players
+    pub turn: u32,
+    /// Enemy units destroyed by each team
+    pub kills: HashMap<usize, usize>,
+    pub result: Option<BattleResult>,
+    /// Seconds per turn, the turn ends by itself when it runs out
+    pub turn_time: Option<f32>,
+    pub turn_time_left: f32,
+
+    pub limits: BattleLimits,
+    /// Frames resolved since the stack was last empty or a player acted
+    pub frames_resolved: usize,
+    /// Every resolved frame, in order
+    pub log: Vec<BattleEvent>,
+    /// Player actions that were accepted, in order
+    pub actions: Vec<(Entity, PlayerAction)>,
+    /// Encounter and decks the battle started with, the actions are added when it is recorded
+    pub replay: Option<Replay>,
+}
+
+/// How a battle ended
+#[derive(Debug, Clone)]
+pub struct BattleResult {
+    /// Winning team, `None` for a draw
+    pub winner: Option<usize>,
+    pub reason: WinCondition,
+    pub turns: u32,
+    pub players: Vec<PlayerResult>,
+    /// Units that fought, filled when the battle is cleaned up
+    pub units: Vec<UnitResult>,
+    /// `Ability` templates used, the `Player` owning them and how many times
+    pub ability_uses: Vec<(Entity, Entity, i32)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerResult {
+    /// The `Player`, not the player instance
+    pub player_id: Entity,
+    pub name: String,
+    pub team: usize,
+    pub cards_played: usize,
+    /// The `Card`s summoned, each counted once
+    pub cards_used: Vec<Entity>,
+    /// Turns ended by the turn timer
+    pub timeouts: usize,
+    /// Reward
+    pub money: i32,
+}
+
+impl BattleResult {
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+
+    pub fn winners(&self) -> Vec<&PlayerResult> {
+        self.players
+            .iter()
+            .filter(|player| Some(player.team) == self.winner)
+            .collect()
+    }
+
+    pub fn losers(&self) -> Vec<&PlayerResult> {
+        self.players
+            .iter()
+            .filter(|player| self.winner.is_some() && Some(player.team) != self.winner)
+            .collect()
+    }
+}
+
+impl Battle {
+    /// Human player input goes to: the responder, the current player, or the first human
+    pub fn human_player(
+        &self,
+        controller_query: &Query<(Entity, &PlayerController)>,
+    ) -> Option<Entity> {
+        let is_human = |player: &Entity| {
+            controller_query
+                .get(*player)
+                .map(|(_, &controller)| controller == PlayerController::Player)
+                .unwrap_or(false)
+        };
+        self.response
+            .map(|window| window.player)
+            .filter(is_human)
+            .or_else(|| {
+                self.players
+                    .get(self.current_player_index)
+                    .copied()
+                    .filter(is_human)
+            })
+            .or_else(|| self.players.iter().copied().find(is_human))
+    }
+
+    /// Next player in turn order that is not eliminated
+    pub fn next_player_index(&self) -> usize {
+        (1..=self.players.len())
+            .map(|i| (self.current_player_index + i) % self.players.len())
+            .find(|&index| !self.eliminated.contains(&self.players[index]))
+            .unwrap_or(self.current_player_index)
+    }
+
+    /// Keep the first result, the battle ends once the stack is empty
+    pub fn end(
+        &mut self,
+        player_query: &Query<&PlayerInstance>,
+        winner: Option<usize>,
+        reason: WinCondition,
+    ) {
+        if self.result.is_some() {
+            return;
+        }
+        let players = self
+            .players
+            .iter()
+            .filter_map(|&player| player_query.get(player).ok())
+            .map(|player| PlayerResult {
+                player_id: player.player_id,
+                name: player.name.clone(),
+                team: player.team,
+                cards_played: 0,
+                cards_used: vec![],
+                timeouts: 0,
+                money: 0,
+            })
+            .collect();
+        self.result = Some(BattleResult {
+            winner,
+            reason,
+            turns: self.turn,
+            players,
+            units: vec![],
+            ability_uses: vec![],
+        });
+        info!("Battle result: {:?}", self.result);
+        self.stage = BattleFlow::BattleEnd;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BattleLimits {
+    pub max_stack_depth: usize,
+    pub max_frames_per_action: usize,
+}
+
+impl Default for BattleLimits {
+    fn default() -> Self {
+        Self {
+            max_stack_depth: 64,
+            max_frames_per_action: 512,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -299,9 +832,11 @@ pub fn summon_unit_system(
     mut query: Query<(Entity, &mut CardInstance, &mut Position)>,
 ) {
     for e in ev.iter() {
-        let (_, card, mut pos) = query.get_mut(e.card).unwrap();
+        let (_, _, mut pos) = query.get_mut(e.card).unwrap();
         let z = pos.z;
         *pos = e.position;
+        // whatever the action said, units on the board are seen by everyone
+        pos.face_up = true;
         query
             .iter_mut()
             .filter(|(_, _, pos)| {
@@ -316,6 +851,7 @@ pub fn summon_unit_system(
 pub fn player_draw_system(
     mut ev: EventReader<PlayerDraw>,
     mut query: Query<(Entity, &mut CardInstance, &mut Position)>,
+    mut battle: ResMut<Option<Battle>>,
 ) {
     for e in ev.iter() {
         // player.hands.get(index)
@@ -332,6 +868,22 @@ pub fn player_draw_system(
             })
             .collect::<Vec<_>>();
         decks.sort_by_key(|(_, _, pos)| pos.z);
+        if (decks.len() as i32) < e.c {
+            if let Some(battle) = battle.as_mut() {
+                if battle.win_conditions.contains(&WinCondition::DeckOut)
+                    && !battle.eliminated.contains(&e.player)
+                {
+                    battle
+                        .stacks
+                        .push(BattleFrame::new(BattleEvent::PlayerEliminated(
+                            PlayerEliminated {
+                                player: e.player,
+                                reason: WinCondition::DeckOut,
+                            },
+                        )));
+                }
+            }
+        }
         for (i, (_, _, mut pos)) in decks.into_iter().enumerate() {
             if (i as i32) < e.c {
                 pos.position_type = PositionType::Hand;
@@ -347,52 +899,245 @@ pub fn player_draw_system(
 pub fn unit_die_system(
     mut ev: EventReader<UnitDie>,
     mut query_set: QuerySet<(
-        Query<(Entity, &UnitInstance, &Position)>,
-        Query<(Entity, &mut UnitInstance, &mut Position)>,
-        Query<(Entity, &mut CardInstance, &mut Position)>,
         Query<(Entity, &CardInstance, &Position)>,
+        Query<(Entity, &mut CardInstance, &mut Position)>,
     )>,
+    unit_query: Query<&UnitInstance>,
     player_query: Query<(Entity, &PlayerInstance, &PlayerController)>,
     mut battle: ResMut<Option<Battle>>,
 ) {
     for e in ev.iter() {
-        let (_, unit, pos) = query_set.q1_mut().get_mut(e.dead).unwrap();
-        let player_id = unit.owner;
-        let pid = pos.player_id;
-        let px = pos.x;
-        let py = pos.y;
+        let player_id = match unit_query.get(e.dead) {
+            Ok(unit) => unit.owner,
+            Err(_) => continue,
+        };
         let z = query_set
-            .q3()
+            .q0()
             .iter()
             .filter(|(_, _, pos)| {
                 pos.player_id == player_id && pos.position_type == PositionType::Grave
             })
             .count();
-        query_set
-            .q2_mut()
-            .iter_mut()
-            .filter(|(_, _, p)| {
-                p.position_type == PositionType::Board
-                    && p.player_id == pid
-                    && p.x == px
-                    && p.y == py
-            })
-            .for_each(|(_, _, mut pos)| {
+        // only the dead card, whatever else is on its cell stays
+        if let Ok((card, _, mut pos)) = query_set.q1_mut().get_mut(e.dead) {
+            if pos.position_type == PositionType::Board {
                 pos.position_type = PositionType::Grave;
-                pos.z = pos.z + z as i32;
-            });
+                pos.z += z as i32;
+                battle.as_mut().unwrap().stacks.push(BattleFrame::new(
+                    BattleEvent::CardEnterGrave(CardEnterGrave { card }),
+                ));
+            }
+        }
 
-        let end = battle
-            .as_mut()
-            .unwrap()
-            .players
+        let battle = battle.as_mut().unwrap();
+        if let Some(&player) = battle.players.iter().find(|&&id| {
+            player_query
+                .get(id)
+                .map(|(_, player, _)| player.player_card_instance_id == e.dead)
+                .unwrap_or_default()
+        }) {
+            if !battle.eliminated.contains(&player)
+                && battle
+                    .win_conditions
+                    .contains(&WinCondition::CharacterDeath)
+            {
+                battle
+                    .stacks
+                    .push(BattleFrame::new(BattleEvent::PlayerEliminated(
+                        PlayerEliminated {
+                            player,
+                            reason: WinCondition::CharacterDeath,
+                        },
+                    )));
+            }
+        }
+    }
+}
+
+pub fn unit_revive_system(
+    mut ev: EventReader<UnitRevive>,
+    mut query: Query<(Entity, &mut CardInstance, &mut Position), Without<Cell>>,
+    mut unit_query: Query<&mut UnitInstance>,
+    template_query: Query<&Unit>,
+    cell_query: Query<(&Cell, &Position)>,
+    mut battle: ResMut<Option<Battle>>,
+) {
+    for e in ev.iter() {
+        let old_pos = match query.get_mut(e.unit) {
+            Ok((_, _, pos)) => *pos,
+            Err(_) => {
+                info!("Revive failed, not a card: {:?}", e.unit);
+                continue;
+            }
+        };
+        if old_pos.position_type != PositionType::Grave {
+            info!("Revive failed, not in grave: {:?}", e.unit);
+            continue;
+        }
+        let mut cells: Vec<_> = cell_query
             .iter()
-            .map(|&id| player_query.get(id).unwrap())
-            .map(|(_, player, _)| query_set.q3().get(player.player_card_instance_id).unwrap())
-            .any(|(card, _, _)| card == e.dead);
-        if end {
-            battle.as_mut().unwrap().stage = BattleFlow::BattleEnd;
+            .filter(|(cell, _)| cell.cell_type.is_open())
+            .map(|(_, cell)| cell)
+            .filter(|cell| cell.player_id == old_pos.player_id)
+            .filter(|cell| {
+                !query.iter_mut().any(|(_, _, pos)| {
+                    pos.position_type == PositionType::Board
+                        && pos.player_id == cell.player_id
+                        && pos.x == cell.x
+                        && pos.y == cell.y
+                })
+            })
+            .collect();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        let cell = match cells.first() {
+            Some(&&cell) => cell,
+            None => {
+                info!("Revive failed, no free cell: {:?}", e.unit);
+                continue;
+            }
+        };
+
+        if let Ok((_, _, mut pos)) = query.get_mut(e.unit) {
+            *pos = cell;
         }
+        close_gap(&mut query, old_pos);
+        // back as a fresh unit, with the hp of the revive
+        if let Ok(mut unit) = unit_query.get_mut(e.unit) {
+            if let Ok(template) = template_query.get(unit.unit_id) {
+                unit.atk = template.atk;
+                unit.def = template.def;
+            }
+            unit.hp = e.hp;
+            unit.ap = 0;
+            unit.states = Default::default();
+        }
+        let stacks = &mut battle.as_mut().unwrap().stacks;
+        stacks.push(BattleFrame::new(BattleEvent::UnitEnterCell(
+            UnitEnterCell {
+                unit: e.unit,
+                position: cell,
+            },
+        )));
+        stacks.push(BattleFrame::new(BattleEvent::CardLeaveGrave(
+            CardLeaveGrave {
+                card: e.unit,
+                to: PositionType::Board,
+            },
+        )));
+    }
+}
+
+pub fn card_return_to_hand_system(
+    mut ev: EventReader<CardReturnToHand>,
+    mut query: Query<(Entity, &mut CardInstance, &mut Position), Without<Cell>>,
+    mut unit_query: Query<&mut UnitInstance>,
+    template_query: Query<&Unit>,
+    mut battle: ResMut<Option<Battle>>,
+) {
+    for e in ev.iter() {
+        let old_pos = *query.get_mut(e.card).unwrap().2;
+        let z = query
+            .iter_mut()
+            .filter(|(_, _, pos)| {
+                pos.player_id == old_pos.player_id && pos.position_type == PositionType::Hand
+            })
+            .count();
+        *query.get_mut(e.card).unwrap().2 = Position {
+            x: 0,
+            y: 0,
+            z: z as i32,
+            face_up: false,
+            player_id: old_pos.player_id,
+            position_type: PositionType::Hand,
+        };
+        close_gap(&mut query, old_pos);
+
+        // back to hand as a fresh card
+        if let Ok(mut unit) = unit_query.get_mut(e.card) {
+            let template = template_query.get(unit.unit_id).unwrap();
+            unit.hp = template.hp;
+            unit.atk = template.atk;
+            unit.def = template.def;
+            unit.ap = 0;
+            unit.states = Default::default();
+        }
+        if old_pos.position_type == PositionType::Grave {
+            battle
+                .as_mut()
+                .unwrap()
+                .stacks
+                .push(BattleFrame::new(BattleEvent::CardLeaveGrave(
+                    CardLeaveGrave {
+                        card: e.card,
+                        to: PositionType::Hand,
+                    },
+                )));
+        }
+    }
+}
+
+pub fn card_exile_system(
+    mut ev: EventReader<CardExile>,
+    mut query: Query<(Entity, &mut CardInstance, &mut Position), Without<Cell>>,
+    mut battle: ResMut<Option<Battle>>,
+) {
+    for e in ev.iter() {
+        let old_pos = *query.get_mut(e.card).unwrap().2;
+        if old_pos.position_type == PositionType::Exile {
+            continue;
+        }
+        let z = query
+            .iter_mut()
+            .filter(|(_, _, pos)| {
+                pos.player_id == old_pos.player_id && pos.position_type == PositionType::Exile
+            })
+            .count();
+        *query.get_mut(e.card).unwrap().2 = Position {
+            x: 0,
+            y: 0,
+            z: z as i32,
+            face_up: true,
+            player_id: old_pos.player_id,
+            position_type: PositionType::Exile,
+        };
+        close_gap(&mut query, old_pos);
+        if old_pos.position_type == PositionType::Grave {
+            battle
+                .as_mut()
+                .unwrap()
+                .stacks
+                .push(BattleFrame::new(BattleEvent::CardLeaveGrave(
+                    CardLeaveGrave {
+                        card: e.card,
+                        to: PositionType::Exile,
+                    },
+                )));
+        }
+    }
+}
+
+/// Shift down the cards stacked above a card that left a pile (deck, hand, grave, exile)
+fn close_gap(
+    query: &mut Query<(Entity, &mut CardInstance, &mut Position), Without<Cell>>,
+    old_pos: Position,
+) {
+    if old_pos.position_type == PositionType::Board {
+        return;
+    }
+    query
+        .iter_mut()
+        .filter(|(_, _, pos)| {
+            pos.player_id == old_pos.player_id
+                && pos.position_type == old_pos.position_type
+                && pos.z > old_pos.z
+        })
+        .for_each(|(_, _, mut pos)| pos.z -= 1);
+}
+
+pub fn unit_move_system(mut ev: EventReader<UnitMove>, mut query: Query<&mut Position>) {
+    for e in ev.iter() {
+        let mut pos = query.get_mut(e.unit).unwrap();
+        *pos = e.to;
     }
 }
 
@@ -409,6 +1154,22 @@ pub fn pre_turn_start_system(
     }
 }
 
+pub fn unit_heal_system(
+    mut ev: EventReader<UnitHeal>,
+    mut query: Query<(Entity, &mut UnitInstance)>,
+    template_query: Query<&Unit>,
+) {
+    for e in ev.iter() {
+        let (_, mut unit) = query.get_mut(e.target).unwrap();
+        // up to the hp of the unit template
+        let max_hp = template_query
+            .get(unit.unit_id)
+            .map(|u| u.hp)
+            .unwrap_or(unit.hp);
+        unit.hp = (unit.hp + e.value).min(max_hp).max(unit.hp);
+    }
+}
+
 pub fn unit_hurt_system(
     mut ev: EventReader<UnitHurt>,
     mut query: Query<(Entity, &mut UnitInstance)>,
@@ -418,88 +1179,19 @@ pub fn unit_hurt_system(
         let (_, mut unit) = query.get_mut(e.target).unwrap();
         unit.hp -= e.value;
         if unit.hp <= 0 {
-            battle.as_mut().unwrap().stacks.push(BattleFrame {
-                event: BattleEvent::UnitDie(UnitDie {
+            battle
+                .as_mut()
+                .unwrap()
+                .stacks
+                .push(BattleFrame::new(BattleEvent::UnitDie(UnitDie {
                     dead: e.target,
                     killer: e.source,
-                }),
-                on_stacks: Default::default(),
-                started: false,
-            });
+                })));
         }
     }
 }
 
-fn ability_system(
-    e: AbilityStart,
-    query: &mut Query<(Entity, &mut UnitInstance, &mut Position)>,
-    ability_query: &mut Query<(Entity, &mut AbilityInstance)>,
-) {
-    let result: Result<(), Box<dyn std::error::Error>> = (|| {
-        let owner_id = {
-            let (_, card, _) = query.get_mut(e.source)?;
-            card.owner
-        };
-
-        let (_, ability) = ability_query.get_mut(e.ability)?;
-        for (et, ee) in ability.effects.iter() {
-            let targets: Vec<Entity> = match et {
-                EffectTarget::Target => vec![e.target],
-                EffectTarget::Source => vec![e.source],
-                EffectTarget::AllEnemy => query
-                    .iter_mut()
-                    .filter(|(_, owner, _)| owner.owner != owner_id)
-                    .map(|(id, _, _)| id)
-                    .collect(),
-                EffectTarget::AllAlliance => query
-                    .iter_mut()
-                    .filter(|(_, o, _)| o.owner == owner_id)
-                    .map(|(id, _, _)| id)
-                    .collect(),
-                EffectTarget::AllEnemyExceptTarget => query
-                    .iter_mut()
-                    .filter(|(id, o, _)| o.owner != owner_id && *id != e.target)
-                    .map(|(id, _, _)| id)
-                    .collect(),
-                EffectTarget::AllAllianceExceptTarget => query
-                    .iter_mut()
-                    .filter(|(id, o, _)| o.owner == owner_id && *id != e.target)
-                    .map(|(id, _, _)| id)
-                    .collect(),
-            };
-
-            match ee {
-                AbilityEffect::Attack => {
-                    //
-                    todo!()
-                }
-                AbilityEffect::Damage(damage) => {
-                    for entity in targets {
-                        let (_, mut character, _) = query.get_mut(entity).unwrap();
-                        character.hp -= damage;
-                    }
-                }
-                AbilityEffect::Heal(value) => {
-                    for entity in targets {
-                        let (_, mut character, _) = query.get_mut(entity).unwrap();
-                        character.hp += value;
-                    }
-                }
-                AbilityEffect::SoulDrain(_) => {}
-                AbilityEffect::Reflection => {}
-                AbilityEffect::Curse(_) => {}
-                AbilityEffect::Charm(_) => {}
-            }
-        }
-        Ok(())
-    })();
-    match result {
-        Ok(_) => (),
-        Err(e) => println!("{:?}", e),
-    }
-}
-
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BattleFlow {
     BattleStart,
     PreTurnStart,
@@ -522,7 +1214,7 @@ impl Default for BattleFlow {
 pub fn flow_systme(
     mut battle: ResMut<Option<Battle>>,
     unit_query: Query<(Entity, &UnitInstance, &Position)>,
-    player_query: Query<(Entity, &PlayerInstance, &PlayerController)>,
+    player_query: Query<(Entity, &PlayerInstance, &PlayerController, &AiProfile)>,
     ability_query: Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
     mut app_state: ResMut<State<AppState>>,
 ) {
@@ -534,65 +1226,68 @@ pub fn flow_systme(
             BattleFlow::BattleStart => Some(BattleFlow::PreTurnStart),
             BattleFlow::PreTurnStart => {
                 let &player = game.players.get(game.current_player_index).unwrap();
-                game.stacks.push(BattleFrame {
-                    event: BattleEvent::PreTurnStart(PreTurnStart { player }),
-                    on_stacks: Default::default(),
-                    started: false,
-                });
+                game.stacks
+                    .push(BattleFrame::new(BattleEvent::PreTurnStart(PreTurnStart {
+                        player,
+                    })));
                 Some(BattleFlow::TurnStart)
             }
             BattleFlow::TurnStart => {
                 let &player = game.players.get(game.current_player_index).unwrap();
-                game.stacks.push(BattleFrame {
-                    event: BattleEvent::TurnStart(TurnStart { player }),
-                    on_stacks: Default::default(),
-                    started: false,
-                });
+                game.stacks
+                    .push(BattleFrame::new(BattleEvent::TurnStart(TurnStart {
+                        player,
+                    })));
                 Some(BattleFlow::PlayerAction)
             }
             BattleFlow::PlayerAction => {
                 // warn!("BattleFlow::PlayerAction need TODO!");
                 if let Some(&id) = game.players.get(game.current_player_index) {
                     // if player turn do nothing
-                    if player_query
-                        .get(id)
-                        .map(|(_, _, controller)| match controller {
-                            PlayerController::Player => false,
-                            PlayerController::Ai => true,
-                        })
-                        .unwrap_or_default()
-                    {
-                        game.stacks.push(BattleFrame {
-                            event: BattleEvent::PlayerEndTurn(PlayerEndTurn { player: id }),
-                            on_stacks: Default::default(),
-                            started: false,
-                        });
-                        for (enemy_unit, _, _) in unit_query
+                    let profile =
+                        player_query
+                            .get(id)
+                            .ok()
+                            .and_then(|(_, _, controller, &profile)| match controller {
+                                PlayerController::Player | PlayerController::Remote => None,
+                                PlayerController::Ai => Some(profile),
+                            });
+                    if let Some(profile) = profile {
+                        let units = board_units(unit_query.iter());
+                        let attack = unit_query
                             .iter()
-                            .find(|(_, unit, _)| unit.owner == id && unit.ap > 0)
-                        {
-                            if let Some((target_unit, _, _)) =
-                                unit_query.iter().find(|(_, unit, pos)| {
-                                    unit.owner != id && pos.position_type == PositionType::Board
+                            .filter(|(_, unit, _)| {
+                                profile != AiProfile::Passive && unit.owner == id && unit.ap > 0
+                            })
+                            .find_map(|(enemy_unit, _, _)| {
+                                let target_unit =
+                                    units.iter().map(|unit| unit.entity).find(|&target| {
+                                        can_target(
+                                            &TargetSelection::Reach,
+                                            enemy_unit,
+                                            target,
+                                            &units,
+                                        )
+                                    })?;
+                                let (ability, _, _) = ability_query.iter().find(|(_, a, u)| {
+                                    a.ability_data_id == AbilityDataId::Attack && u.0 == enemy_unit
+                                })?;
+                                Some(UnitStartAbility {
+                                    ability,
+                                    source: enemy_unit,
+                                    target: target_unit,
                                 })
-                            {
-                                if let Some((ability, _, _)) =
-                                    ability_query.iter().find(|(_, a, u)| {
-                                        a.ability_data_id == AbilityDataId::Attack
-                                            && u.0 == enemy_unit
-                                    })
-                                {
-                                    game.stacks.push(BattleFrame {
-                                        event: BattleEvent::UnitStartAbility(UnitStartAbility {
-                                            ability,
-                                            source: enemy_unit,
-                                            target: target_unit,
-                                        }),
-                                        on_stacks: Default::default(),
-                                        started: false,
-                                    });
-                                }
-                            }
+                            });
+                        // aggressive AI comes back here until no unit can attack
+                        if attack.is_none() || profile != AiProfile::Aggressive {
+                            game.stacks
+                                .push(BattleFrame::new(BattleEvent::PlayerEndTurn(
+                                    PlayerEndTurn { player: id },
+                                )));
+                        }
+                        if let Some(attack) = attack {
+                            game.stacks
+                                .push(BattleFrame::new(BattleEvent::UnitStartAbility(attack)));
                         }
                     }
                 }
@@ -601,21 +1296,22 @@ pub fn flow_systme(
             BattleFlow::TurnEnd => Some(BattleFlow::PostTurnEnd),
             BattleFlow::PostTurnEnd => Some(BattleFlow::PlayerChange),
             BattleFlow::PlayerChange => {
-                let next_index = (game.current_player_index + 1) % game.players.len();
+                let next_index = game.next_player_index();
                 let next_player = game.players[next_index];
-                game.stacks.push(BattleFrame {
-                    event: BattleEvent::PlayerChange(PlayerChange {
+                game.stacks
+                    .push(BattleFrame::new(BattleEvent::PlayerChange(PlayerChange {
+                        prev_player: game.players[game.current_player_index],
                         next_player,
                         next_index,
-                    }),
-                    on_stacks: Default::default(),
-                    started: false,
-                });
+                    })));
                 Some(BattleFlow::PreTurnStart)
             }
             BattleFlow::BattleEnd => {
-                println!("battle_end");
-                app_state.set(AppState::BattleEnd).unwrap();
+                // the battle server keeps the battle around on the end screen
+                if *app_state.current() != AppState::BattleEnd {
+                    info!("Battle over");
+                    app_state.set(AppState::BattleEnd).unwrap();
+                }
                 None
             }
         };
@@ -625,3 +1321,76 @@ pub fn flow_systme(
         }
     }
 }
+
+pub fn ai_response_system(
+    battle: Res<Option<Battle>>,
+    controller_query: Query<&PlayerController>,
+    unit_query: Query<(Entity, &UnitInstance, &Position)>,
+    ability_query: Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+) {
+    if let Some(window) = battle.as_ref().as_ref().and_then(|battle| battle.response) {
+        if controller_query
+            .get(window.player)
+            .map(|&controller| controller != PlayerController::Ai)
+            .unwrap_or(true)
+        {
+            return;
+        }
+        // use the first instant available, otherwise let it resolve
+        let units = board_units(unit_query.iter());
+        let action = ability_query
+            .iter()
+            .find_map(|(ability, a, u)| match &a.ability_type {
+                AbilityType::Instant(selection) => unit_query
+                    .get(u.0)
+                    .ok()
+                    .filter(|(_, unit, pos)| {
+                        unit.owner == window.player
+                            && unit.ap >= a.ap
+                            && pos.position_type == PositionType::Board
+                    })
+                    .map(|(source, _, _)| (source, window.target(selection, source)))
+                    .filter(|&(source, target)| can_target(selection, source, target, &units))
+                    .map(|(source, target)| PlayerAction::UseAbility {
+                        ability,
+                        source,
+                        target,
+                    }),
+                _ => None,
+            })
+            .unwrap_or(PlayerAction::Pass);
+        ev_player.send(PlayerActionEvent {
+            player_id: window.player,
+            action,
+        });
+    }
+}
+
+pub fn turn_timer_system(
+    time: Res<Time>,
+    mut battle: ResMut<Option<Battle>>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+) {
+    if let Some(battle) = battle.as_mut() {
+        if battle.turn_time.is_none()
+            || battle.stage != BattleFlow::PlayerAction
+            || battle.response.is_some()
+            || !battle.stacks.is_empty()
+        {
+            return;
+        }
+        battle.turn_time_left -= time.delta_seconds();
+        if battle.turn_time_left <= 0.0 {
+            let player = battle.players[battle.current_player_index];
+            battle.turn_time_left = battle.turn_time.unwrap_or_default();
+            battle
+                .log
+                .push(BattleEvent::TurnTimeout(TurnTimeout { player }));
+            ev_player.send(PlayerActionEvent {
+                player_id: player,
+                action: PlayerAction::EndTurn,
+            });
+        }
+    }
+}
@@ -1,26 +1,106 @@
 use bevy::prelude::*;
 
 use crate::game::{
-    components::{AbilityDataId, AbilityInstance, PlayerController, UnitInstance, UnitInstanceRef},
+    components::{
+        AbilityDataId, AbilityInstance, AbilityType, CardInstance, Cell, Position, PositionType,
+        UnitInstance, UnitInstanceRef, WinCondition,
+    },
     events::{
-        BattleEvent, PlayerAction, PlayerActionEvent, PlayerEndTurn, SummonUnit, UnitStartAbility,
+        BattleEvent, PlayerAction, PlayerActionEvent, PlayerEliminated, PlayerEndTurn, SummonUnit,
+        UnitMove, UnitStartAbility,
     },
 };
 
-use super::{battle::BattleFrame, Battle, BattleFlow};
+use super::{
+    battle::BattleFrame,
+    battle_rule::{board_units, can_target},
+    Battle, BattleFlow, MOVE_AP_COST,
+};
 
 pub fn player_event_system(
     mut ev_player: EventReader<PlayerActionEvent>,
     mut game: ResMut<Option<Battle>>,
     query: Query<&UnitInstance>,
-    player_query: Query<(Entity, &PlayerController)>,
     ability_query: Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
+    card_query: Query<(Entity, &CardInstance, &Position)>,
+    cell_query: Query<(&Cell, &Position)>,
+    unit_query: Query<(Entity, &UnitInstance, &Position)>,
 ) {
     if let Some(game) = game.as_mut() {
-        if !game.stacks.is_empty() {
-            return;
-        }
+        let resolving = !game.stacks.is_empty();
         for ev in ev_player.iter() {
+            // allowed at any time, even while the stack resolves
+            if let PlayerAction::Surrender = ev.action {
+                if game.win_conditions.contains(&WinCondition::Surrender)
+                    && game.players.contains(&ev.player_id)
+                    && !game.eliminated.contains(&ev.player_id)
+                {
+                    if game.response.map(|window| window.player) == Some(ev.player_id) {
+                        game.response = None;
+                    }
+                    game.stacks
+                        .push(BattleFrame::new(BattleEvent::PlayerEliminated(
+                            PlayerEliminated {
+                                player: ev.player_id,
+                                reason: WinCondition::Surrender,
+                            },
+                        )));
+                    game.actions.push((ev.player_id, ev.action));
+                }
+                continue;
+            }
+            if let Some(window) = game.response {
+                if ev.player_id != window.player {
+                    continue;
+                }
+                match ev.action {
+                    PlayerAction::UseAbility {
+                        ability,
+                        source,
+                        target,
+                    } => {
+                        let units = board_units(unit_query.iter());
+                        let instant = ability_query
+                            .get(ability)
+                            .map(|(_, a, u)| match &a.ability_type {
+                                AbilityType::Instant(selection) => {
+                                    u.0 == source
+                                        && query
+                                            .get(source)
+                                            .map(|unit| {
+                                                unit.owner == window.player && unit.ap >= a.ap
+                                            })
+                                            .unwrap_or_default()
+                                        && can_target(selection, source, target, &units)
+                                }
+                                _ => false,
+                            })
+                            .unwrap_or_default();
+                        if !instant {
+                            continue;
+                        }
+                        game.stacks.push(BattleFrame {
+                            responded: true,
+                            ..BattleFrame::new(BattleEvent::UnitStartAbility(UnitStartAbility {
+                                ability,
+                                source,
+                                target,
+                            }))
+                        });
+                        game.response = None;
+                        game.actions.push((ev.player_id, ev.action));
+                    }
+                    PlayerAction::Pass => {
+                        game.response = None;
+                        game.actions.push((ev.player_id, ev.action));
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            if resolving {
+                continue;
+            }
             if game.players[game.current_player_index] != ev.player_id {
                 continue;
             }
@@ -30,60 +110,133 @@ pub fn player_event_system(
                     continue;
                 }
             }
+            let frames = game.stacks.len();
             match ev.action {
                 PlayerAction::EndTurn => {
-                    game.stacks.push(BattleFrame {
-                        event: BattleEvent::PlayerEndTurn(PlayerEndTurn {
-                            player: ev.player_id,
-                        }),
-                        on_stacks: Default::default(),
-                        started: false,
-                    });
+                    game.stacks
+                        .push(BattleFrame::new(BattleEvent::PlayerEndTurn(
+                            PlayerEndTurn {
+                                player: ev.player_id,
+                            },
+                        )));
                 }
                 PlayerAction::UseAbility {
                     ability,
                     source,
                     target,
                 } => {
-                    // add checking...
-                    game.stacks.push(BattleFrame {
-                        event: BattleEvent::UnitStartAbility(UnitStartAbility {
-                            ability,
-                            source,
-                            target,
-                        }),
-                        on_stacks: Default::default(),
-                        started: false,
-                    });
+                    let units = board_units(unit_query.iter());
+                    let can_use = ability_query
+                        .get(ability)
+                        .map(|(_, a, u)| {
+                            let selection = match &a.ability_type {
+                                AbilityType::Active(selection) => selection,
+                                AbilityType::Instant(selection) => selection,
+                                AbilityType::Trigger(_) => return false,
+                            };
+                            u.0 == source
+                                && query
+                                    .get(source)
+                                    .map(|unit| unit.owner == ev.player_id && unit.ap >= a.ap)
+                                    .unwrap_or_default()
+                                && can_target(selection, source, target, &units)
+                        })
+                        .unwrap_or_default();
+                    if can_use {
+                        game.stacks
+                            .push(BattleFrame::new(BattleEvent::UnitStartAbility(
+                                UnitStartAbility {
+                                    ability,
+                                    source,
+                                    target,
+                                },
+                            )));
+                    }
                 }
                 PlayerAction::Attack { source, target } => {
-                    // add checking...
-                    let character = query.get(source).unwrap();
-                    if character.ap > 0 {
-                        if let Some((ability, _, _)) = ability_query.iter().find(|(_, a, u)| {
+                    let character = match query.get(source) {
+                        Ok(character) => character,
+                        Err(_) => continue,
+                    };
+                    if character.owner == ev.player_id && character.ap > 0 {
+                        if let Some((ability, a, _)) = ability_query.iter().find(|(_, a, u)| {
                             a.ability_data_id == AbilityDataId::Attack && u.0 == source
                         }) {
-                            game.stacks.push(BattleFrame {
-                                event: BattleEvent::UnitStartAbility(UnitStartAbility {
-                                    ability,
-                                    source,
-                                    target,
-                                }),
-                                on_stacks: Default::default(),
-                                started: false,
-                            });
+                            let units = board_units(unit_query.iter());
+                            let in_reach = match &a.ability_type {
+                                AbilityType::Active(selection) => {
+                                    can_target(selection, source, target, &units)
+                                }
+                                _ => false,
+                            };
+                            if in_reach {
+                                game.stacks
+                                    .push(BattleFrame::new(BattleEvent::UnitStartAbility(
+                                        UnitStartAbility {
+                                            ability,
+                                            source,
+                                            target,
+                                        },
+                                    )));
+                            }
                         }
                     }
                 }
+                PlayerAction::Surrender => (),
+                PlayerAction::Pass => {
+                    // nothing to respond to
+                }
+                PlayerAction::Move { unit, to } => {
+                    let (character, from) = match (query.get(unit), card_query.get(unit)) {
+                        (Ok(character), Ok((_, _, &from))) => (character, from),
+                        _ => continue,
+                    };
+                    // one orthogonal step into an empty cell of your board
+                    let can_move = character.owner == ev.player_id
+                        && character.ap >= MOVE_AP_COST
+                        && from.is_adjacent(&to)
+                        && cell_query
+                            .iter()
+                            .any(|(cell, pos)| cell.cell_type.is_open() && pos.same_cell(&to))
+                        && !card_query.iter().any(|(_, _, pos)| pos.same_cell(&to));
+                    if can_move {
+                        game.stacks
+                            .push(BattleFrame::new(BattleEvent::UnitMove(UnitMove {
+                                unit,
+                                from,
+                                to,
+                            })));
+                    }
+                }
                 PlayerAction::SummonUnit { card, position } => {
-                    // checking
-                    game.stacks.push(BattleFrame {
-                        event: BattleEvent::SummonUnit(SummonUnit { card, position }),
-                        on_stacks: Default::default(),
-                        started: false,
-                    });
+                    // from your hand into an open, empty cell of your board
+                    let can_summon = card_query
+                        .get(card)
+                        .map(|(_, _, pos)| {
+                            pos.player_id == ev.player_id && pos.position_type == PositionType::Hand
+                        })
+                        .unwrap_or_default()
+                        && position.player_id == ev.player_id
+                        && cell_query.iter().any(|(cell, pos)| {
+                            cell.cell_type.is_open() && pos.same_cell(&position)
+                        })
+                        && !card_query
+                            .iter()
+                            .any(|(_, _, pos)| pos.same_cell(&position));
+                    if can_summon {
+                        game.stacks
+                            .push(BattleFrame::new(BattleEvent::SummonUnit(SummonUnit {
+                                player: ev.player_id,
+                                card,
+                                position,
+                            })));
+                    }
                 }
             }
+            if game.stacks.len() > frames {
+                game.frames_resolved = 0;
+                game.actions.push((ev.player_id, ev.action));
+            }
         }
     }
 }
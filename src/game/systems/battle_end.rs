@@ -0,0 +1,319 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game::{
+        components::{
+            Ability, AbilityInstance, Card, CardInstance, LocalPlayer, Player, PlayerInstance,
+            PlayerSlot, Unit, UnitInstance,
+        },
+        events::{BattleEvent, SaveGameEvent},
+    },
+    AppState,
+};
+
+use super::{Battle, BattleResult, CampaignProgress, PlayerResult, RunState, Spectator};
+
+pub const WIN_MONEY: i32 = 50;
+pub const DRAW_MONEY: i32 = 20;
+pub const LOSE_MONEY: i32 = 10;
+/// Every unit that dealt damage gets this much exp, plus its damage dealt
+pub const FIGHT_EXP: i32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct UnitResult {
+    /// The `Unit`, not the unit instance
+    pub unit_id: Entity,
+    pub name: String,
+    /// The `Player` owning the unit
+    pub player_id: Entity,
+    pub owner: String,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub deaths: i32,
+    pub exp: i32,
+    /// Filled when the rewards are given
+    pub level_ups: i32,
+}
+
+/// Fill the result with the stats of the battle log, before the battle entities are gone
+pub fn collect_battle_stats(
+    battle: &Battle,
+    result: &mut BattleResult,
+    unit_query: &Query<(Entity, &UnitInstance)>,
+    card_query: &Query<&CardInstance>,
+    ability_query: &Query<&AbilityInstance>,
+    player_query: &Query<&PlayerInstance>,
+) {
+    let mut units: Vec<(Entity, UnitResult)> = vec![];
+    for event in battle.log.iter() {
+        match event {
+            BattleEvent::UnitHurt(event) => {
+                let owner = |entity| unit_query.get(entity).map(|(_, unit)| unit.owner).ok();
+                // hurting your own units, a trap you stepped on included, earns nothing
+                let credited = owner(event.source) != owner(event.target);
+                if let Some(unit) = unit_result(&mut units, event.source, unit_query, player_query)
+                    .filter(|_| credited && event.value > 0)
+                {
+                    if unit.exp == 0 {
+                        unit.exp += FIGHT_EXP;
+                    }
+                    unit.damage_dealt += event.value;
+                    unit.exp += event.value;
+                }
+                if let Some(unit) = unit_result(&mut units, event.target, unit_query, player_query)
+                {
+                    unit.damage_taken += event.value;
+                }
+            }
+            BattleEvent::UnitCombat(event) => {
+                unit_result(&mut units, event.source, unit_query, player_query);
+                unit_result(&mut units, event.target, unit_query, player_query);
+            }
+            BattleEvent::AbilityStart(event) => {
+                // terrain abilities are used by cells, they have no owner and are skipped
+                let owner = unit_query
+                    .get(event.source)
+                    .ok()
+                    .and_then(|(_, unit)| player_query.get(unit.owner).ok());
+                if let (Ok(ability), Some(owner)) = (ability_query.get(event.ability), owner) {
+                    match result
+                        .ability_uses
+                        .iter_mut()
+                        .find(|(id, _, _)| *id == ability.ability_id)
+                    {
+                        Some((_, _, uses)) => *uses += 1,
+                        None => result
+                            .ability_uses
+                            .push((ability.ability_id, owner.player_id, 1)),
+                    }
+                }
+            }
+            BattleEvent::UnitDie(event) => {
+                if let Some(unit) = unit_result(&mut units, event.dead, unit_query, player_query) {
+                    unit.deaths += 1;
+                }
+            }
+            BattleEvent::SummonUnit(event) => {
+                if let Some(player) = player_result(result, event.player, player_query) {
+                    player.cards_played += 1;
+                    if let Ok(card) = card_query.get(event.card) {
+                        if !player.cards_used.contains(&card.card_id) {
+                            player.cards_used.push(card.card_id);
+                        }
+                    }
+                }
+            }
+            BattleEvent::TurnTimeout(event) => {
+                if let Some(player) = player_result(result, event.player, player_query) {
+                    player.timeouts += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    result.units = units.into_iter().map(|(_, unit)| unit).collect();
+    let winner = result.winner;
+    for player in result.players.iter_mut() {
+        player.money = match winner {
+            None => DRAW_MONEY,
+            Some(team) if team == player.team => WIN_MONEY,
+            Some(_) => LOSE_MONEY,
+        };
+    }
+}
+
+/// Result of a player instance
+fn player_result<'a>(
+    result: &'a mut BattleResult,
+    player: Entity,
+    player_query: &Query<&PlayerInstance>,
+) -> Option<&'a mut PlayerResult> {
+    let player_id = player_query.get(player).ok()?.player_id;
+    result
+        .players
+        .iter_mut()
+        .find(|player| player.player_id == player_id)
+}
+
+/// Stats of a unit, added on its first appearance in the log
+fn unit_result<'a>(
+    units: &'a mut Vec<(Entity, UnitResult)>,
+    entity: Entity,
+    unit_query: &Query<(Entity, &UnitInstance)>,
+    player_query: &Query<&PlayerInstance>,
+) -> Option<&'a mut UnitResult> {
+    let index = match units.iter().position(|(e, _)| *e == entity) {
+        Some(index) => index,
+        None => {
+            let (_, unit) = unit_query.get(entity).ok()?;
+            let player = player_query.get(unit.owner).ok()?;
+            units.push((
+                entity,
+                UnitResult {
+                    unit_id: unit.unit_id,
+                    name: unit.name.clone(),
+                    player_id: player.player_id,
+                    owner: player.name.clone(),
+                    damage_dealt: 0,
+                    damage_taken: 0,
+                    deaths: 0,
+                    exp: 0,
+                    level_ups: 0,
+                },
+            ));
+            units.len() - 1
+        }
+    };
+    Some(&mut units[index].1)
+}
+
+/// Only humans are rewarded, the `Player`s of AI slots and encounters stay as they are
+pub fn give_battle_rewards(
+    mut result: ResMut<Option<BattleResult>>,
+    mut player_query: Query<(&mut Player, Option<&LocalPlayer>, Option<&PlayerSlot>)>,
+    mut card_query: Query<&mut Card>,
+    mut unit_query: Query<&mut Unit>,
+    mut ability_query: Query<&mut Ability>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    spectator: Res<Spectator>,
+    run: Res<RunState>,
+) {
+    // the players of a watched battle are only borrowed from the replay,
+    // a run gives its rewards in `run_battle_end`
+    let rewarded = !spectator.is_active() && !run.in_battle;
+    if let Some(result) = result.as_mut() {
+        let winner = result.winner;
+        let mut humans = vec![];
+        for reward in result.players.iter_mut() {
+            let mut player = match player_query.get_mut(reward.player_id) {
+                Ok((player, local, slot))
+                    if rewarded && (local.is_some() || slot == Some(&PlayerSlot::SecondHuman)) =>
+                {
+                    player
+                }
+                _ => {
+                    reward.money = 0;
+                    continue;
+                }
+            };
+            humans.push(reward.player_id);
+            player.money += reward.money;
+            for &card in reward.cards_used.iter() {
+                if let Ok(mut card) = card_query.get_mut(card) {
+                    card.use_count += 1;
+                    match winner {
+                        None => (),
+                        Some(team) if team == reward.team => card.wins += 1,
+                        Some(_) => card.loses += 1,
+                    }
+                }
+            }
+        }
+        for reward in result.units.iter_mut() {
+            if !humans.contains(&reward.player_id) {
+                reward.exp = 0;
+                continue;
+            }
+            if let Ok(mut unit) = unit_query.get_mut(reward.unit_id) {
+                reward.level_ups = unit.gain_exp(reward.exp);
+                unit.dead_count += reward.deaths;
+            }
+        }
+        for &(ability, player, uses) in result.ability_uses.iter() {
+            if !humans.contains(&player) {
+                continue;
+            }
+            if let Ok(mut ability) = ability_query.get_mut(ability) {
+                ability.use_count += uses;
+            }
+        }
+        if !humans.is_empty() {
+            ev_save.send(SaveGameEvent);
+        }
+    }
+}
+
+pub fn battle_end_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut progress: ResMut<CampaignProgress>,
+    mut run: ResMut<RunState>,
+    result: Res<Option<BattleResult>>,
+    spectator: Res<Spectator>,
+) {
+    egui::Window::new("Battle End").show(egui_context.ctx(), |ui| {
+        match result.as_ref() {
+            Some(result) => {
+                let names = |players: Vec<&PlayerResult>| {
+                    players
+                        .iter()
+                        .map(|player| player.name.clone())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                if result.is_draw() {
+                    ui.heading("Draw");
+                } else {
+                    ui.heading(format!("Winner: {}", names(result.winners())));
+                    ui.label(format!("Loser: {}", names(result.losers())));
+                }
+                ui.label(format!("Reason: {:?}", result.reason));
+                ui.label(format!("Turns: {}", result.turns));
+                ui.separator();
+                egui::Grid::new("battle_end_players").show(ui, |ui| {
+                    ui.label("Player");
+                    ui.label("Cards played");
+                    ui.label("Timeouts");
+                    ui.label("Money");
+                    ui.end_row();
+                    for player in result.players.iter() {
+                        ui.label(&player.name);
+                        ui.label(player.cards_played.to_string());
+                        ui.label(player.timeouts.to_string());
+                        ui.label(format!("+{}", player.money));
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                egui::Grid::new("battle_end_units").show(ui, |ui| {
+                    ui.label("Unit");
+                    ui.label("Owner");
+                    ui.label("Damage dealt");
+                    ui.label("Damage taken");
+                    ui.label("Deaths");
+                    ui.label("Exp");
+                    ui.end_row();
+                    for unit in result.units.iter() {
+                        ui.label(&unit.name);
+                        ui.label(&unit.owner);
+                        ui.label(unit.damage_dealt.to_string());
+                        ui.label(unit.damage_taken.to_string());
+                        ui.label(unit.deaths.to_string());
+                        if unit.level_ups > 0 {
+                            ui.label(format!("+{} Level up!", unit.exp));
+                        } else {
+                            ui.label(format!("+{}", unit.exp));
+                        }
+                        ui.end_row();
+                    }
+                });
+            }
+            None => {
+                ui.label("No result");
+            }
+        }
+        if ui.button("Continue").clicked() {
+            if run.in_battle {
+                run.in_battle = false;
+                app_state.set(AppState::Run).unwrap();
+            } else if progress.pending.take().is_some() {
+                app_state.set(AppState::Campaign).unwrap();
+            } else if spectator.is_active() {
+                app_state.set(AppState::Spectate).unwrap();
+            } else {
+                app_state.set(AppState::Lobby).unwrap();
+            }
+        }
+    });
+}
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+
+use crate::game::components::{AttackRange, Position, PositionType, TargetSelection, UnitInstance};
+
+/// A unit on a board, as seen by the targeting rules
+#[derive(Debug, Clone, Copy)]
+pub struct BoardUnit {
+    pub entity: Entity,
+    pub owner: Entity,
+    pub team: usize,
+    pub range: AttackRange,
+    pub position: Position,
+    /// Character cards are only removed by dying
+    pub character: bool,
+}
+
+impl BoardUnit {
+    pub fn new(entity: Entity, unit: &UnitInstance, position: &Position) -> Self {
+        Self {
+            entity,
+            owner: unit.owner,
+            team: unit.team,
+            range: unit.range,
+            position: *position,
+            character: unit.unit_data_id.is_character(),
+        }
+    }
+}
+
+/// Units on boards only
+pub fn board_units<'a>(
+    iter: impl Iterator<Item = (Entity, &'a UnitInstance, &'a Position)>,
+) -> Vec<BoardUnit> {
+    iter.filter(|(_, _, pos)| pos.position_type == PositionType::Board)
+        .map(|(entity, unit, pos)| BoardUnit::new(entity, unit, pos))
+        .collect()
+}
+
+/// Row 0 is the front row, a unit is covered by any unit in front of it in the same column
+pub fn is_covered(unit: &BoardUnit, units: &[BoardUnit]) -> bool {
+    units.iter().any(|other| {
+        other.position.player_id == unit.position.player_id
+            && other.position.x == unit.position.x
+            && other.position.y < unit.position.y
+    })
+}
+
+pub fn neighbours(target: Entity, units: &[BoardUnit]) -> Vec<Entity> {
+    match units.iter().find(|unit| unit.entity == target) {
+        Some(target) => units
+            .iter()
+            .filter(|unit| unit.position.is_adjacent(&target.position))
+            .map(|unit| unit.entity)
+            .collect(),
+        None => vec![],
+    }
+}
+
+pub fn can_target(
+    selection: &TargetSelection,
+    source: Entity,
+    target: Entity,
+    units: &[BoardUnit],
+) -> bool {
+    let source = units.iter().find(|unit| unit.entity == source);
+    let target = units.iter().find(|unit| unit.entity == target);
+    match (source, target) {
+        (Some(source), Some(target)) => match selection {
+            TargetSelection::None => source.entity == target.entity,
+            TargetSelection::Alliance => source.team == target.team,
+            TargetSelection::Enemy => source.team != target.team,
+            TargetSelection::Any => !target.character,
+            TargetSelection::Reach => {
+                source.team != target.team
+                    && (source.range == AttackRange::Ranged || !is_covered(target, units))
+            }
+            TargetSelection::AdjacentAlliance => {
+                source.team == target.team && source.position.is_adjacent(&target.position)
+            }
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: u32 = 100;
+    const SECOND: u32 = 200;
+
+    fn unit(id: u32, board: u32, x: i32, y: i32, range: AttackRange) -> BoardUnit {
+        BoardUnit {
+            entity: Entity::new(id),
+            owner: Entity::new(board),
+            team: board as usize,
+            range,
+            position: Position {
+                x,
+                y,
+                z: 0,
+                face_up: true,
+                player_id: Entity::new(board),
+                position_type: PositionType::Board,
+            },
+            character: false,
+        }
+    }
+
+    #[test]
+    fn front_row_covers_its_column() {
+        let front = unit(1, FIRST, 1, 0, AttackRange::Melee);
+        let back = unit(2, FIRST, 1, 1, AttackRange::Melee);
+        let side = unit(3, FIRST, 2, 1, AttackRange::Melee);
+        let enemy = unit(4, SECOND, 1, 0, AttackRange::Melee);
+        let units = [front, back, side, enemy];
+        assert!(!is_covered(&front, &units));
+        assert!(is_covered(&back, &units));
+        assert!(!is_covered(&side, &units));
+    }
+
+    #[test]
+    fn melee_cannot_reach_covered_enemies() {
+        let melee = unit(1, FIRST, 0, 0, AttackRange::Melee);
+        let ranged = unit(2, FIRST, 1, 1, AttackRange::Ranged);
+        let front = unit(3, SECOND, 1, 0, AttackRange::Melee);
+        let back = unit(4, SECOND, 1, 1, AttackRange::Melee);
+        let units = [melee, ranged, front, back];
+        let reach = TargetSelection::Reach;
+        assert!(can_target(&reach, melee.entity, front.entity, &units));
+        assert!(!can_target(&reach, melee.entity, back.entity, &units));
+        assert!(can_target(&reach, ranged.entity, back.entity, &units));
+        assert!(!can_target(&reach, melee.entity, ranged.entity, &units));
+    }
+
+    #[test]
+    fn targets_must_be_on_the_board() {
+        let source = unit(1, FIRST, 0, 0, AttackRange::Ranged);
+        let units = [source];
+        assert!(!can_target(
+            &TargetSelection::Enemy,
+            source.entity,
+            Entity::new(2),
+            &units
+        ));
+    }
+
+    #[test]
+    fn selections_by_team_and_adjacency() {
+        let source = unit(1, FIRST, 0, 0, AttackRange::Melee);
+        let next = unit(2, FIRST, 1, 0, AttackRange::Melee);
+        let far = unit(3, FIRST, 2, 2, AttackRange::Melee);
+        let enemy = unit(4, SECOND, 0, 0, AttackRange::Melee);
+        let units = [source, next, far, enemy];
+        let allows = |selection, target: &BoardUnit| {
+            can_target(&selection, source.entity, target.entity, &units)
+        };
+        assert!(allows(TargetSelection::None, &source));
+        assert!(!allows(TargetSelection::None, &next));
+        assert!(allows(TargetSelection::Alliance, &far));
+        assert!(!allows(TargetSelection::Alliance, &enemy));
+        assert!(allows(TargetSelection::Enemy, &enemy));
+        assert!(!allows(TargetSelection::Enemy, &next));
+        assert!(allows(TargetSelection::AdjacentAlliance, &next));
+        assert!(!allows(TargetSelection::AdjacentAlliance, &far));
+        assert!(!allows(TargetSelection::AdjacentAlliance, &enemy));
+    }
+
+    #[test]
+    fn any_skips_characters() {
+        let source = unit(1, FIRST, 0, 0, AttackRange::Melee);
+        let enemy = unit(2, SECOND, 0, 0, AttackRange::Melee);
+        let character = BoardUnit {
+            character: true,
+            ..unit(3, SECOND, 1, 0, AttackRange::Melee)
+        };
+        let units = [source, enemy, character];
+        assert!(can_target(
+            &TargetSelection::Any,
+            source.entity,
+            enemy.entity,
+            &units
+        ));
+        assert!(!can_target(
+            &TargetSelection::Any,
+            source.entity,
+            character.entity,
+            &units
+        ));
+    }
+}
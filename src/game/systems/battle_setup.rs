@@ -2,63 +2,212 @@ use std::f32::consts::PI;
 
 use bevy::prelude::*;
 
-use crate::game::components::{
-    Ability, AbilityInstance, Board, Card, CardInstance, CardInstanceType, CardType, Cell, Player,
-    PlayerController, PlayerInstance, PlayerInstanceRef, Position, PositionType, Unit,
-    UnitInstance, UnitInstanceRef,
+use crate::{
+    game::components::{
+        Ability, AbilityInstance, Board, BoardData, Card, CardInstance, CardInstanceType, CardType,
+        Cell, CellRef, Deck, EncounterDataId, EnemyPlayer, LocalPlayer, ParticipantPlayer, Player,
+        PlayerController, PlayerInstance, PlayerInstanceRef, PlayerSlot, Position, PositionType,
+        Unit, UnitInstance, UnitInstanceRef,
+    },
+    AppState,
 };
 
-use super::{Battle, InputState};
+use super::{
+    battle_end::collect_battle_stats,
+    net::deck_saves,
+    spectator::{Replay, ReplayPlayer},
+    Battle, BattleResult, InputState, NetSession,
+};
+
+/// Settings for the next battle, read when the battle starts
+pub struct BattleConfig {
+    pub encounter: EncounterDataId,
+    /// Seconds per turn, no timer when `None`
+    pub turn_time: Option<f32>,
+    /// `Player` playing instead of the local player, the run player in run mode
+    pub you: Option<Entity>,
+    /// `Player`s by participant index instead of the load order, server clients and replays
+    pub players: Vec<Entity>,
+    /// Every participant is `Remote`, the actions come from a replay
+    pub spectate: bool,
+    /// Why the last battle could not start, shown in the lobby
+    pub error: Option<String>,
+}
+
+impl Default for BattleConfig {
+    fn default() -> Self {
+        Self {
+            encounter: EncounterDataId::Training,
+            turn_time: None,
+            you: None,
+            players: vec![],
+            spectate: false,
+            error: None,
+        }
+    }
+}
 
 pub fn start_battle(
     mut commands: Commands,
-    q: Query<Entity, With<Player>>,
+    mut config: ResMut<BattleConfig>,
+    mut app_state: ResMut<State<AppState>>,
+    net: Res<NetSession>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+    slot_query: Query<(Entity, &PlayerSlot)>,
+    enemy_query: Query<(Entity, &EnemyPlayer)>,
     player_query: Query<&Player>,
     card_query: Query<&Card>,
     unit_query: Query<&Unit>,
     ability_query: Query<&Ability>,
 ) {
-    let ids: Vec<Entity> = q.iter().collect();
-    let player_id = ids[0];
-    let enemy_id = ids[1];
-
-    let (player, player_board) = load_player_to_battle(
-        player_id,
-        &mut commands,
-        &player_query,
-        &card_query,
-        &unit_query,
-        &ability_query,
-    );
-    commands.entity(player_board).insert(Transform {
-        translation: Vec3::new(0.0, -150.0, 0.0),
-        ..Default::default()
-    });
-    commands.entity(player).insert(PlayerController::Player);
-    let (enemy, enemy_board) = load_player_to_battle(
-        enemy_id,
-        &mut commands,
-        &player_query,
-        &card_query,
-        &unit_query,
-        &ability_query,
-    );
-    commands.entity(enemy_board).insert(Transform {
-        translation: Vec3::new(0.0, 150.0, 0.0),
-        rotation: Quat::from_rotation_z(PI),
-        ..Default::default()
-    });
-    commands.entity(enemy).insert(PlayerController::Ai);
+    let local = local_query.iter().next();
+    let encounter = config.encounter.encounter_data();
+    let count = encounter.participants.len();
+
+    let mut players = vec![];
+    let mut teams = vec![];
+    let mut boards = vec![];
+    let mut replay_players = vec![];
+    for (index, participant) in encounter.participants.iter().enumerate() {
+        let board_data = participant.board.board_data();
+        let player_id = match participant.player {
+            _ if index < config.players.len() => Some(config.players[index]),
+            ParticipantPlayer::You => config.you.or(local),
+            ParticipantPlayer::Slot(slot) => slot_query
+                .iter()
+                .find(|(_, &s)| s == slot)
+                .map(|(entity, _)| entity),
+            ParticipantPlayer::Enemy(enemy) => enemy_query
+                .iter()
+                .find(|(_, e)| e.0 == enemy)
+                .map(|(entity, _)| entity),
+            ParticipantPlayer::Joined => None,
+            ParticipantPlayer::Host | ParticipantPlayer::Guest
+                if net.is_local(participant.player) =>
+            {
+                local
+            }
+            ParticipantPlayer::Host | ParticipantPlayer::Guest => net.peer,
+        };
+        let player_id = match player_id {
+            Some(player_id) => player_id,
+            None => {
+                error!(
+                    "No player for {:?}, it sits the battle out",
+                    participant.player
+                );
+                continue;
+            }
+        };
+        let controller = match participant.player {
+            _ if config.spectate => PlayerController::Remote,
+            ParticipantPlayer::Host | ParticipantPlayer::Guest
+                if net.is_local(participant.player) =>
+            {
+                PlayerController::Player
+            }
+            ParticipantPlayer::Host | ParticipantPlayer::Guest => PlayerController::Remote,
+            _ => participant.controller,
+        };
+        let owner = player_query.get(player_id).unwrap();
+        let (deck, (saves, character)) = match (
+            owner.deck(),
+            deck_saves(owner, &card_query, &unit_query, &ability_query),
+        ) {
+            (Some(deck), Some(saves)) => (deck, saves),
+            _ => {
+                error!("{} has no deck and sits the battle out", owner.name);
+                continue;
+            }
+        };
+        replay_players.push(ReplayPlayer {
+            name: owner.name.clone(),
+            deck: saves,
+            character,
+        });
+        let (player, board) = load_player_to_battle(
+            player_id,
+            deck,
+            participant.team,
+            &board_data,
+            &mut commands,
+            &player_query,
+            &card_query,
+            &unit_query,
+            &ability_query,
+        );
+        commands
+            .entity(board)
+            .insert(board_transform(index, count, &board_data));
+        commands
+            .entity(player)
+            .insert(controller)
+            .insert(participant.profile);
+        players.push(player);
+        teams.push(participant.team);
+        boards.push(board);
+    }
+
+    teams.sort_unstable();
+    teams.dedup();
+    if teams.len() < 2 {
+        // the boards spawned so far go with the battle cleanup
+        error!("Only {} players of {} loaded", players.len(), count);
+        config.error = Some(format!(
+            "{} needs two teams, only {} of {} players could join",
+            encounter.name,
+            players.len(),
+            count
+        ));
+        app_state.set(AppState::Lobby).unwrap();
+        return;
+    }
+    config.error = None;
 
     commands.insert_resource(Some(Battle {
-        players: vec![player, enemy],
-        boards: vec![player_board, enemy_board],
+        players,
+        boards,
+        win_conditions: encounter.win_conditions,
+        turn_time: config.turn_time,
+        response_window: encounter.response_window,
+        replay: Some(Replay {
+            encounter: config.encounter,
+            players: replay_players,
+            actions: vec![],
+        }),
         ..Default::default()
     }));
 }
 
+/// Two boards face each other, three or four boards are scaled down two per row
+fn board_transform(index: usize, count: usize, board_data: &BoardData) -> Transform {
+    let offset = 100.0;
+    let (scale, row, column) = if count <= 2 {
+        (1.0, index, 0.0)
+    } else {
+        (0.5, index / 2, (index % 2) as f32 - 0.5)
+    };
+    let y = board_data.height as f32 * offset / 2.0 * scale;
+    Transform {
+        translation: if row == 0 {
+            Vec3::new(column * 640.0, -y, 0.0)
+        } else {
+            Vec3::new(column * 640.0, y, 0.0)
+        },
+        rotation: if row == 0 {
+            Quat::IDENTITY
+        } else {
+            Quat::from_rotation_z(PI)
+        },
+        scale: Vec3::splat(scale),
+    }
+}
+
 pub fn load_player_to_battle(
     player_id: Entity,
+    deck: &Deck,
+    team: usize,
+    board_data: &BoardData,
     commands: &mut Commands,
     player_query: &Query<&Player>,
     card_query: &Query<&Card>,
@@ -68,15 +217,18 @@ pub fn load_player_to_battle(
     let player_instance_id = commands.spawn().id();
     let board = commands
         .spawn()
-        .insert(Board)
+        .insert(Board {
+            width: board_data.width,
+            height: board_data.height,
+        })
         .insert(PlayerInstanceRef(player_instance_id))
         .insert_bundle((Transform::default(), GlobalTransform::default()))
         .insert(Name::new("Board"))
         .id();
 
     let player = player_query.get(player_id).unwrap();
-    let decks: Vec<Entity> = player
-        .decks
+    let decks: Vec<Entity> = deck
+        .cards
         .iter()
         .enumerate()
         .map(|(i, &card_id)| {
@@ -86,6 +238,7 @@ pub fn load_player_to_battle(
                 unit_query,
                 commands,
                 player_instance_id,
+                team,
                 Position {
                     x: 0,
                     y: 0,
@@ -94,7 +247,6 @@ pub fn load_player_to_battle(
                     player_id: player_instance_id,
                     position_type: PositionType::Deck,
                 },
-                board,
                 ability_query,
             )
         })
@@ -103,19 +255,19 @@ pub fn load_player_to_battle(
 
     let player_card_instance = spwan_card_instance(
         card_query,
-        player.character_card,
+        deck.character_card,
         unit_query,
         commands,
         player_instance_id,
+        team,
         Position {
-            x: 1,
-            y: 1,
+            x: board_data.character.0,
+            y: board_data.character.1,
             z: 0,
             face_up: true,
             player_id: player_instance_id,
             position_type: PositionType::Board,
         },
-        board,
         ability_query,
     );
     commands
@@ -125,8 +277,10 @@ pub fn load_player_to_battle(
     commands
         .entity(player_instance_id)
         .insert(PlayerInstance {
+            player_id,
             name: player.name.clone(),
             mp: 5,
+            team,
             player_card_instance_id: player_card_instance,
         })
         .insert(Name::new(format!(
@@ -136,25 +290,38 @@ pub fn load_player_to_battle(
         .id();
     commands.entity(board).push_children(&[player_instance_id]);
 
-    {
-        commands.entity(board).with_children(|builder| {
-            for y in 0..3 {
-                for x in 0..3 {
-                    builder
+    for y in 0..board_data.height {
+        for x in 0..board_data.width {
+            let cell_type = board_data.cell_type(x, y);
+            let cell = commands
+                .spawn()
+                .insert(Cell { cell_type })
+                .insert(Position {
+                    x,
+                    y,
+                    z: 0,
+                    face_up: true,
+                    player_id: player_instance_id,
+                    position_type: PositionType::Board,
+                })
+                .insert(Name::new("Cell"))
+                .id();
+            let abilities: Vec<_> = cell_type
+                .abilities()
+                .into_iter()
+                .map(|ability_data_id| {
+                    // terrain has no ability template, refer to the cell instead
+                    commands
                         .spawn()
-                        .insert(Cell)
-                        .insert(Position {
-                            x,
-                            y,
-                            z: 0,
-                            face_up: true,
-                            player_id: player_instance_id,
-                            position_type: PositionType::Board,
-                        })
-                        .insert(Name::new("Cell"));
-                }
-            }
-        });
+                        .insert(AbilityInstance::new(cell, Ability::new(ability_data_id)))
+                        .insert(CellRef(cell))
+                        .insert(Name::new("Terrain AbilityInstance"))
+                        .id()
+                })
+                .collect();
+            commands.entity(cell).push_children(&abilities[..]);
+            commands.entity(board).push_children(&[cell]);
+        }
     }
     (player_instance_id, board)
 }
@@ -165,8 +332,8 @@ fn spwan_card_instance(
     unit_query: &Query<&Unit>,
     commands: &mut Commands,
     player_instance_id: Entity,
+    team: usize,
     position: Position,
-    board: Entity,
     ability_query: &Query<&Ability>,
 ) -> Entity {
     let card_instance_id = commands.spawn().id();
@@ -180,19 +347,14 @@ fn spwan_card_instance(
                 .abilities
                 .iter()
                 .map(|&ability_id| {
-                    spwan_ability_instance(
-                        commands,
-                        ability_query,
-                        ability_id,
-                        unit_id,
-                        unit_instance_id,
-                    )
+                    spwan_ability_instance(commands, ability_query, ability_id, unit_instance_id)
                 })
                 .collect();
             commands
                 .entity(unit_instance_id)
                 .push_children(&abilities[..]);
-            let unit_instance = UnitInstance::new(unit_id, unit, player_instance_id, abilities);
+            let unit_instance =
+                UnitInstance::new(unit_id, unit, player_instance_id, team, abilities);
             commands
                 .entity(unit_instance_id)
                 .insert(unit_instance)
@@ -225,7 +387,6 @@ fn spwan_ability_instance(
     commands: &mut Commands,
     ability_query: &Query<&Ability>,
     ability_id: Entity,
-    unit_id: Entity,
     unit_instance_id: Entity,
 ) -> Entity {
     let ability_instance_id = commands.spawn().id();
@@ -244,10 +405,32 @@ fn spwan_ability_instance(
     ability_instance_id
 }
 
-pub fn cleanup_battle(mut commands: Commands, query: Query<Entity, With<Board>>) {
+pub fn cleanup_battle(
+    mut commands: Commands,
+    query: Query<Entity, With<Board>>,
+    mut battle: ResMut<Option<Battle>>,
+    unit_query: Query<(Entity, &UnitInstance)>,
+    card_query: Query<&CardInstance>,
+    ability_query: Query<&AbilityInstance>,
+    player_query: Query<&PlayerInstance>,
+) {
+    // keep the result for the battle end screen
+    let result = battle.as_mut().and_then(|battle| {
+        let mut result = battle.result.take()?;
+        collect_battle_stats(
+            battle,
+            &mut result,
+            &unit_query,
+            &card_query,
+            &ability_query,
+            &player_query,
+        );
+        Some(result)
+    });
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
+    commands.insert_resource::<Option<BattleResult>>(result);
     commands.insert_resource(InputState::InTurn);
     commands.insert_resource::<Option<Battle>>(None);
 }
@@ -1,8 +1,14 @@
 use crate::game::{
-    components::{CardInstance, PlayerController, PlayerInstance, Position},
+    components::{
+        AbilityInstance, AbilityType, CardFace, CardInstance, Cell, PlayerController,
+        PlayerInstance, Position, PositionType, TargetSelection, UnitInstance, UnitInstanceRef,
+    },
     events::{HoverChangedEvent, PlayerAction, PlayerActionEvent},
 };
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use super::{Battle, BattleFlow, Hotseat, HoveringEntity, InputState};
 
 pub struct ButtonMaterials {
     normal: Handle<ColorMaterial>,
@@ -21,27 +27,43 @@ impl FromWorld for ButtonMaterials {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleButton {
+    EndTurn,
+    Surrender,
+}
+
 pub fn button_system(
     button_materials: Res<ButtonMaterials>,
     mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>),
+        (&Interaction, &mut Handle<ColorMaterial>, &BattleButton),
         (Changed<Interaction>, With<Button>),
     >,
     mut ev_player: EventWriter<PlayerActionEvent>,
     controller_query: Query<(Entity, &PlayerController)>,
+    battle: Res<Option<Battle>>,
+    hotseat: Res<Hotseat>,
 ) {
-    for (interaction, mut material) in interaction_query.iter_mut() {
+    let player_id = battle
+        .as_ref()
+        .as_ref()
+        .and_then(|battle| battle.human_player(&controller_query))
+        .filter(|_| hotseat.ready);
+    for (interaction, mut material, button) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => {
                 // text.sections[0].value = "Press".to_string();
-                let (player_id, _) = controller_query
-                    .iter()
-                    .find(|(_, &p)| p == PlayerController::Player)
-                    .unwrap();
                 *material = button_materials.pressed.clone();
+                let player_id = match player_id {
+                    Some(player_id) => player_id,
+                    None => continue,
+                };
                 ev_player.send(PlayerActionEvent {
-                    player_id: player_id,
-                    action: PlayerAction::EndTurn,
+                    player_id,
+                    action: match button {
+                        BattleButton::EndTurn => PlayerAction::EndTurn,
+                        BattleButton::Surrender => PlayerAction::Surrender,
+                    },
                 });
             }
             Interaction::Hovered => {
@@ -93,35 +115,52 @@ pub fn setup_ui(
                 .insert(DetailUi);
         });
 
-    // Button
+    // Buttons
     commands
-        .spawn_bundle(ButtonBundle {
+        .spawn_bundle(NodeBundle {
             style: Style {
-                size: Size::new(Val::Px(150.0), Val::Px(65.0)),
-                // center button
+                // center buttons
                 margin: Rect::all(Val::Auto),
-                // horizontally center child text
-                justify_content: JustifyContent::Center,
-                // vertically center child text
-                align_items: AlignItems::Center,
                 ..Default::default()
             },
-            material: button_materials.normal.clone(),
+            material: materials.add(Color::NONE.into()),
             ..Default::default()
         })
-        .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    "End Turn",
-                    TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                    Default::default(),
-                ),
-                ..Default::default()
-            });
+        .with_children(|builder| {
+            for (button, label) in [
+                (BattleButton::EndTurn, "End Turn"),
+                (BattleButton::Surrender, "Surrender"),
+            ] {
+                builder
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(170.0), Val::Px(65.0)),
+                            margin: Rect::all(Val::Px(5.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                label,
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 40.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
         });
 }
 
@@ -129,22 +168,43 @@ pub struct DetailUi;
 pub fn update_ui_system(
     mut ev_hover_changed: EventReader<HoverChangedEvent>,
     mut query: Query<(&mut Text, &DetailUi)>,
-    card_query: Query<(Option<&CardInstance>, Option<&Position>)>,
+    card_query: Query<(
+        Option<&CardInstance>,
+        Option<&Position>,
+        Option<&Cell>,
+        Option<&CardFace>,
+    )>,
+    hovering: Res<HoveringEntity>,
+    face_query: Query<(), Changed<CardFace>>,
 ) {
     let (mut text, _) = query.single_mut().unwrap();
-    for e in ev_hover_changed.iter() {
-        match &e.new {
+    let mut hovered: Vec<_> = ev_hover_changed.iter().map(|e| e.new).collect();
+    // a hovered card that turns over is described again
+    if let Some(entity) = hovering.0 {
+        if hovered.is_empty() && face_query.get(entity).is_ok() {
+            hovered.push(Some(entity));
+        }
+    }
+    for new in hovered.iter() {
+        match new {
             Some(e) => {
-                let (card, field) = card_query.get(*e).unwrap();
+                let (card, field, cell, face) = card_query.get(*e).unwrap();
                 match card {
+                    // the detail never tells more than the card shows
+                    Some(_) if face.map(|face| !face.shown).unwrap_or(false) => {
+                        text.sections[0].value = "Face-down card".to_string();
+                    }
                     Some(card) => {
                         text.sections[0].value = format!("{} ({:?}) ", card.name, e);
                     }
                     None => match field {
                         Some(pos) => {
                             text.sections[0].value = format!(
-                                "Cell - player_id: {:?}, x: {}, y: {}",
-                                pos.player_id, pos.x, pos.y
+                                "Cell ({:?}) - player_id: {:?}, x: {}, y: {}",
+                                cell.map(|cell| cell.cell_type).unwrap_or_default(),
+                                pos.player_id,
+                                pos.x,
+                                pos.y
                             )
                         }
                         None => {
@@ -159,3 +219,105 @@ pub fn update_ui_system(
         }
     }
 }
+
+pub fn response_ui_system(
+    egui_context: ResMut<EguiContext>,
+    battle: Res<Option<Battle>>,
+    controller_query: Query<&PlayerController>,
+    unit_query: Query<(Entity, &UnitInstance, &Position)>,
+    ability_query: Query<(Entity, &AbilityInstance, &UnitInstanceRef)>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+    mut input_state: ResMut<InputState>,
+    hotseat: Res<Hotseat>,
+) {
+    if let Some(window) = battle.as_ref().as_ref().and_then(|battle| battle.response) {
+        if !hotseat.ready
+            || controller_query
+                .get(window.player)
+                .map(|&controller| controller != PlayerController::Player)
+                .unwrap_or(true)
+        {
+            return;
+        }
+        let name = |entity: Entity| {
+            unit_query
+                .get(entity)
+                .map(|(_, unit, _)| unit.name.clone())
+                .unwrap_or_default()
+        };
+        let pending = ability_query
+            .get(window.ability.ability)
+            .map(|(_, a, _)| a.name.clone())
+            .unwrap_or_default();
+        egui::Window::new("Response").show(egui_context.ctx(), |ui| {
+            ui.label(format!(
+                "{} uses {} on {}",
+                name(window.ability.source),
+                pending,
+                name(window.ability.target)
+            ));
+            if let InputState::SelectedInstant(ability) = *input_state {
+                let picked = ability_query
+                    .get(ability)
+                    .map(|(_, a, _)| a.name.clone())
+                    .unwrap_or_default();
+                ui.label(format!("Click a unit to target with {}", picked));
+            }
+            for (ability, a, u) in ability_query.iter() {
+                if let AbilityType::Instant(selection) = &a.ability_type {
+                    if let Ok((source, unit, pos)) = unit_query.get(u.0) {
+                        if unit.owner != window.player || pos.position_type != PositionType::Board {
+                            continue;
+                        }
+                        let button =
+                            egui::Button::new(format!("{}: {} ({} AP)", unit.name, a.name, a.ap));
+                        if !ui.add_enabled(unit.ap >= a.ap, button).clicked() {
+                            continue;
+                        }
+                        match selection {
+                            TargetSelection::None => ev_player.send(PlayerActionEvent {
+                                player_id: window.player,
+                                action: PlayerAction::UseAbility {
+                                    ability,
+                                    source,
+                                    target: source,
+                                },
+                            }),
+                            // the target is picked on the board like any other selection
+                            _ => *input_state = InputState::SelectedInstant(ability),
+                        }
+                    }
+                }
+            }
+            if ui.button("Pass").clicked() {
+                *input_state = InputState::InTurn;
+                ev_player.send(PlayerActionEvent {
+                    player_id: window.player,
+                    action: PlayerAction::Pass,
+                });
+            }
+        });
+    }
+}
+
+pub fn turn_timer_ui_system(
+    egui_context: ResMut<EguiContext>,
+    battle: Res<Option<Battle>>,
+    player_query: Query<&PlayerInstance>,
+) {
+    if let Some(battle) = battle.as_ref() {
+        if battle.turn_time.is_some() && battle.stage == BattleFlow::PlayerAction {
+            let name = player_query
+                .get(battle.players[battle.current_player_index])
+                .map(|player| player.name.clone())
+                .unwrap_or_default();
+            egui::Window::new("Turn Timer").show(egui_context.ctx(), |ui| {
+                ui.label(format!(
+                    "{}: {:.0}s",
+                    name,
+                    battle.turn_time_left.max(0.0).ceil()
+                ));
+            });
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game::{
+        components::{CampaignDataId, CampaignNodeType, Card, CardType, LocalPlayer, Player, Unit},
+        events::SaveGameEvent,
+    },
+    AppState,
+};
+
+use super::{BattleConfig, BattleResult, Shop};
+
+/// Exp for every unit of the selected deck at a rest node
+pub const REST_EXP: i32 = 5;
+
+/// Progress through the campaign map, saved with the player
+#[derive(Debug, Clone)]
+pub struct CampaignProgress {
+    pub campaign: CampaignDataId,
+    /// Cleared nodes, in order
+    pub cleared: Vec<usize>,
+    /// Battle node being played, until the battle end screen is closed
+    pub pending: Option<usize>,
+    /// Message about the last node, shown on the map
+    pub message: Option<String>,
+}
+
+impl Default for CampaignProgress {
+    fn default() -> Self {
+        Self {
+            campaign: CampaignDataId::Main,
+            cleared: vec![],
+            pending: None,
+            message: None,
+        }
+    }
+}
+
+impl CampaignProgress {
+    /// Progress from a save, the path stops at the first node that could not have been played
+    pub fn load(cleared: &[usize]) -> Self {
+        let mut progress = Self::default();
+        for &node in cleared {
+            if !progress.available().contains(&node) {
+                warn!(
+                    "Campaign node {} can not follow {:?}, dropping it and the rest",
+                    node, progress.cleared
+                );
+                break;
+            }
+            progress.cleared.push(node);
+        }
+        progress
+    }
+
+    /// Nodes that can be played next
+    pub fn available(&self) -> Vec<usize> {
+        match self.cleared.last() {
+            Some(&last) => self.campaign.campaign_data().nodes[last].next.clone(),
+            None => vec![0],
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.cleared.is_empty() && self.available().is_empty()
+    }
+
+    /// Clear the node and return its money reward
+    pub fn clear(&mut self, node: usize) -> i32 {
+        self.cleared.push(node);
+        self.campaign.campaign_data().nodes[node].reward
+    }
+}
+
+pub fn campaign_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut progress: ResMut<CampaignProgress>,
+    mut battle_config: ResMut<BattleConfig>,
+    mut shop: ResMut<Shop>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    mut player_query: Query<&mut Player, With<LocalPlayer>>,
+    card_query: Query<&Card>,
+    mut unit_query: Query<&mut Unit>,
+) {
+    let mut player = match player_query.single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let data = progress.campaign.campaign_data();
+    let available = progress.available();
+    let mut picked = None;
+    egui::Window::new(&data.name).show(egui_context.ctx(), |ui| {
+        egui::Grid::new("campaign_nodes")
+            .striped(true)
+            .show(ui, |ui| {
+                for (index, node) in data.nodes.iter().enumerate() {
+                    ui.label(&node.name);
+                    ui.label(match node.node_type {
+                        CampaignNodeType::Battle(_) => "Battle",
+                        CampaignNodeType::Shop => "Shop",
+                        CampaignNodeType::Rest => "Rest",
+                        CampaignNodeType::Boss(_) => "Boss",
+                    });
+                    if node.reward > 0 {
+                        ui.label(format!("Reward: {}", node.reward));
+                    } else {
+                        ui.label("");
+                    }
+                    if progress.cleared.contains(&index) {
+                        ui.label("Cleared");
+                    } else if available.contains(&index) {
+                        if ui.button("Go").clicked() {
+                            picked = Some(index);
+                        }
+                    } else {
+                        ui.label("Locked");
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(message) = &progress.message {
+            ui.separator();
+            ui.label(message);
+        }
+        ui.separator();
+        if progress.is_finished() {
+            ui.label("Campaign complete!");
+            if ui.button("Restart").clicked() {
+                *progress = CampaignProgress::default();
+                ev_save.send(SaveGameEvent);
+            }
+        }
+        if ui.button("Back to Lobby").clicked() {
+            app_state.set(AppState::Lobby).unwrap();
+        }
+    });
+
+    if let Some(index) = picked {
+        let node = &data.nodes[index];
+        match node.node_type {
+            CampaignNodeType::Battle(encounter) | CampaignNodeType::Boss(encounter) => {
+                battle_config.encounter = encounter;
+                progress.pending = Some(index);
+                app_state.set(AppState::DeckSelect).unwrap();
+            }
+            CampaignNodeType::Shop => {
+                progress.clear(index);
+                progress.message = Some(format!("Visited the {}", node.name));
+                shop.back_to_campaign = true;
+                ev_save.send(SaveGameEvent);
+                app_state.set(AppState::Shop).unwrap();
+            }
+            CampaignNodeType::Rest => {
+                let mut level_ups = 0;
+                for &card in player.deck().iter().flat_map(|deck| deck.cards.iter()) {
+                    if let Ok(CardType::Unit(unit)) = card_query.get(card).map(|c| &c.card_type) {
+                        if let Ok(mut unit) = unit_query.get_mut(*unit) {
+                            level_ups += unit.gain_exp(REST_EXP);
+                        }
+                    }
+                }
+                player.money += progress.clear(index);
+                progress.message = Some(format!(
+                    "Rested at the {}: +{} exp, {} level ups",
+                    node.name, REST_EXP, level_ups
+                ));
+                ev_save.send(SaveGameEvent);
+            }
+        }
+    }
+}
+
+/// Clear the battle node when the local player's team won
+pub fn campaign_battle_end(
+    mut progress: ResMut<CampaignProgress>,
+    result: Res<Option<BattleResult>>,
+    mut player_query: Query<(Entity, &mut Player), With<LocalPlayer>>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+) {
+    let node = match progress.pending {
+        Some(node) => node,
+        None => return,
+    };
+    let name = progress.campaign.campaign_data().nodes[node].name.clone();
+    if let (Some(result), Ok((entity, mut player))) = (result.as_ref(), player_query.single_mut()) {
+        let won = result
+            .winners()
+            .iter()
+            .any(|winner| winner.player_id == entity);
+        progress.message = Some(if won {
+            let reward = progress.clear(node);
+            player.money += reward;
+            format!("Cleared {}: +{} money", name, reward)
+        } else {
+            format!("Lost at {}, try again", name)
+        });
+        ev_save.send(SaveGameEvent);
+    }
+}
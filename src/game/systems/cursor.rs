@@ -38,7 +38,8 @@ pub fn cursor_system(
             let s = sprite_query
                 .iter()
                 .filter(|(_, gt, sprite)| {
-                    let size = sprite.size;
+                    // boards may be scaled down
+                    let size = sprite.size * gt.scale.truncate();
                     let x1 = gt.translation.x - size.x / 2.0;
                     let x2 = gt.translation.x + size.x / 2.0;
                     let y1 = gt.translation.y - size.y / 2.0;
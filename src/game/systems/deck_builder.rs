@@ -0,0 +1,431 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game::{
+        components::{Ability, Card, CardDataId, CardType, Deck, LocalPlayer, Player, Unit},
+        events::SaveGameEvent,
+    },
+    AppState,
+};
+
+use super::CampaignProgress;
+
+pub const DECK_MIN_SIZE: usize = 3;
+pub const DECK_MAX_SIZE: usize = 20;
+/// Copies of the same card allowed in a deck
+pub const MAX_COPIES: usize = 3;
+
+/// Working copy of the decks, written back to the `Player` on save
+#[derive(Debug, Default)]
+pub struct DeckBuilder {
+    pub player: Option<Entity>,
+    pub decks: Vec<Deck>,
+    /// Index into `decks` being edited
+    pub current: usize,
+    pub collection: Vec<Entity>,
+    pub characters: Vec<Entity>,
+    pub selected: Option<Entity>,
+}
+
+/// Reasons the deck can not be saved
+pub fn deck_rule_errors(deck: &[Entity], card_query: &Query<&Card>) -> Vec<String> {
+    let cards: Vec<_> = deck
+        .iter()
+        .filter_map(|&card| card_query.get(card).ok())
+        .map(|card| card.card_data_id)
+        .collect();
+    card_rule_errors(&cards)
+}
+
+/// Deck rules by card data, for decks that are not spawned
+pub fn card_rule_errors(cards: &[CardDataId]) -> Vec<String> {
+    let mut errors = vec![];
+    if cards.len() < DECK_MIN_SIZE {
+        errors.push(format!("Deck needs at least {} cards", DECK_MIN_SIZE));
+    }
+    if cards.len() > DECK_MAX_SIZE {
+        errors.push(format!("Deck can have at most {} cards", DECK_MAX_SIZE));
+    }
+    let mut checked = vec![];
+    for &card in cards.iter() {
+        if checked.contains(&card) {
+            continue;
+        }
+        checked.push(card);
+        let copies = cards.iter().filter(|&&other| other == card).count();
+        if copies > MAX_COPIES {
+            errors.push(format!(
+                "{}: {} copies, at most {}",
+                card.card_data().name,
+                copies,
+                MAX_COPIES
+            ));
+        }
+    }
+    errors
+}
+
+pub fn open_deck_builder(
+    mut builder: ResMut<DeckBuilder>,
+    query: Query<(Entity, &Player), With<LocalPlayer>>,
+) {
+    *builder = match query.single() {
+        Ok((entity, player)) => DeckBuilder {
+            player: Some(entity),
+            decks: player.decks.clone(),
+            current: player
+                .selected_deck
+                .min(player.decks.len().saturating_sub(1)),
+            collection: player.cards.clone(),
+            characters: player.characters.clone(),
+            selected: None,
+        },
+        Err(_) => DeckBuilder::default(),
+    };
+}
+
+pub fn deck_builder_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut builder: ResMut<DeckBuilder>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    mut player_query: Query<&mut Player>,
+    card_query: Query<&Card>,
+    unit_query: Query<&Unit>,
+    mut ability_query: QuerySet<(Query<&Ability>, Query<&mut Ability>)>,
+) {
+    let builder = &mut *builder;
+    // opened without a local player
+    let errors = match builder.decks.get(builder.current) {
+        Some(deck) => deck_rule_errors(&deck.cards, &card_query),
+        None => {
+            egui::Window::new("Deck Builder").show(egui_context.ctx(), |ui| {
+                ui.label("No decks to edit");
+                if ui.button("Back").clicked() {
+                    app_state.set(AppState::Lobby).unwrap();
+                }
+            });
+            return;
+        }
+    };
+    let money = builder
+        .player
+        .and_then(|player| player_query.get_mut(player).ok())
+        .map(|player| player.money)
+        .unwrap_or_default();
+    let mut upgrade = None;
+    let name = |card: Entity| {
+        card_query
+            .get(card)
+            .map(|card| card.name.clone())
+            .unwrap_or_default()
+    };
+    let all_valid = builder
+        .decks
+        .iter()
+        .all(|deck| deck_rule_errors(&deck.cards, &card_query).is_empty());
+    egui::Window::new("Deck Builder").show(egui_context.ctx(), |ui| {
+        ui.horizontal(|ui| {
+            for index in 0..builder.decks.len() {
+                let deck_name = builder.decks[index].name.clone();
+                ui.selectable_value(&mut builder.current, index, deck_name);
+            }
+            let first_character = builder.characters.first().copied();
+            let new_deck = egui::Button::new("New Deck");
+            if ui
+                .add_enabled(first_character.is_some(), new_deck)
+                .clicked()
+            {
+                if let Some(character_card) = first_character {
+                    builder.decks.push(Deck {
+                        name: format!("Deck {}", builder.decks.len() + 1),
+                        cards: vec![],
+                        character_card,
+                    });
+                    builder.current = builder.decks.len() - 1;
+                }
+            }
+            let delete = egui::Button::new("Delete Deck");
+            if ui.add_enabled(builder.decks.len() > 1, delete).clicked() {
+                let deck = builder.decks.remove(builder.current);
+                builder.collection.extend(deck.cards);
+                builder.current = builder.current.min(builder.decks.len() - 1);
+            }
+        });
+        let current = builder.current;
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut builder.decks[current].name);
+            ui.label("Character:");
+            let character_card = builder.decks[current].character_card;
+            egui::ComboBox::from_id_source("character")
+                .selected_text(name(character_card))
+                .show_ui(ui, |ui| {
+                    for &character in builder.characters.iter() {
+                        ui.selectable_value(
+                            &mut builder.decks[current].character_card,
+                            character,
+                            name(character),
+                        );
+                    }
+                });
+        });
+        ui.separator();
+        ui.columns(3, |columns| {
+            columns[0].heading(format!("Collection ({})", builder.collection.len()));
+            let mut add = None;
+            egui::ScrollArea::vertical()
+                .id_source("collection")
+                .show(&mut columns[0], |ui| {
+                    for &card in builder.collection.iter() {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .selectable_label(builder.selected == Some(card), name(card))
+                                .clicked()
+                            {
+                                builder.selected = Some(card);
+                            }
+                            if ui.small_button("Add").clicked() {
+                                add = Some(card);
+                            }
+                        });
+                    }
+                });
+            if let Some(card) = add {
+                builder.collection.retain(|&c| c != card);
+                builder.decks[current].cards.push(card);
+            }
+
+            let deck = &builder.decks[current].cards;
+            columns[1].heading(format!("Deck ({})", deck.len()));
+            let mut remove = None;
+            egui::ScrollArea::vertical()
+                .id_source("deck")
+                .show(&mut columns[1], |ui| {
+                    for &card in deck.iter() {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .selectable_label(builder.selected == Some(card), name(card))
+                                .clicked()
+                            {
+                                builder.selected = Some(card);
+                            }
+                            if ui.small_button("Remove").clicked() {
+                                remove = Some(card);
+                            }
+                        });
+                    }
+                });
+            if let Some(card) = remove {
+                builder.decks[current].cards.retain(|&c| c != card);
+                builder.collection.push(card);
+            }
+
+            columns[2].heading(format!("Detail (Money: {})", money));
+            if let Some(card) = builder.selected.and_then(|card| card_query.get(card).ok()) {
+                upgrade = card_detail_ui(
+                    &mut columns[2],
+                    card,
+                    money,
+                    &unit_query,
+                    ability_query.q0(),
+                );
+            }
+        });
+        ui.separator();
+        for error in errors.iter() {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        if errors.is_empty() && !all_valid {
+            ui.colored_label(egui::Color32::RED, "Another deck breaks the deck rules");
+        }
+        ui.horizontal(|ui| {
+            let save = egui::Button::new("Save");
+            if ui.add_enabled(all_valid, save).clicked() {
+                if let Some(mut player) = builder
+                    .player
+                    .and_then(|player| player_query.get_mut(player).ok())
+                {
+                    player.decks = builder.decks.clone();
+                    player.selected_deck = builder.current;
+                    player.cards = builder.collection.clone();
+                }
+                ev_save.send(SaveGameEvent);
+                app_state.set(AppState::Lobby).unwrap();
+            }
+            if ui.button("Cancel").clicked() {
+                app_state.set(AppState::Lobby).unwrap();
+            }
+        });
+    });
+
+    // upgrades are paid and saved right away, Cancel does not undo them
+    if let Some(entity) = upgrade {
+        let player = builder
+            .player
+            .and_then(|player| player_query.get_mut(player).ok());
+        if let (Some(mut player), Ok(mut ability)) =
+            (player, ability_query.q1_mut().get_mut(entity))
+        {
+            if let Some(price) = ability
+                .upgrade_price()
+                .filter(|&price| player.money >= price)
+            {
+                player.money -= price;
+                let level = ability.level + 1;
+                ability.set_level(level);
+                ev_save.send(SaveGameEvent);
+            }
+        }
+    }
+}
+
+/// Pick the deck taken into the battle
+pub fn deck_select_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    mut progress: ResMut<CampaignProgress>,
+    mut query: Query<&mut Player, With<LocalPlayer>>,
+    card_query: Query<&Card>,
+) {
+    let mut player = match query.single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    egui::Window::new("Select Deck").show(egui_context.ctx(), |ui| {
+        let mut selected_deck = player.selected_deck;
+        egui::Grid::new("decks").striped(true).show(ui, |ui| {
+            for (index, deck) in player.decks.iter().enumerate() {
+                ui.radio_value(&mut selected_deck, index, &deck.name);
+                let character = card_query
+                    .get(deck.character_card)
+                    .map(|card| card.name.clone())
+                    .unwrap_or_default();
+                ui.label(character);
+                ui.label(format!("{} cards", deck.cards.len()));
+                ui.end_row();
+            }
+        });
+        player.selected_deck = selected_deck;
+        ui.separator();
+        let errors = match player.deck() {
+            Some(deck) => deck_rule_errors(&deck.cards, &card_query),
+            None => vec!["No deck selected".into()],
+        };
+        for error in errors.iter() {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        ui.horizontal(|ui| {
+            let start = egui::Button::new("Start Battle");
+            if ui.add_enabled(errors.is_empty(), start).clicked() {
+                ev_save.send(SaveGameEvent);
+                app_state.set(AppState::Battle).unwrap();
+            }
+            if ui.button("Back").clicked() {
+                if progress.pending.take().is_some() {
+                    app_state.set(AppState::Campaign).unwrap();
+                } else {
+                    app_state.set(AppState::Lobby).unwrap();
+                }
+            }
+        });
+    });
+}
+
+fn card_detail_ui(
+    ui: &mut egui::Ui,
+    card: &Card,
+    money: i32,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> Option<Entity> {
+    let mut upgrade = None;
+    ui.label(format!("{} (Cost: {})", card.name, card.cost));
+    ui.label(format!(
+        "Used: {}  Wins: {}  Loses: {}",
+        card.use_count, card.wins, card.loses
+    ));
+    match card.card_type {
+        CardType::Unit(unit) => {
+            if let Ok(unit) = unit_query.get(unit) {
+                ui.label(&unit.description);
+                ui.label(format!(
+                    "Level: {}  Exp: {}/{}  Deaths: {}",
+                    unit.level,
+                    unit.exp,
+                    unit.exp_to_level_up(),
+                    unit.dead_count
+                ));
+                ui.label(format!(
+                    "HP: {}  ATK: {}  DEF: {}  {:?}",
+                    unit.hp, unit.atk, unit.def, unit.range
+                ));
+                for (entity, ability) in unit
+                    .abilities
+                    .iter()
+                    .filter_map(|&ability| Some((ability, ability_query.get(ability).ok()?)))
+                {
+                    ui.separator();
+                    ui.label(format!("{} ({} AP)", ability.name, ability.ap));
+                    ui.label(&ability.description);
+                    ui.label(format!(
+                        "Level: {}/{}  Used: {}",
+                        ability.level,
+                        ability.max_level(),
+                        ability.use_count
+                    ));
+                    if let Some(price) = ability.upgrade_price() {
+                        let button = egui::Button::new(format!("Upgrade ({})", price));
+                        if ui.add_enabled(money >= price, button).clicked() {
+                            upgrade = Some(entity);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    upgrade
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legal_deck_has_no_errors() {
+        let cards = [CardDataId::Solider, CardDataId::Solider, CardDataId::Archer];
+        assert!(card_rule_errors(&cards).is_empty());
+    }
+
+    #[test]
+    fn deck_size_is_bounded() {
+        let small = [CardDataId::Solider, CardDataId::Archer];
+        assert_eq!(
+            card_rule_errors(&small),
+            vec![format!("Deck needs at least {} cards", DECK_MIN_SIZE)]
+        );
+        let large: Vec<_> = [CardDataId::Solider, CardDataId::Archer, CardDataId::Spare]
+            .iter()
+            .cycle()
+            .take(DECK_MAX_SIZE + 1)
+            .copied()
+            .collect();
+        assert!(card_rule_errors(&large)
+            .contains(&format!("Deck can have at most {} cards", DECK_MAX_SIZE)));
+    }
+
+    #[test]
+    fn copies_are_counted_once_per_card() {
+        let cards = vec![CardDataId::Solider; MAX_COPIES + 1];
+        assert_eq!(
+            card_rule_errors(&cards),
+            vec![format!(
+                "Soldier: {} copies, at most {}",
+                MAX_COPIES + 1,
+                MAX_COPIES
+            )]
+        );
+    }
+}
@@ -1,40 +1,87 @@
 use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::game::components::{
-    Ability, AbilityDataId, Card, CardDataId, CardDataType, CardType, Player, Unit, UnitDataId,
+    Ability, AbilityDataId, Card, CardDataId, CardDataType, CardType, Deck, EnemyDataId,
+    EnemyPlayer, LocalPlayer, Player, PlayerSlot, Unit, UnitDataId,
 };
 
-fn spawn_ability(commands: &mut Commands, ability_data_id: AbilityDataId) -> Entity {
+use super::{
+    save::{read_save, AbilitySave, CardSave, PlayerSave},
+    CampaignProgress,
+};
+
+/// Randomness outside of battles, shop pulls
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+fn spawn_ability(
+    commands: &mut Commands,
+    ability_data_id: AbilityDataId,
+    save: Option<&AbilitySave>,
+) -> Entity {
+    let mut ability = Ability::new(ability_data_id);
+    if let Some(save) = save {
+        ability.set_level(save.level);
+        ability.use_count = save.use_count;
+    }
     let ability_id = commands
         .spawn()
-        .insert(Ability::new(ability_data_id))
+        .insert(ability)
         .insert(Name::new("Ability"))
         .id();
     ability_id
 }
-fn spawn_unit(commands: &mut Commands, unit_data_id: UnitDataId) -> Entity {
+fn spawn_unit(commands: &mut Commands, unit_data_id: UnitDataId, save: &CardSave) -> Entity {
     let unit_id = commands.spawn().id();
     let data = unit_data_id.unit_data();
     let abilities: Vec<_> = data
         .abilities
         .iter()
-        .map(|&ability_data_id| spawn_ability(commands, ability_data_id))
+        .enumerate()
+        .map(|(i, &ability_data_id)| {
+            // without an id the entries are in the order of the unit data, older replays
+            let ability_save = save
+                .abilities
+                .iter()
+                .find(|ability| ability.ability_data_id == Some(ability_data_id))
+                .or_else(|| {
+                    save.abilities
+                        .get(i)
+                        .filter(|ability| ability.ability_data_id.is_none())
+                });
+            spawn_ability(commands, ability_data_id, ability_save)
+        })
         .collect();
     commands.entity(unit_id).push_children(&abilities[..]);
+    let mut unit = Unit::new(unit_data_id, abilities);
+    unit.set_level(save.level);
+    unit.exp = save.exp;
+    unit.dead_count = save.dead_count;
     commands
         .entity(unit_id)
-        .insert(Unit::new(unit_data_id, abilities))
+        .insert(unit)
         .insert(Name::new("Unit"))
         .id();
     unit_id
 }
 
-fn spawn_card(commands: &mut Commands, card_data_id: CardDataId) -> Entity {
+pub fn spawn_card(commands: &mut Commands, card_data_id: CardDataId) -> Entity {
+    spawn_saved_card(commands, &CardSave::new(card_data_id))
+}
+
+pub fn spawn_saved_card(commands: &mut Commands, save: &CardSave) -> Entity {
+    let card_data_id = save.card_data_id;
     let card_id = commands.spawn().id();
     let data = card_data_id.card_data();
     let card_type = match data.card_type {
         CardDataType::Unit(unit_data_id) => {
-            let unit_id = spawn_unit(commands, unit_data_id);
+            let unit_id = spawn_unit(commands, unit_data_id, save);
             let card_type = CardType::Unit(unit_id);
             commands.entity(card_id).push_children(&[unit_id]);
             card_type
@@ -42,23 +89,70 @@ fn spawn_card(commands: &mut Commands, card_data_id: CardDataId) -> Entity {
     };
     commands
         .entity(card_id)
-        .insert(Card::new(card_data_id, card_type))
+        .insert(Card {
+            use_count: save.use_count,
+            wins: save.wins,
+            loses: save.loses,
+            ..Card::new(card_data_id, card_type)
+        })
         .insert(Name::new("Card"))
         .id();
     card_id
 }
 
+fn spawn_saved_player(commands: &mut Commands, game: Entity, save: &PlayerSave) {
+    let mut cards = |saves: &Vec<CardSave>| {
+        let cards: Vec<_> = saves
+            .iter()
+            .map(|save| spawn_saved_card(commands, save))
+            .collect();
+        commands.entity(game).push_children(&cards[..]);
+        cards
+    };
+    let characters = cards(&save.characters);
+    let decks = save
+        .decks
+        .iter()
+        .map(|deck| Deck {
+            name: deck.name.clone(),
+            cards: cards(&deck.cards),
+            character_card: characters[deck.character_card.min(characters.len() - 1)],
+        })
+        .collect();
+    let collection = cards(&save.cards);
+    let player = commands
+        .spawn()
+        .insert(Player {
+            name: save.name.clone(),
+            money: save.money,
+            decks,
+            selected_deck: save.selected_deck,
+            cards: collection,
+            characters,
+        })
+        .insert(LocalPlayer)
+        .insert(Name::new("Player"))
+        .id();
+    commands.entity(game).push_children(&[player]);
+}
+
 pub fn load_game(mut commands: Commands) {
     let game = commands.spawn().id();
-    {
-        // Load player
+    if let Some(save) = read_save() {
+        spawn_saved_player(&mut commands, game, &save.player);
+        commands.insert_resource(CampaignProgress::load(&save.campaign.cleared));
+    } else {
+        // New player
         let decks = vec![
             spawn_card(&mut commands, CardDataId::Solider),
             spawn_card(&mut commands, CardDataId::Solider),
+            spawn_card(&mut commands, CardDataId::Archer),
         ];
         let cards = vec![
             spawn_card(&mut commands, CardDataId::Spare),
             spawn_card(&mut commands, CardDataId::Spare),
+            spawn_card(&mut commands, CardDataId::Solider),
+            spawn_card(&mut commands, CardDataId::Archer),
         ];
         let character_card = spawn_card(&mut commands, CardDataId::You);
         commands.entity(game).push_children(&decks[..]);
@@ -69,10 +163,16 @@ pub fn load_game(mut commands: Commands) {
             .insert(Player {
                 name: "You".into(),
                 money: 100,
-                decks,
+                decks: vec![Deck {
+                    name: "Starter".into(),
+                    cards: decks,
+                    character_card,
+                }],
+                selected_deck: 0,
                 cards,
-                character_card,
+                characters: vec![character_card],
             })
+            .insert(LocalPlayer)
             .insert(Name::new("Player"))
             .id();
         commands.entity(game).push_children(&[player]);
@@ -82,6 +182,7 @@ pub fn load_game(mut commands: Commands) {
         let decks = vec![
             spawn_card(&mut commands, CardDataId::Demon),
             spawn_card(&mut commands, CardDataId::Demon),
+            spawn_card(&mut commands, CardDataId::Demon),
         ];
 
         let character_card = spawn_card(&mut commands, CardDataId::Enemy);
@@ -92,10 +193,81 @@ pub fn load_game(mut commands: Commands) {
             .insert(Player {
                 name: "Enemy".into(),
                 money: 100,
-                decks,
+                decks: vec![Deck {
+                    name: "Starter".into(),
+                    cards: decks,
+                    character_card,
+                }],
+                selected_deck: 0,
+                cards: vec![],
+                characters: vec![character_card],
+            })
+            .insert(PlayerSlot::Opponent)
+            .insert(Name::new("Player"))
+            .id();
+        commands.entity(game).push_children(&[player]);
+    }
+    {
+        // Load ally and rival for battles with more than two players, and the second hotseat human
+        for (name, character, slot) in [
+            ("Ally", CardDataId::Ally, PlayerSlot::Ally),
+            ("Rival", CardDataId::Rival, PlayerSlot::Rival),
+            ("Player 2", CardDataId::You, PlayerSlot::SecondHuman),
+        ] {
+            let decks = vec![
+                spawn_card(&mut commands, CardDataId::Solider),
+                spawn_card(&mut commands, CardDataId::Solider),
+                spawn_card(&mut commands, CardDataId::Archer),
+            ];
+            let character_card = spawn_card(&mut commands, character);
+            commands.entity(game).push_children(&decks[..]);
+            commands.entity(game).push_children(&[character_card]);
+            let player = commands
+                .spawn()
+                .insert(Player {
+                    name: name.into(),
+                    money: 100,
+                    decks: vec![Deck {
+                        name: "Starter".into(),
+                        cards: decks,
+                        character_card,
+                    }],
+                    selected_deck: 0,
+                    cards: vec![],
+                    characters: vec![character_card],
+                })
+                .insert(slot)
+                .insert(Name::new("Player"))
+                .id();
+            commands.entity(game).push_children(&[player]);
+        }
+    }
+    // Load campaign enemies
+    for enemy in EnemyDataId::all() {
+        let data = enemy.enemy_data();
+        let decks: Vec<_> = data
+            .deck
+            .iter()
+            .map(|&card_data_id| spawn_card(&mut commands, card_data_id))
+            .collect();
+        let character_card = spawn_card(&mut commands, data.character);
+        commands.entity(game).push_children(&decks[..]);
+        commands.entity(game).push_children(&[character_card]);
+        let player = commands
+            .spawn()
+            .insert(Player {
+                name: data.name,
+                money: 0,
+                decks: vec![Deck {
+                    name: "Starter".into(),
+                    cards: decks,
+                    character_card,
+                }],
+                selected_deck: 0,
                 cards: vec![],
-                character_card,
+                characters: vec![character_card],
             })
+            .insert(EnemyPlayer(enemy))
             .insert(Name::new("Player"))
             .id();
         commands.entity(game).push_children(&[player]);
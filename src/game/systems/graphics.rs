@@ -2,15 +2,18 @@ use bevy::prelude::*;
 
 use crate::{
     game::components::{
-        ApText, CardInstance, CardName, CardSprite, Cell, HighlightSprite, HpText, Position,
-        SelectType, Selectable, UnitInstance,
+        ApText, CardBack, CardFace, CardInstance, CardName, CardSprite, Cell, HighlightSprite,
+        HpText, Position, SelectType, Selectable, UnitInstance,
     },
     game::{
-        components::{AtkText, Board, DefText, PlayerInstance, PlayerInstanceRef},
-        systems::HighlightColor,
+        components::{AtkText, Board, CellType, DefText, PlayerInstance, PlayerInstanceRef},
+        systems::{HighlightColor, Hotseat, Spectator},
     },
 };
 
+/// Seconds a card takes to turn over
+const FLIP_TIME: f32 = 0.3;
+
 // pub fn update_hp_text(
 //     card_query: Query<&Card, Changed<Card>>,
 //     mut text_query: Query<(&mut Text, &Parent), With<HpText>>,
@@ -65,11 +68,21 @@ pub fn update_def_text(
     }
 }
 
+/// Size of the board the position belongs to
+fn board_of(board_query: &Query<(&Board, &PlayerInstanceRef)>, player_id: Entity) -> Board {
+    board_query
+        .iter()
+        .find(|(_, player)| player.0 == player_id)
+        .map(|(&board, _)| board)
+        .unwrap()
+}
+
 pub fn update_transform_by_position(
     mut query: Query<(&Position, &mut Transform), Changed<Position>>,
+    board_query: Query<(&Board, &PlayerInstanceRef)>,
 ) {
     for (pos, mut tranform) in query.iter_mut() {
-        tranform.translation = pos.xyz();
+        tranform.translation = pos.xyz(&board_of(&board_query, pos.player_id));
     }
 }
 
@@ -78,6 +91,7 @@ pub fn attach_graphic_to_card(
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     query: Query<(Entity, &CardInstance, &Position), Added<CardInstance>>,
+    board_query: Query<(&Board, &PlayerInstanceRef)>,
 ) {
     let size = Vec2::new(95.0, 95.0);
     for (entity, card, position) in query.iter() {
@@ -85,7 +99,7 @@ pub fn attach_graphic_to_card(
             let child_id = commamds
                 .spawn_bundle(Text2dBundle {
                     text: Text::with_section(
-                        format!("{}", card.name.clone()),
+                        card.name.clone(),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 30.0,
@@ -97,6 +111,7 @@ pub fn attach_graphic_to_card(
                         },
                     ),
                     transform: Transform::from_xyz(0.0, 0.0, 0.2),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -120,6 +135,7 @@ pub fn attach_graphic_to_card(
                             .into(),
                     ),
                     transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -128,6 +144,24 @@ pub fn attach_graphic_to_card(
                 .insert(CardSprite(child_id))
                 .push_children(&[child_id]);
         }
+        {
+            let child_id = commamds
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        size,
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgb(0.3, 0.2, 0.45).into()),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.25),
+                    visible: face_visible(!position.face_up),
+                    ..Default::default()
+                })
+                .id();
+            commamds
+                .entity(entity)
+                .insert(CardBack(child_id))
+                .push_children(&[child_id]);
+        }
         {
             let child_id = commamds
                 .spawn_bundle(SpriteBundle {
@@ -148,7 +182,7 @@ pub fn attach_graphic_to_card(
             .entity(entity)
             .insert_bundle((
                 Transform {
-                    translation: position.xyz(),
+                    translation: position.xyz(&board_of(&board_query, position.player_id)),
                     ..Default::default()
                 },
                 GlobalTransform::default(),
@@ -157,16 +191,20 @@ pub fn attach_graphic_to_card(
                 size,
                 select_type: SelectType::Card,
             })
-            .insert(HighlightColor::None);
+            .insert(HighlightColor::None)
+            .insert(CardFace {
+                shown: position.face_up,
+                flip: 0.0,
+            });
     }
 }
 
 pub fn attach_graphic_to_unit(
     mut commamds: Commands,
     asset_server: Res<AssetServer>,
-    query: Query<(Entity, &UnitInstance), Added<UnitInstance>>,
+    query: Query<(Entity, &UnitInstance, &Position), Added<UnitInstance>>,
 ) {
-    for (entity, unit) in query.iter() {
+    for (entity, unit, position) in query.iter() {
         {
             let child_id = commamds
                 .spawn_bundle(Text2dBundle {
@@ -183,6 +221,7 @@ pub fn attach_graphic_to_unit(
                         },
                     ),
                     transform: Transform::from_xyz(0.0, 40.0, 0.2),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -207,6 +246,7 @@ pub fn attach_graphic_to_unit(
                         },
                     ),
                     transform: Transform::from_xyz(0.0, -40.0, 0.2),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -231,6 +271,7 @@ pub fn attach_graphic_to_unit(
                         },
                     ),
                     transform: Transform::from_xyz(-40.0, -40.0, 0.2),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -255,6 +296,7 @@ pub fn attach_graphic_to_unit(
                         },
                     ),
                     transform: Transform::from_xyz(40.0, -40.0, 0.2),
+                    visible: face_visible(position.face_up),
                     ..Default::default()
                 })
                 .id();
@@ -304,8 +346,9 @@ pub fn attach_graphic_to_cell(
     mut commamds: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     query: Query<(Entity, &Cell, &Position), Added<Cell>>,
+    board_query: Query<(&Board, &PlayerInstanceRef)>,
 ) {
-    for (entity, _, position) in query.iter() {
+    for (entity, cell, position) in query.iter() {
         {
             let child_id = commamds
                 .spawn_bundle(SpriteBundle {
@@ -330,10 +373,19 @@ pub fn attach_graphic_to_cell(
                     resize_mode: SpriteResizeMode::Manual,
                     ..Default::default()
                 },
-                material: materials.add(Color::rgb(0.5, 0.5, 0.9).into()),
+                material: materials.add(
+                    match cell.cell_type {
+                        CellType::Normal => Color::rgb(0.5, 0.5, 0.9),
+                        CellType::Blocked => Color::rgb(0.2, 0.2, 0.2),
+                        CellType::HealingSpring => Color::rgb(0.4, 0.8, 0.5),
+                        CellType::Trap => Color::rgb(0.8, 0.4, 0.3),
+                        CellType::Fortified => Color::rgb(0.6, 0.6, 0.6),
+                    }
+                    .into(),
+                ),
                 transform: Transform {
                     translation: {
-                        let mut pos = position.xyz();
+                        let mut pos = position.xyz(&board_of(&board_query, position.player_id));
                         pos.z = 0.0;
                         pos
                     },
@@ -390,3 +442,68 @@ pub fn attach_graphic_to_board(
         }
     }
 }
+
+/// Children on the shown side of a card are visible, sprites and texts are transparent
+fn face_visible(is_visible: bool) -> Visible {
+    Visible {
+        is_visible,
+        is_transparent: true,
+    }
+}
+
+/// Show the back of every card the local screen may not see, turning cards over when that changes
+pub fn card_face_system(
+    time: Res<Time>,
+    hotseat: Res<Hotseat>,
+    spectator: Res<Spectator>,
+    mut card_query: Query<(
+        &Position,
+        &mut CardFace,
+        &mut Transform,
+        &CardName,
+        &CardSprite,
+        &CardBack,
+        Option<(&HpText, &ApText, &AtkText, &DefText)>,
+    )>,
+    mut visible_query: Query<&mut Visible>,
+) {
+    // a hotseat screen waiting for the next player shows no hand
+    let viewer = hotseat.viewer.filter(|_| hotseat.ready);
+    let half = FLIP_TIME / 2.0;
+    for (position, mut face, mut transform, name, sprite, back, texts) in card_query.iter_mut() {
+        let shown = spectator.show_hidden || position.is_visible_to(viewer);
+        if shown != face.shown {
+            face.shown = shown;
+            face.flip = FLIP_TIME;
+        }
+        if face.flip > 0.0 {
+            face.flip = (face.flip - time.delta_seconds()).max(0.0);
+        }
+        // the old side narrows away, then the new side widens back
+        let front = if face.flip > half {
+            !face.shown
+        } else {
+            face.shown
+        };
+        let scale = ((face.flip - half) / half).abs();
+        if transform.scale.x != scale {
+            transform.scale.x = scale;
+        }
+
+        let mut set_visible = |entity: Entity, is_visible: bool| {
+            if let Ok(mut visible) = visible_query.get_mut(entity) {
+                if visible.is_visible != is_visible {
+                    visible.is_visible = is_visible;
+                }
+            }
+        };
+        set_visible(back.0, !front);
+        set_visible(name.0, front);
+        set_visible(sprite.0, front);
+        if let Some((hp, ap, atk, def)) = texts {
+            for text in [hp.0, ap.0, atk.0, def.0] {
+                set_visible(text, front);
+            }
+        }
+    }
+}
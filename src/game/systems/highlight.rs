@@ -8,6 +8,7 @@ pub enum HighlightColor {
     Green,
     Yellow,
     GreenYellow,
+    #[allow(dead_code)]
     Red,
 }
 impl Default for HighlightColor {
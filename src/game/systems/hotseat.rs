@@ -0,0 +1,105 @@
+use bevy::{prelude::*, render::camera::Camera};
+use bevy_egui::{egui, EguiContext};
+
+use crate::game::components::{Board, PlayerController, PlayerInstance};
+
+use super::Battle;
+
+/// Which human the screen belongs to, for battles with more than one human
+#[derive(Debug, Clone, Copy)]
+pub struct Hotseat {
+    /// Human player the camera and hands are shown for
+    pub viewer: Option<Entity>,
+    /// Cleared when another human takes over, until they press Ready
+    pub ready: bool,
+}
+
+impl Default for Hotseat {
+    fn default() -> Self {
+        Self {
+            viewer: None,
+            ready: true,
+        }
+    }
+}
+
+/// Follow the human player in control and flip the camera to their board, other hands stay face down
+pub fn hotseat_system(
+    mut hotseat: ResMut<Hotseat>,
+    battle: Res<Option<Battle>>,
+    controller_query: Query<(Entity, &PlayerController)>,
+    board_query: Query<&Transform, (With<Board>, Without<Camera>)>,
+    mut camera_query: Query<(&mut Transform, &Camera)>,
+) {
+    let battle = match battle.as_ref() {
+        Some(battle) => battle,
+        None => {
+            if hotseat.viewer.is_some() {
+                *hotseat = Hotseat::default();
+                for (mut transform, camera) in camera_query.iter_mut() {
+                    if camera.name == Some("Camera2d".into()) {
+                        transform.rotation = Quat::IDENTITY;
+                    }
+                }
+            }
+            return;
+        }
+    };
+    let humans = battle
+        .players
+        .iter()
+        .filter(|&&player| {
+            controller_query
+                .get(player)
+                .map(|(_, &controller)| controller == PlayerController::Player)
+                .unwrap_or(false)
+        })
+        .count();
+    let viewer = battle.human_player(&controller_query);
+    if viewer != hotseat.viewer {
+        hotseat.viewer = viewer;
+        // a single human never has to pass the screen
+        hotseat.ready = humans <= 1;
+    }
+    let viewer = match viewer {
+        Some(viewer) => viewer,
+        None => return,
+    };
+
+    let rotation = battle
+        .players
+        .iter()
+        .position(|&player| player == viewer)
+        .and_then(|index| board_query.get(battle.boards[index]).ok())
+        .map(|transform| transform.rotation)
+        .unwrap_or(Quat::IDENTITY);
+    for (mut transform, camera) in camera_query.iter_mut() {
+        if camera.name == Some("Camera2d".into()) && transform.rotation != rotation {
+            transform.rotation = rotation;
+        }
+    }
+}
+
+/// Cover the battle until the next human takes the screen
+pub fn hotseat_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut hotseat: ResMut<Hotseat>,
+    player_query: Query<&PlayerInstance>,
+) {
+    if hotseat.ready {
+        return;
+    }
+    let name = hotseat
+        .viewer
+        .and_then(|viewer| player_query.get(viewer).ok())
+        .map(|player| player.name.clone())
+        .unwrap_or_default();
+    egui::Window::new("Hotseat")
+        .collapsible(false)
+        .show(egui_context.ctx(), |ui| {
+            ui.label(format!("Pass to {}", name));
+            if ui.button("Ready").clicked() {
+                hotseat.ready = true;
+            }
+        });
+}
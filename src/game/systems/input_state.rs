@@ -1,7 +1,8 @@
 use bevy::prelude::*;
 
+use super::{Battle, Hotseat};
 use crate::game::{
-    components::{PlayerController, Position, PositionType},
+    components::{CardInstance, PlayerController, Position, PositionType, UnitInstanceRef},
     events::{
         InputMappingEvent, PlayerAction, PlayerActionEvent, SelectEntityEvent,
         SelectionChangedEvent,
@@ -14,6 +15,8 @@ pub enum InputState {
     SelectedCell(Entity),
     SelectedUnitCard(Entity),
     SelectedUnitCardInHand(Entity),
+    /// Instant picked in the response window, the next clicked card is its target
+    SelectedInstant(Entity),
 }
 
 pub fn input_event_system(
@@ -22,46 +25,68 @@ pub fn input_event_system(
     mut ev_player_action: EventWriter<PlayerActionEvent>,
     mut ev_select_entity: EventWriter<SelectEntityEvent>,
     pos_q: Query<&Position>,
+    card_pos_q: Query<&Position, With<CardInstance>>,
     controller_query: Query<(Entity, &PlayerController)>,
+    ability_query: Query<&UnitInstanceRef>,
+    battle: Res<Option<Battle>>,
+    hotseat: Res<Hotseat>,
 ) {
+    // hotseat: wait until the next player took over
+    let player_id = match battle
+        .as_ref()
+        .as_ref()
+        .and_then(|battle| battle.human_player(&controller_query))
+    {
+        Some(player_id) if hotseat.ready => player_id,
+        _ => return,
+    };
     for ev in ev_input.iter() {
         info!("Input Event: {:?}", ev);
         match ev {
-            InputMappingEvent::EndTurn => {
-                let (player_id, _) = controller_query
-                    .iter()
-                    .find(|(_, &p)| p == PlayerController::Player)
-                    .unwrap();
-                ev_player_action.send(PlayerActionEvent {
-                    player_id,
-                    action: PlayerAction::EndTurn,
-                })
-            }
+            InputMappingEvent::EndTurn => ev_player_action.send(PlayerActionEvent {
+                player_id,
+                action: PlayerAction::EndTurn,
+            }),
             InputMappingEvent::ClickCell(entity) => match *input_state {
                 InputState::InTurn => {
                     *input_state = InputState::SelectedCell(*entity);
                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                 }
-                InputState::SelectedCell(_) | InputState::SelectedUnitCard(_) => {
+                InputState::SelectedCell(_) => {
                     *input_state = InputState::SelectedCell(*entity);
                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                 }
+                InputState::SelectedUnitCard(e) => {
+                    let unit_pos = pos_q.get(e).unwrap();
+                    let pos = pos_q.get(*entity).unwrap();
+                    let empty = !card_pos_q.iter().any(|p| p.same_cell(pos));
+                    if unit_pos.position_type == PositionType::Board
+                        && pos.player_id == player_id
+                        && empty
+                    {
+                        // keep the unit selected after moving
+                        ev_player_action.send(PlayerActionEvent {
+                            player_id,
+                            action: PlayerAction::Move { unit: e, to: *pos },
+                        });
+                    } else {
+                        *input_state = InputState::SelectedCell(*entity);
+                        ev_select_entity.send(SelectEntityEvent(Some(*entity)));
+                    }
+                }
                 InputState::SelectedUnitCardInHand(e) => {
-                    let (player_id, _) = controller_query
-                        .iter()
-                        .find(|(_, &p)| p == PlayerController::Player)
-                        .unwrap();
                     let pos = pos_q.get(*entity).unwrap();
                     ev_player_action.send(PlayerActionEvent {
                         player_id,
                         action: PlayerAction::SummonUnit {
                             card: e,
-                            position: pos.clone(),
+                            position: *pos,
                         },
                     });
                     *input_state = InputState::InTurn;
                     ev_select_entity.send(SelectEntityEvent(None));
                 }
+                InputState::SelectedInstant(_) => (),
             },
             InputMappingEvent::ClickCard(entity) => match *input_state {
                 InputState::InTurn | InputState::SelectedCell(_) => {
@@ -71,7 +96,10 @@ pub fn input_event_system(
                             *input_state = InputState::SelectedUnitCardInHand(*entity);
                             ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                         }
-                        PositionType::Deck | PositionType::Board | PositionType::Grave => {
+                        PositionType::Deck
+                        | PositionType::Board
+                        | PositionType::Grave
+                        | PositionType::Exile => {
                             *input_state = InputState::SelectedUnitCard(*entity);
                             ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                         }
@@ -87,15 +115,11 @@ pub fn input_event_system(
                                     *input_state = InputState::SelectedUnitCardInHand(*entity);
                                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                                 }
-                                PositionType::Deck | PositionType::Grave => {
+                                PositionType::Deck | PositionType::Grave | PositionType::Exile => {
                                     *input_state = InputState::SelectedUnitCard(*entity);
                                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                                 }
                                 PositionType::Board => {
-                                    let (player_id, _) = controller_query
-                                        .iter()
-                                        .find(|(_, &p)| p == PlayerController::Player)
-                                        .unwrap();
                                     ev_player_action.send(PlayerActionEvent {
                                         player_id,
                                         action: PlayerAction::Attack {
@@ -106,14 +130,20 @@ pub fn input_event_system(
                                 }
                             }
                         }
-                        PositionType::Deck | PositionType::Hand | PositionType::Grave => {
+                        PositionType::Deck
+                        | PositionType::Hand
+                        | PositionType::Grave
+                        | PositionType::Exile => {
                             let new_pos = pos_q.get(*entity).unwrap();
                             match new_pos.position_type {
                                 PositionType::Hand => {
                                     *input_state = InputState::SelectedUnitCardInHand(*entity);
                                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                                 }
-                                PositionType::Deck | PositionType::Grave | PositionType::Board => {
+                                PositionType::Deck
+                                | PositionType::Grave
+                                | PositionType::Board
+                                | PositionType::Exile => {
                                     *input_state = InputState::SelectedUnitCard(*entity);
                                     ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                                 }
@@ -128,18 +158,36 @@ pub fn input_event_system(
                             *input_state = InputState::SelectedUnitCardInHand(*entity);
                             ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                         }
-                        PositionType::Deck | PositionType::Board | PositionType::Grave => {
+                        PositionType::Deck
+                        | PositionType::Board
+                        | PositionType::Grave
+                        | PositionType::Exile => {
                             *input_state = InputState::SelectedUnitCard(*entity);
                             ev_select_entity.send(SelectEntityEvent(Some(*entity)));
                         }
                     }
                 }
+                InputState::SelectedInstant(ability) => {
+                    if let Ok(source) = ability_query.get(ability) {
+                        ev_player_action.send(PlayerActionEvent {
+                            player_id,
+                            action: PlayerAction::UseAbility {
+                                ability,
+                                source: source.0,
+                                target: *entity,
+                            },
+                        });
+                    }
+                    *input_state = InputState::InTurn;
+                    ev_select_entity.send(SelectEntityEvent(None));
+                }
             },
             InputMappingEvent::Cancel => match *input_state {
                 InputState::InTurn => (),
                 InputState::SelectedCell(_)
                 | InputState::SelectedUnitCard(_)
-                | InputState::SelectedUnitCardInHand(_) => {
+                | InputState::SelectedUnitCardInHand(_)
+                | InputState::SelectedInstant(_) => {
                     *input_state = InputState::InTurn;
                     ev_select_entity.send(SelectEntityEvent(None));
                 }
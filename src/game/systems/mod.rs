@@ -1,29 +1,96 @@
 mod battle;
 mod battle_action;
+mod battle_end;
+mod battle_rule;
 mod battle_setup;
 mod battle_ui;
+mod campaign;
 mod cursor;
+mod deck_builder;
 mod game;
 mod graphics;
 mod highlight;
+mod hotseat;
 mod input;
 mod input_state;
+mod net;
+mod run;
+mod save;
 mod selection;
+mod server;
+mod server_client;
+mod shop;
+mod spectator;
 
 pub use graphics::attach_graphic_to_board;
 pub use graphics::attach_graphic_to_card;
 pub use graphics::attach_graphic_to_cell;
 pub use graphics::attach_graphic_to_unit;
+pub use graphics::card_face_system;
 pub use graphics::update_ap_text;
 pub use graphics::update_atk_text;
 pub use graphics::update_def_text;
 pub use graphics::update_hp_text;
 pub use graphics::update_transform_by_position;
 
+pub use battle_end::battle_end_ui_system;
+pub use battle_end::give_battle_rewards;
+pub use battle_end::UnitResult;
+
 pub use battle_setup::cleanup_battle;
 pub use battle_setup::start_battle;
+pub use battle_setup::BattleConfig;
+
+pub use campaign::campaign_battle_end;
+pub use campaign::campaign_ui_system;
+pub use campaign::CampaignProgress;
 
 pub use game::load_game;
+pub use game::spawn_card;
+pub use game::GameRng;
+
+pub use deck_builder::deck_builder_ui_system;
+pub use deck_builder::deck_select_ui_system;
+pub use deck_builder::open_deck_builder;
+pub use deck_builder::DeckBuilder;
+
+pub use net::net_battle_system;
+pub use net::net_connection_system;
+pub use net::net_status_ui_system;
+pub use net::online_ui_system;
+pub use net::NetSession;
+
+pub use run::run_battle_end;
+pub use run::run_ui_system;
+pub use run::RunState;
+
+pub use save::save_game_system;
+pub use save::CardSave;
+
+pub use server::server_connection_system;
+pub use server::server_stream_system;
+pub use server::BattleServer;
+pub use server::BattleView;
+pub use server::ClientMessage;
+pub use server::ServerMessage;
+pub use server::SERVER_ADDRESS;
+pub use server::SERVER_VERSION;
+
+pub use server_client::server_battle_ui_system;
+pub use server_client::server_client_system;
+pub use server_client::ServerClient;
+
+pub use spectator::close_spectator;
+pub use spectator::not_spectating;
+pub use spectator::save_replay;
+pub use spectator::spectate_ui_system;
+pub use spectator::spectator_system;
+pub use spectator::spectator_ui_system;
+pub use spectator::Spectator;
+
+pub use shop::open_shop;
+pub use shop::shop_ui_system;
+pub use shop::Shop;
 
 pub use cursor::cursor_system;
 pub use cursor::HoveringEntity;
@@ -39,7 +106,9 @@ pub use input_state::SelectedEntity;
 pub use battle_action::player_event_system;
 
 pub use battle_ui::button_system;
+pub use battle_ui::response_ui_system;
 pub use battle_ui::setup_ui;
+pub use battle_ui::turn_timer_ui_system;
 pub use battle_ui::update_ui_system;
 pub use battle_ui::ButtonMaterials;
 pub use battle_ui::DetailUi;
@@ -51,13 +120,30 @@ pub use highlight::update_highlight_color;
 pub use highlight::HighlightColor;
 pub use highlight::SelectionBoxColorMaterials;
 
+pub use hotseat::hotseat_system;
+pub use hotseat::hotseat_ui_system;
+pub use hotseat::Hotseat;
+
+pub use battle::ai_response_system;
+pub use battle::card_exile_system;
+pub use battle::card_return_to_hand_system;
 pub use battle::flow_systme;
 pub use battle::player_draw_system;
 pub use battle::pre_turn_start_system;
 pub use battle::summon_unit_system;
+pub use battle::turn_timer_system;
 pub use battle::unit_die_system;
+pub use battle::unit_heal_system;
 pub use battle::unit_hurt_system;
+pub use battle::unit_move_system;
+pub use battle::unit_revive_system;
 pub use battle::update;
 pub use battle::Battle;
 pub use battle::BattleFlow;
+pub use battle::BattleFrame;
+pub use battle::BattleLimits;
+pub use battle::BattleResult;
 pub use battle::EventState;
+pub use battle::PlayerResult;
+pub use battle::ResponseWindow;
+pub use battle::MOVE_AP_COST;
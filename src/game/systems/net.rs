@@ -0,0 +1,1045 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    game::{
+        components::{
+            Ability, AbilityInstance, Board, Card, CardInstance, Cell, Deck, EncounterDataId,
+            LocalPlayer, ParticipantPlayer, Player, PlayerController, PlayerInstance, Position,
+            PositionType, RemotePlayer, Unit, UnitInstance,
+        },
+        events::{PlayerAction, PlayerActionEvent},
+    },
+    AppState,
+};
+
+use super::{
+    game::spawn_saved_card,
+    save::{card_save, CardSave},
+    server_client::join_battle_server,
+    Battle, BattleConfig, BattleFlow, ServerClient,
+};
+
+/// Bump when the messages change, both sides must match
+pub const NET_VERSION: u32 = 2;
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+/// Seconds between connection attempts of the guest
+const RECONNECT_TIME: f32 = 1.0;
+/// Frames a released remote action may take to be accepted before it counts as a desync
+const ACCEPT_FRAMES: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetRole {
+    Host,
+    Guest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetStatus {
+    Offline,
+    /// Host waiting for the guest, guest trying to reach the host
+    Waiting,
+    Connected,
+    /// The connection dropped, the battle waits until it is back
+    Reconnecting,
+    Failed(String),
+}
+
+/// Sent as one line of ron per message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetMessage {
+    /// First message of every connection, `received` resumes the actions after a reconnect
+    Hello {
+        version: u32,
+        name: String,
+        deck: Vec<CardSave>,
+        character: CardSave,
+        /// Battles started by the host so far
+        battles: u32,
+        /// Actions received from the peer in the current battle
+        received: u32,
+    },
+    /// The host started a battle, the guest answers with its own deck
+    Start {
+        name: String,
+        deck: Vec<CardSave>,
+        character: CardSave,
+    },
+    Action {
+        seq: u32,
+        action: NetAction,
+    },
+    /// State of the battle while it waits for the next action
+    Checksum {
+        turn: u32,
+        /// Actions taken in the battle so far
+        actions: u32,
+        checksum: u64,
+    },
+}
+
+/// `PlayerAction` with entities replaced by their net ids
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum NetAction {
+    EndTurn,
+    UseAbility {
+        ability: u32,
+        source: u32,
+        target: u32,
+    },
+    Attack {
+        source: u32,
+        target: u32,
+    },
+    SummonUnit {
+        card: u32,
+        position: NetPosition,
+    },
+    Move {
+        unit: u32,
+        to: NetPosition,
+    },
+    Pass,
+    Surrender,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NetPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub face_up: bool,
+    pub player: u32,
+    pub position_type: PositionType,
+}
+
+/// One ron message per line over a non blocking tcp stream
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: vec![],
+            outgoing: vec![],
+        })
+    }
+
+    /// Queued until the next `flush`
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        match ron::to_string(message) {
+            Ok(text) => {
+                self.outgoing.extend_from_slice(text.as_bytes());
+                self.outgoing.push(b'\n');
+            }
+            Err(e) => error!("Failed to write message: {}", e),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send what is left and close
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.flush()
+    }
+
+    /// Messages received so far, an error once the connection dropped and nothing is left
+    pub fn poll<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 4096];
+        let mut closed = false;
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let mut messages = vec![];
+        while let Some(end) = self.incoming.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            match std::str::from_utf8(&line)
+                .map_err(|e| e.to_string())
+                .and_then(|text| ron::from_str(text.trim()).map_err(|e| e.to_string()))
+            {
+                Ok(message) => messages.push(message),
+                Err(e) => warn!("Bad message: {}", e),
+            }
+        }
+        if closed && messages.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(messages)
+    }
+}
+
+/// Connection to the other player of an online battle
+///
+/// Both sides run the same battle and only exchange the actions that were accepted.
+pub struct NetSession {
+    pub role: Option<NetRole>,
+    pub address: String,
+    pub status: NetStatus,
+    /// Turn at which the battles stopped matching
+    pub desync: Option<u32>,
+    /// The `RemotePlayer`, spawned again from the deck the peer starts each battle with
+    pub peer: Option<Entity>,
+    pub battles: u32,
+    /// Host only, the battle waits for the guest's deck
+    starting: bool,
+    listener: Option<TcpListener>,
+    connection: Option<Connection>,
+    reconnect_timer: f32,
+    /// Actions of this side in the current battle, resent after a reconnect
+    sent: Vec<NetAction>,
+    /// Actions of the peer in the current battle
+    received: u32,
+    /// Remote actions waiting until the battle can take them
+    queue: VecDeque<NetAction>,
+    /// Frames since a remote action was handed to the battle
+    released: Option<u32>,
+    /// `Battle::actions` already looked at
+    synced: usize,
+    ids: NetIds,
+    /// Battle seen waiting for an action last frame, by turn and actions taken
+    waiting: Option<(u32, u32)>,
+    checksums: HashMap<(u32, u32), u64>,
+    remote_checksums: HashMap<(u32, u32), u64>,
+}
+
+impl Default for NetSession {
+    fn default() -> Self {
+        Self {
+            role: None,
+            address: DEFAULT_ADDRESS.into(),
+            status: NetStatus::Offline,
+            desync: None,
+            peer: None,
+            battles: 0,
+            starting: false,
+            listener: None,
+            connection: None,
+            reconnect_timer: 0.0,
+            sent: vec![],
+            received: 0,
+            queue: VecDeque::new(),
+            released: None,
+            synced: 0,
+            ids: NetIds::default(),
+            waiting: None,
+            checksums: HashMap::new(),
+            remote_checksums: HashMap::new(),
+        }
+    }
+}
+
+impl NetSession {
+    pub fn host(&mut self) -> Result<(), String> {
+        let listener = TcpListener::bind(&self.address).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        self.listener = Some(listener);
+        self.role = Some(NetRole::Host);
+        self.status = NetStatus::Waiting;
+        Ok(())
+    }
+
+    pub fn join(&mut self) {
+        self.role = Some(NetRole::Guest);
+        self.status = NetStatus::Waiting;
+        self.reconnect_timer = 0.0;
+    }
+
+    /// Drop the connection, returns the `RemotePlayer` to despawn
+    pub fn close(&mut self) -> Option<Entity> {
+        let peer = self.peer;
+        *self = Self {
+            address: self.address.clone(),
+            ..Default::default()
+        };
+        peer
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.role == Some(NetRole::Host)
+    }
+
+    /// Whether the online participant is played on this side
+    pub fn is_local(&self, player: ParticipantPlayer) -> bool {
+        match player {
+            ParticipantPlayer::Host => self.is_host(),
+            ParticipantPlayer::Guest => !self.is_host(),
+            _ => true,
+        }
+    }
+
+    /// Host only, sends the deck and starts the online encounter once the guest sent its own
+    pub fn start_battle(
+        &mut self,
+        player: &Player,
+        card_query: &Query<&Card>,
+        unit_query: &Query<&Unit>,
+        ability_query: &Query<&Ability>,
+    ) -> Result<(), String> {
+        let start = start(player, card_query, unit_query, ability_query)
+            .ok_or_else(|| "No deck selected".to_string())?;
+        self.send(&start);
+        self.new_battle();
+        self.starting = true;
+        Ok(())
+    }
+
+    pub fn is_starting(&self) -> bool {
+        self.starting
+    }
+
+    fn new_battle(&mut self) {
+        self.battles += 1;
+        self.desync = None;
+        self.sent.clear();
+        self.received = 0;
+        self.queue.clear();
+        self.released = None;
+        self.synced = 0;
+        self.ids.clear();
+        self.waiting = None;
+        self.checksums.clear();
+        self.remote_checksums.clear();
+    }
+
+    fn connected(&mut self, stream: TcpStream, hello: NetMessage) {
+        match Connection::new(stream) {
+            Ok(connection) => self.connection = Some(connection),
+            Err(e) => {
+                error!("Failed to set up connection: {}", e);
+                return;
+            }
+        }
+        self.status = NetStatus::Connected;
+        self.send(&hello);
+    }
+
+    fn disconnected(&mut self) {
+        self.connection = None;
+        self.status = NetStatus::Reconnecting;
+        self.reconnect_timer = RECONNECT_TIME;
+    }
+
+    /// Dropped while there is no connection, actions are resent from the hello
+    fn send(&mut self, message: &NetMessage) {
+        if let Some(connection) = &mut self.connection {
+            connection.send(message);
+        }
+    }
+
+    fn check(&mut self, key: (u32, u32)) {
+        if let (Some(local), Some(remote)) =
+            (self.checksums.get(&key), self.remote_checksums.get(&key))
+        {
+            let (turn, _) = key;
+            if local != remote && self.desync.is_none() {
+                error!("Battle desync at turn {}", turn);
+                self.desync = Some(turn);
+            }
+        }
+    }
+}
+
+/// Battle entities in net id order, the same on every side that spawned the same battle
+#[derive(Debug, Clone, Default)]
+pub struct NetIds {
+    ids: Vec<Entity>,
+}
+
+impl NetIds {
+    /// Boards and their children are spawned in the same order on every side
+    pub fn collect(
+        &mut self,
+        battle: &Battle,
+        children_query: &Query<&Children>,
+        net_query: &Query<(), BattleEntityFilter>,
+    ) {
+        self.ids.clear();
+        for &board in battle.boards.iter() {
+            collect_ids(board, children_query, net_query, &mut self.ids);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.ids.iter()
+    }
+
+    pub fn net_id(&self, entity: Entity) -> u32 {
+        self.ids
+            .iter()
+            .position(|&id| id == entity)
+            .map(|id| id as u32)
+            .unwrap_or(u32::MAX)
+    }
+
+    pub fn entity(&self, net_id: u32) -> Option<Entity> {
+        self.ids.get(net_id as usize).copied()
+    }
+
+    fn to_net_position(&self, position: &Position) -> NetPosition {
+        NetPosition {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            face_up: position.face_up,
+            player: self.net_id(position.player_id),
+            position_type: position.position_type,
+        }
+    }
+
+    fn from_net_position(&self, position: &NetPosition) -> Option<Position> {
+        Some(Position {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            face_up: position.face_up,
+            player_id: self.entity(position.player)?,
+            position_type: position.position_type,
+        })
+    }
+
+    pub fn to_net(&self, action: &PlayerAction) -> NetAction {
+        match *action {
+            PlayerAction::EndTurn => NetAction::EndTurn,
+            PlayerAction::UseAbility {
+                ability,
+                source,
+                target,
+            } => NetAction::UseAbility {
+                ability: self.net_id(ability),
+                source: self.net_id(source),
+                target: self.net_id(target),
+            },
+            PlayerAction::Attack { source, target } => NetAction::Attack {
+                source: self.net_id(source),
+                target: self.net_id(target),
+            },
+            PlayerAction::SummonUnit { card, position } => NetAction::SummonUnit {
+                card: self.net_id(card),
+                position: self.to_net_position(&position),
+            },
+            PlayerAction::Move { unit, to } => NetAction::Move {
+                unit: self.net_id(unit),
+                to: self.to_net_position(&to),
+            },
+            PlayerAction::Pass => NetAction::Pass,
+            PlayerAction::Surrender => NetAction::Surrender,
+        }
+    }
+
+    pub fn from_net(&self, action: &NetAction) -> Option<PlayerAction> {
+        Some(match *action {
+            NetAction::EndTurn => PlayerAction::EndTurn,
+            NetAction::UseAbility {
+                ability,
+                source,
+                target,
+            } => PlayerAction::UseAbility {
+                ability: self.entity(ability)?,
+                source: self.entity(source)?,
+                target: self.entity(target)?,
+            },
+            NetAction::Attack { source, target } => PlayerAction::Attack {
+                source: self.entity(source)?,
+                target: self.entity(target)?,
+            },
+            NetAction::SummonUnit { card, position } => PlayerAction::SummonUnit {
+                card: self.entity(card)?,
+                position: self.from_net_position(&position)?,
+            },
+            NetAction::Move { unit, to } => PlayerAction::Move {
+                unit: self.entity(unit)?,
+                to: self.from_net_position(&to)?,
+            },
+            NetAction::Pass => PlayerAction::Pass,
+            NetAction::Surrender => PlayerAction::Surrender,
+        })
+    }
+}
+
+/// Whether the battle would take the player's action right now
+pub fn is_ready(battle: &Battle, player: Entity, action: &NetAction) -> bool {
+    match battle.response {
+        _ if matches!(action, NetAction::Surrender) => true,
+        Some(window) => {
+            window.player == player
+                && matches!(action, NetAction::UseAbility { .. } | NetAction::Pass)
+        }
+        None => {
+            battle.stacks.is_empty()
+                && matches!(battle.stage, BattleFlow::PlayerAction)
+                && battle.players.get(battle.current_player_index) == Some(&player)
+        }
+    }
+}
+
+/// The selected deck and its character card, as sent to the other side, `None` without either
+pub fn deck_saves(
+    player: &Player,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> Option<(Vec<CardSave>, CardSave)> {
+    let deck = player.deck()?;
+    Some((
+        deck.cards
+            .iter()
+            .filter_map(|&card| card_save(card, card_query, unit_query, ability_query))
+            .collect(),
+        card_save(deck.character_card, card_query, unit_query, ability_query)?,
+    ))
+}
+
+fn hello(
+    net: &NetSession,
+    player: &Player,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> Option<NetMessage> {
+    let (deck, character) = deck_saves(player, card_query, unit_query, ability_query)?;
+    Some(NetMessage::Hello {
+        version: NET_VERSION,
+        name: player.name.clone(),
+        deck,
+        character,
+        battles: net.battles,
+        received: net.received,
+    })
+}
+
+/// Deck this side plays the next battle with
+fn start(
+    player: &Player,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> Option<NetMessage> {
+    let (deck, character) = deck_saves(player, card_query, unit_query, ability_query)?;
+    Some(NetMessage::Start {
+        name: player.name.clone(),
+        deck,
+        character,
+    })
+}
+
+pub fn spawn_remote_player(
+    commands: &mut Commands,
+    name: String,
+    deck: &[CardSave],
+    character: &CardSave,
+) -> Entity {
+    let cards = deck
+        .iter()
+        .map(|save| spawn_saved_card(commands, save))
+        .collect();
+    let character_card = spawn_saved_card(commands, character);
+    commands
+        .spawn()
+        .insert(Player {
+            name,
+            money: 0,
+            decks: vec![Deck {
+                name: "Online".into(),
+                cards,
+                character_card,
+            }],
+            selected_deck: 0,
+            cards: vec![],
+            characters: vec![character_card],
+        })
+        .insert(RemotePlayer)
+        .insert(Name::new("Remote Player"))
+        .id()
+}
+
+pub fn despawn_remote_player(
+    commands: &mut Commands,
+    entity: Entity,
+    player_query: &Query<&Player>,
+) {
+    if let Ok(player) = player_query.get(entity) {
+        for deck in player.decks.iter() {
+            for &card in deck.cards.iter() {
+                commands.entity(card).despawn_recursive();
+            }
+        }
+        for &card in player.characters.iter() {
+            commands.entity(card).despawn_recursive();
+        }
+    }
+    commands.entity(entity).despawn_recursive();
+}
+
+fn start_online_battle(app_state: &mut State<AppState>, battle_config: &mut BattleConfig) {
+    battle_config.encounter = EncounterDataId::Online;
+    // the timer runs on each side's clock, online turns have none
+    battle_config.turn_time = None;
+    battle_config.you = None;
+    if *app_state.current() != AppState::Battle {
+        app_state.set(AppState::Battle).unwrap();
+    }
+}
+
+/// Accept or reconnect, then read the messages of the peer
+pub fn net_connection_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut net: ResMut<NetSession>,
+    mut app_state: ResMut<State<AppState>>,
+    mut battle_config: ResMut<BattleConfig>,
+    player_query: Query<&Player, With<LocalPlayer>>,
+    peer_query: Query<&Player>,
+    card_query: Query<&Card>,
+    unit_query: Query<&Unit>,
+    ability_query: Query<&Ability>,
+) {
+    let net = &mut *net;
+    if net.role.is_none() || matches!(net.status, NetStatus::Failed(_)) {
+        return;
+    }
+    let player = match player_query.single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    // a new connection replaces the old one, the old one may not have noticed it dropped
+    let mut stream = None;
+    if let Some(listener) = &net.listener {
+        match listener.accept() {
+            Ok((accepted, _)) => stream = Some(accepted),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(e) => warn!("Failed to accept: {}", e),
+        }
+    } else if net.connection.is_none() {
+        net.reconnect_timer -= time.delta_seconds();
+        if net.reconnect_timer <= 0.0 {
+            net.reconnect_timer = RECONNECT_TIME;
+            match net.address.parse::<SocketAddr>() {
+                Ok(address) => {
+                    stream = TcpStream::connect_timeout(&address, Duration::from_millis(200)).ok()
+                }
+                Err(e) => net.status = NetStatus::Failed(e.to_string()),
+            }
+        }
+    }
+    if let Some(stream) = stream {
+        match hello(net, player, &card_query, &unit_query, &ability_query) {
+            Some(hello) => net.connected(stream, hello),
+            None => net.status = NetStatus::Failed("No deck selected".into()),
+        }
+    }
+
+    let messages = match net.connection.as_mut().map(Connection::poll) {
+        None => vec![],
+        Some(Ok(messages)) => messages,
+        Some(Err(e)) => {
+            warn!("Connection lost: {}", e);
+            net.disconnected();
+            return;
+        }
+    };
+    for message in messages {
+        // deck of the peer for a battle starting now
+        let started = match message {
+            NetMessage::Hello {
+                version,
+                name,
+                deck,
+                character,
+                battles,
+                received,
+            } => {
+                if version != NET_VERSION {
+                    net.status = NetStatus::Failed(format!(
+                        "Peer version {} does not match {}",
+                        version, NET_VERSION
+                    ));
+                    net.connection = None;
+                    return;
+                }
+                // a start or its answer sent while the connection was down
+                let missed = if net.is_host() {
+                    net.starting && battles == net.battles
+                } else {
+                    battles > net.battles
+                };
+                if missed && !net.is_host() {
+                    net.new_battle();
+                }
+                let resend: Vec<_> = net
+                    .sent
+                    .iter()
+                    .enumerate()
+                    .skip(received as usize)
+                    .map(|(seq, &action)| NetMessage::Action {
+                        seq: seq as u32,
+                        action,
+                    })
+                    .collect();
+                for message in resend.iter() {
+                    net.send(message);
+                }
+                if missed {
+                    Some((name, deck, character))
+                } else {
+                    if net.peer.is_none() {
+                        net.peer =
+                            Some(spawn_remote_player(&mut commands, name, &deck, &character));
+                    }
+                    None
+                }
+            }
+            NetMessage::Start {
+                name,
+                deck,
+                character,
+            } => {
+                if !net.is_host() {
+                    net.new_battle();
+                    Some((name, deck, character))
+                } else if net.starting {
+                    Some((name, deck, character))
+                } else {
+                    None
+                }
+            }
+            NetMessage::Action { seq, action } => {
+                // earlier ones were resent after a reconnect
+                if seq == net.received {
+                    net.queue.push_back(action);
+                    net.received += 1;
+                }
+                None
+            }
+            NetMessage::Checksum {
+                turn,
+                actions,
+                checksum,
+            } => {
+                net.remote_checksums.insert((turn, actions), checksum);
+                net.check((turn, actions));
+                None
+            }
+        };
+        if let Some((name, deck, character)) = started {
+            if !net.is_host() {
+                match start(player, &card_query, &unit_query, &ability_query) {
+                    Some(start) => net.send(&start),
+                    None => {
+                        net.status = NetStatus::Failed("No deck selected".into());
+                        return;
+                    }
+                }
+            }
+            net.starting = false;
+            if let Some(peer) = net.peer.take() {
+                despawn_remote_player(&mut commands, peer, &peer_query);
+            }
+            net.peer = Some(spawn_remote_player(&mut commands, name, &deck, &character));
+            start_online_battle(&mut app_state, &mut battle_config);
+        }
+    }
+
+    if let Some(Err(e)) = net.connection.as_mut().map(Connection::flush) {
+        warn!("Connection lost: {}", e);
+        net.disconnected();
+    }
+}
+
+/// Battle entities that actions and events refer to
+pub type BattleEntityFilter = Or<(
+    With<Board>,
+    With<PlayerInstance>,
+    With<CardInstance>,
+    With<Cell>,
+    With<AbilityInstance>,
+)>;
+
+/// Depth first from the entity, in the order the children were added
+pub fn collect_ids(
+    entity: Entity,
+    children_query: &Query<&Children>,
+    net_query: &Query<(), BattleEntityFilter>,
+    ids: &mut Vec<Entity>,
+) {
+    if net_query.get(entity).is_ok() {
+        ids.push(entity);
+    }
+    if let Ok(children) = children_query.get(entity) {
+        for &child in children.iter() {
+            collect_ids(child, children_query, net_query, ids);
+        }
+    }
+}
+
+/// Send this side's accepted actions, hand the remote ones to the battle and compare checksums
+pub fn net_battle_system(
+    mut net: ResMut<NetSession>,
+    battle: Res<Option<Battle>>,
+    controller_query: Query<(Entity, &PlayerController)>,
+    children_query: Query<&Children>,
+    net_query: Query<(), BattleEntityFilter>,
+    unit_query: Query<(&UnitInstance, &Position)>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+) {
+    let net = &mut *net;
+    if net.role.is_none() {
+        return;
+    }
+    let battle = match battle.as_ref() {
+        Some(battle) => battle,
+        None => {
+            net.ids.clear();
+            net.synced = 0;
+            return;
+        }
+    };
+    let remote = match battle.players.iter().copied().find(|&player| {
+        controller_query
+            .get(player)
+            .map(|(_, &controller)| controller == PlayerController::Remote)
+            .unwrap_or(false)
+    }) {
+        Some(remote) => remote,
+        None => return,
+    };
+
+    if net.ids.is_empty() {
+        net.ids.collect(battle, &children_query, &net_query);
+    }
+
+    for (player, action) in battle.actions.iter().skip(net.synced) {
+        if *player == remote {
+            net.released = None;
+        } else {
+            let action = net.ids.to_net(action);
+            let seq = net.sent.len() as u32;
+            net.sent.push(action);
+            net.send(&NetMessage::Action { seq, action });
+        }
+    }
+    net.synced = battle.actions.len();
+
+    if let Some(frames) = net.released.as_mut() {
+        *frames += 1;
+        if *frames > ACCEPT_FRAMES {
+            net.released = None;
+            net.desync.get_or_insert(battle.turn);
+        }
+    } else if let Some(action) = net.queue.front().copied() {
+        if is_ready(battle, remote, &action) {
+            net.queue.pop_front();
+            match net.ids.from_net(&action) {
+                Some(action) => {
+                    ev_player.send(PlayerActionEvent {
+                        player_id: remote,
+                        action,
+                    });
+                    net.released = Some(0);
+                }
+                None => {
+                    net.desync.get_or_insert(battle.turn);
+                }
+            }
+        }
+    }
+
+    // nothing changes while the battle waits for an action, the events of the last frame are
+    // applied once it waited a whole frame
+    let key = (battle.turn, battle.actions.len() as u32);
+    let waiting = battle.stacks.is_empty()
+        && battle.response.is_none()
+        && matches!(battle.stage, BattleFlow::PlayerAction);
+    let waited = waiting && net.waiting == Some(key);
+    net.waiting = Some(key).filter(|_| waiting);
+    if waited && !net.checksums.contains_key(&key) {
+        let mut hasher = DefaultHasher::new();
+        battle.turn.hash(&mut hasher);
+        battle.current_player_index.hash(&mut hasher);
+        battle.eliminated.len().hash(&mut hasher);
+        for (net_id, &entity) in net.ids.iter().enumerate() {
+            if let Ok((unit, position)) = unit_query.get(entity) {
+                net_id.hash(&mut hasher);
+                (unit.hp, unit.atk, unit.def, unit.ap).hash(&mut hasher);
+                (position.x, position.y, position.z).hash(&mut hasher);
+                (position.position_type as u8).hash(&mut hasher);
+                net.ids.net_id(position.player_id).hash(&mut hasher);
+            }
+        }
+        let checksum = hasher.finish();
+        net.checksums.insert(key, checksum);
+        net.send(&NetMessage::Checksum {
+            turn: battle.turn,
+            actions: key.1,
+            checksum,
+        });
+        net.check(key);
+    }
+}
+
+/// Host or join a game, the host starts the battles
+pub fn online_ui_system(
+    mut commands: Commands,
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut net: ResMut<NetSession>,
+    mut server_client: ResMut<ServerClient>,
+    player_query: Query<&Player>,
+    local_query: Query<&Player, With<LocalPlayer>>,
+    card_query: Query<&Card>,
+    unit_query: Query<&Unit>,
+    ability_query: Query<&Ability>,
+) {
+    let net = &mut *net;
+    egui::Window::new("Online").show(egui_context.ctx(), |ui| {
+        if let Ok(player) = local_query.single() {
+            let deck = player.deck().map(|deck| deck.name.as_str());
+            ui.label(format!("Deck: {}", deck.unwrap_or("None")));
+        }
+        if net.role.is_none() {
+            ui.horizontal(|ui| {
+                ui.label("Address:");
+                ui.text_edit_singleline(&mut net.address);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Host").clicked() {
+                    if let Err(e) = net.host() {
+                        net.status = NetStatus::Failed(e);
+                    }
+                }
+                if ui.button("Join").clicked() {
+                    net.join();
+                }
+            });
+            if let NetStatus::Failed(e) = &net.status {
+                ui.label(format!("Failed: {}", e));
+            }
+        } else {
+            ui.label(format!("Address: {}", net.address));
+            ui.label(match &net.status {
+                NetStatus::Offline => "Offline".to_string(),
+                NetStatus::Waiting if net.is_host() => "Waiting for a player to join".to_string(),
+                NetStatus::Waiting => "Connecting".to_string(),
+                NetStatus::Connected => "Connected".to_string(),
+                NetStatus::Reconnecting => "Reconnecting".to_string(),
+                NetStatus::Failed(e) => format!("Failed: {}", e),
+            });
+            if let Some(peer) = net.peer.and_then(|peer| player_query.get(peer).ok()) {
+                ui.label(format!("Playing against {}", peer.name));
+                if net.is_starting() {
+                    ui.label("Waiting for the guest's deck");
+                } else if net.is_host() {
+                    if ui.button("Start Battle").clicked() {
+                        if let Ok(player) = local_query.single() {
+                            if let Err(e) =
+                                net.start_battle(player, &card_query, &unit_query, &ability_query)
+                            {
+                                net.status = NetStatus::Failed(e);
+                            }
+                        }
+                    }
+                } else {
+                    ui.label("Waiting for the host to start");
+                }
+            }
+            if ui.button("Disconnect").clicked() {
+                if let Some(peer) = net.close() {
+                    despawn_remote_player(&mut commands, peer, &player_query);
+                }
+            }
+        }
+        ui.separator();
+        ui.label("Battle server:");
+        ui.horizontal(|ui| {
+            ui.label("Address:");
+            ui.text_edit_singleline(&mut server_client.address);
+        });
+        if let Some(e) = &server_client.error {
+            ui.label(format!("Failed: {}", e));
+        }
+        if ui.button("Join Server").clicked() {
+            if let Ok(player) = local_query.single() {
+                join_battle_server(
+                    &mut server_client,
+                    player,
+                    &card_query,
+                    &unit_query,
+                    &ability_query,
+                );
+                if server_client.is_connected() {
+                    app_state.set(AppState::ServerBattle).unwrap();
+                }
+            }
+        }
+        ui.separator();
+        if ui.button("Back to Lobby").clicked() {
+            app_state.set(AppState::Lobby).unwrap();
+        }
+    });
+}
+
+/// Connection state shown during online battles
+pub fn net_status_ui_system(
+    egui_context: ResMut<EguiContext>,
+    net: Res<NetSession>,
+    battle: Res<Option<Battle>>,
+) {
+    if net.role.is_none() || battle.is_none() || net.ids.is_empty() {
+        return;
+    }
+    egui::Window::new("Connection").show(egui_context.ctx(), |ui| {
+        match &net.status {
+            NetStatus::Connected => ui.label("Connected"),
+            NetStatus::Reconnecting => ui.label("Connection lost, reconnecting"),
+            NetStatus::Failed(e) => ui.label(format!("Failed: {}", e)),
+            _ => ui.label("Connecting"),
+        };
+        if let Some(turn) = net.desync {
+            ui.label(format!("Battles out of sync since turn {}", turn));
+        }
+    });
+}
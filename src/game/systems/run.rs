@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    game::components::{BoosterDataId, Card, CardDataId, Deck, EncounterDataId, Player, RunPlayer},
+    AppState,
+};
+
+use super::{
+    game::{spawn_card, GameRng},
+    shop::pull_card,
+    BattleConfig, BattleResult,
+};
+
+/// Cards a run starts with
+pub const RUN_DECK: [CardDataId; 3] =
+    [CardDataId::Solider, CardDataId::Solider, CardDataId::Archer];
+/// Cards offered after each win
+pub const DRAFT_SIZE: usize = 3;
+
+/// A roguelike run, its deck is separate from the player's collection
+pub struct RunState {
+    /// Shown so a run can be replayed with the same seed
+    pub seed: u64,
+    /// Draws the encounters and drafts of the run
+    pub rng: StdRng,
+    /// The run's `Player`, `None` when no run is going on
+    pub player: Option<Entity>,
+    pub wins: u32,
+    /// Cards to pick one from after a win
+    pub draft: Vec<CardDataId>,
+    pub in_battle: bool,
+    /// Set when a battle is lost, the run is over
+    pub lost: bool,
+    /// Seed typed in the run screen
+    pub seed_input: String,
+}
+
+impl Default for RunState {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            player: None,
+            wins: 0,
+            draft: vec![],
+            in_battle: false,
+            lost: false,
+            seed_input: String::new(),
+        }
+    }
+}
+
+impl RunState {
+    /// Encounters are drawn from these
+    pub fn encounters() -> Vec<EncounterDataId> {
+        vec![
+            EncounterDataId::Training,
+            EncounterDataId::BanditCamp,
+            EncounterDataId::CultShrine,
+            EncounterDataId::Outpost,
+            EncounterDataId::Ruins,
+        ]
+    }
+
+    fn roll_draft(&mut self) {
+        let booster = BoosterDataId::Basic.booster_data();
+        let rng = &mut self.rng;
+        self.draft = (0..DRAFT_SIZE).map(|_| pull_card(rng, &booster)).collect();
+    }
+}
+
+fn start_run(commands: &mut Commands, run: &mut RunState, seed: u64) {
+    let cards: Vec<_> = RUN_DECK
+        .iter()
+        .map(|&card_data_id| spawn_card(commands, card_data_id))
+        .collect();
+    let character_card = spawn_card(commands, CardDataId::You);
+    let player = commands
+        .spawn()
+        .insert(Player {
+            name: "You".into(),
+            money: 0,
+            decks: vec![Deck {
+                name: "Run".into(),
+                cards,
+                character_card,
+            }],
+            selected_deck: 0,
+            cards: vec![],
+            characters: vec![character_card],
+        })
+        .insert(RunPlayer)
+        .insert(Name::new("Run Player"))
+        .id();
+    *run = RunState {
+        seed,
+        rng: StdRng::seed_from_u64(seed),
+        player: Some(player),
+        seed_input: run.seed_input.clone(),
+        ..Default::default()
+    };
+}
+
+fn end_run(commands: &mut Commands, run: &mut RunState, player_query: &Query<&Player>) {
+    if let Some(entity) = run.player.take() {
+        if let Ok(player) = player_query.get(entity) {
+            for deck in player.decks.iter() {
+                for &card in deck.cards.iter() {
+                    commands.entity(card).despawn_recursive();
+                }
+            }
+            for &card in player.characters.iter() {
+                commands.entity(card).despawn_recursive();
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+    run.draft.clear();
+    run.lost = false;
+}
+
+pub fn run_ui_system(
+    mut commands: Commands,
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut run: ResMut<RunState>,
+    mut game_rng: ResMut<GameRng>,
+    mut battle_config: ResMut<BattleConfig>,
+    mut player_query: QuerySet<(Query<&Player>, Query<&mut Player>)>,
+    card_query: Query<&Card>,
+) {
+    let run = &mut *run;
+    egui::Window::new("Run").show(egui_context.ctx(), |ui| {
+        let player = match run.player {
+            Some(player) => player,
+            None => {
+                ui.label("Start with a small deck, draft a card after every win.");
+                ui.label("A loss ends the run.");
+                ui.horizontal(|ui| {
+                    ui.label("Seed:");
+                    ui.text_edit_singleline(&mut run.seed_input);
+                });
+                if ui.button("Start Run").clicked() {
+                    let seed = run
+                        .seed_input
+                        .trim()
+                        .parse()
+                        .unwrap_or_else(|_| game_rng.0.gen());
+                    start_run(&mut commands, run, seed);
+                }
+                if ui.button("Back to Lobby").clicked() {
+                    app_state.set(AppState::Lobby).unwrap();
+                }
+                return;
+            }
+        };
+
+        ui.label(format!("Seed: {}", run.seed));
+        ui.label(format!("Wins: {}", run.wins));
+        if let Ok(player) = player_query.q0().get(player) {
+            let names: Vec<_> = player
+                .deck()
+                .iter()
+                .flat_map(|deck| deck.cards.iter())
+                .filter_map(|&card| card_query.get(card).ok())
+                .map(|card| card.name.clone())
+                .collect();
+            ui.label(format!("Deck: {}", names.join(", ")));
+        }
+        ui.separator();
+
+        if run.lost {
+            ui.label(format!("Run over after {} wins", run.wins));
+            if ui.button("Finish").clicked() {
+                end_run(&mut commands, run, player_query.q0());
+            }
+            return;
+        }
+
+        if !run.draft.is_empty() {
+            ui.label("Pick a card:");
+            let mut picked = None;
+            ui.horizontal(|ui| {
+                for &card_data_id in run.draft.iter() {
+                    let data = card_data_id.card_data();
+                    if ui
+                        .button(format!("{} ({:?})", data.name, data.rarity))
+                        .clicked()
+                    {
+                        picked = Some(card_data_id);
+                    }
+                }
+            });
+            if let Some(card_data_id) = picked {
+                let card = spawn_card(&mut commands, card_data_id);
+                if let Ok(mut player) = player_query.q1_mut().get_mut(player) {
+                    let selected_deck = player.selected_deck;
+                    player.decks[selected_deck].cards.push(card);
+                }
+                run.draft.clear();
+            }
+            return;
+        }
+
+        if ui.button("Next Battle").clicked() {
+            let encounters = RunState::encounters();
+            battle_config.encounter = encounters[run.rng.gen_range(0..encounters.len())];
+            battle_config.you = Some(player);
+            run.in_battle = true;
+            app_state.set(AppState::Battle).unwrap();
+        }
+        if ui.button("Abandon Run").clicked() {
+            end_run(&mut commands, run, player_query.q0());
+        }
+        if ui.button("Back to Lobby").clicked() {
+            app_state.set(AppState::Lobby).unwrap();
+        }
+    });
+}
+
+/// A win offers a draft, a loss ends the run
+pub fn run_battle_end(
+    mut run: ResMut<RunState>,
+    mut battle_config: ResMut<BattleConfig>,
+    result: Res<Option<BattleResult>>,
+) {
+    if !run.in_battle {
+        return;
+    }
+    battle_config.you = None;
+    let won = match (run.player, result.as_ref()) {
+        (Some(player), Some(result)) => result
+            .winners()
+            .iter()
+            .any(|winner| winner.player_id == player),
+        _ => false,
+    };
+    if won {
+        run.wins += 1;
+        run.roll_draft();
+    } else {
+        run.lost = true;
+    }
+}
@@ -0,0 +1,472 @@
+use std::{env, fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    components::{
+        Ability, AbilityDataId, Card, CardDataId, CardDataType, CardType, LocalPlayer, Player, Unit,
+    },
+    events::SaveGameEvent,
+};
+
+use super::CampaignProgress;
+
+/// Bump when the save format changes and add a step to `migrate`
+pub const SAVE_VERSION: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    /// Files written before versioning have no version, read as 0
+    #[serde(default)]
+    pub version: u32,
+    pub player: PlayerSave,
+    #[serde(default)]
+    pub campaign: CampaignSave,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CampaignSave {
+    /// Cleared nodes of the campaign, in order
+    pub cleared: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub name: String,
+    pub money: i32,
+    pub decks: Vec<DeckSave>,
+    #[serde(default)]
+    pub selected_deck: usize,
+    pub cards: Vec<CardSave>,
+    pub characters: Vec<CardSave>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeckSave {
+    pub name: String,
+    pub cards: Vec<CardSave>,
+    /// Index into `PlayerSave::characters`
+    pub character_card: usize,
+}
+
+/// A card and the progression of its unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardSave {
+    pub card_data_id: CardDataId,
+    #[serde(default)]
+    pub exp: i32,
+    #[serde(default)]
+    pub level: i32,
+    #[serde(default)]
+    pub dead_count: i32,
+    #[serde(default)]
+    pub use_count: i32,
+    #[serde(default)]
+    pub wins: i32,
+    #[serde(default)]
+    pub loses: i32,
+    #[serde(default)]
+    pub abilities: Vec<AbilitySave>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbilitySave {
+    /// `None` only before version 5, filled in by `migrate`
+    #[serde(default)]
+    pub ability_data_id: Option<AbilityDataId>,
+    pub level: i32,
+    pub use_count: i32,
+}
+
+impl CardSave {
+    /// A new card without progression
+    pub fn new(card_data_id: CardDataId) -> Self {
+        Self {
+            card_data_id,
+            exp: 0,
+            level: 1,
+            dead_count: 0,
+            use_count: 0,
+            wins: 0,
+            loses: 0,
+            abilities: vec![],
+        }
+    }
+}
+
+/// `<data dir>/bevy_card/save.ron`
+pub fn save_path() -> Option<PathBuf> {
+    let data_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    data_dir.map(|dir| dir.join("bevy_card").join("save.ron"))
+}
+
+/// `None` when there is no save yet or it can not be read
+pub fn read_save() -> Option<SaveData> {
+    let path = save_path()?;
+    let text = fs::read_to_string(&path).ok()?;
+    match ron::from_str::<SaveData>(&text)
+        .map_err(|e| e.to_string())
+        .and_then(migrate)
+        .and_then(|save| {
+            if save.player.decks.is_empty() || save.player.characters.is_empty() {
+                Err("save has no deck or character".into())
+            } else {
+                Ok(save)
+            }
+        }) {
+        Ok(save) => Some(save),
+        Err(e) => {
+            // keep the unreadable file around instead of overwriting it on the next save
+            error!("Failed to read save {:?}: {}", path, e);
+            let _ = fs::rename(&path, path.with_extension("ron.bak"));
+            None
+        }
+    }
+}
+
+pub fn write_save(save: &SaveData) -> Result<(), String> {
+    let path = save_path().ok_or("No data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let text = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())?;
+    // write then rename, a crash never leaves a half written save
+    let tmp = path.with_extension("ron.tmp");
+    fs::write(&tmp, text).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+/// Upgrade an older save one version at a time
+fn migrate(mut save: SaveData) -> Result<SaveData, String> {
+    if save.version > SAVE_VERSION {
+        return Err(format!(
+            "save version {} is newer than {}",
+            save.version, SAVE_VERSION
+        ));
+    }
+    while save.version < SAVE_VERSION {
+        match save.version {
+            // 0 => 1: only adds the version, same layout
+            0 => (),
+            // 1 => 2: units gain levels, start at level 1
+            1 => {
+                let player = &mut save.player;
+                let cards = player
+                    .decks
+                    .iter_mut()
+                    .flat_map(|deck| deck.cards.iter_mut())
+                    .chain(player.cards.iter_mut())
+                    .chain(player.characters.iter_mut());
+                for card in cards {
+                    card.level = 1;
+                }
+            }
+            // 2 => 3: adds ability progression, missing abilities are level 1
+            2 => (),
+            // 3 => 4: adds campaign progress, starts from the first node
+            3 => (),
+            // 4 => 5: abilities are saved by id, they were in the order of the unit data
+            4 => {
+                let player = &mut save.player;
+                let cards = player
+                    .decks
+                    .iter_mut()
+                    .flat_map(|deck| deck.cards.iter_mut())
+                    .chain(player.cards.iter_mut())
+                    .chain(player.characters.iter_mut());
+                for card in cards {
+                    let abilities = match card.card_data_id.card_data().card_type {
+                        CardDataType::Unit(unit) => unit.unit_data().abilities,
+                    };
+                    card.abilities.truncate(abilities.len());
+                    for (ability, id) in card.abilities.iter_mut().zip(abilities) {
+                        ability.ability_data_id = Some(id);
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+        save.version += 1;
+    }
+    Ok(save)
+}
+
+/// `None` when the card is gone, it is left out of the save
+pub fn card_save(
+    card: Entity,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> Option<CardSave> {
+    let card = match card_query.get(card) {
+        Ok(card) => card,
+        Err(_) => {
+            warn!("Card {:?} is missing, not saved", card);
+            return None;
+        }
+    };
+    let mut save = CardSave {
+        use_count: card.use_count,
+        wins: card.wins,
+        loses: card.loses,
+        ..CardSave::new(card.card_data_id)
+    };
+    match card.card_type {
+        CardType::Unit(unit) => {
+            if let Ok(unit) = unit_query.get(unit) {
+                save.exp = unit.exp;
+                save.level = unit.level;
+                save.dead_count = unit.dead_count;
+                save.abilities = unit
+                    .abilities
+                    .iter()
+                    .filter_map(|&ability| ability_query.get(ability).ok())
+                    .map(|ability| AbilitySave {
+                        ability_data_id: Some(ability.ability_data_id),
+                        level: ability.level,
+                        use_count: ability.use_count,
+                    })
+                    .collect();
+            }
+        }
+    }
+    Some(save)
+}
+
+pub fn save_data(
+    player: &Player,
+    progress: &CampaignProgress,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) -> SaveData {
+    let cards = |cards: &Vec<Entity>| {
+        cards
+            .iter()
+            .filter_map(|&card| card_save(card, card_query, unit_query, ability_query))
+            .collect()
+    };
+    // indices into the characters that are saved
+    let characters: Vec<_> = player
+        .characters
+        .iter()
+        .copied()
+        .filter(|&character| card_query.get(character).is_ok())
+        .collect();
+    SaveData {
+        version: SAVE_VERSION,
+        player: PlayerSave {
+            name: player.name.clone(),
+            money: player.money,
+            decks: player
+                .decks
+                .iter()
+                .map(|deck| DeckSave {
+                    name: deck.name.clone(),
+                    cards: cards(&deck.cards),
+                    character_card: characters
+                        .iter()
+                        .position(|&character| character == deck.character_card)
+                        .unwrap_or(0),
+                })
+                .collect(),
+            selected_deck: player.selected_deck,
+            cards: cards(&player.cards),
+            characters: cards(&characters),
+        },
+        campaign: CampaignSave {
+            cleared: progress.cleared.clone(),
+        },
+    }
+}
+
+pub fn save_game_system(
+    mut events: EventReader<SaveGameEvent>,
+    progress: Res<CampaignProgress>,
+    player_query: Query<&Player, With<LocalPlayer>>,
+    card_query: Query<&Card>,
+    unit_query: Query<&Unit>,
+    ability_query: Query<&Ability>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    if let Ok(player) = player_query.single() {
+        if let Err(e) = write_save(&save_data(
+            player,
+            &progress,
+            &card_query,
+            &unit_query,
+            &ability_query,
+        )) {
+            error!("Failed to save: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Written before the save had a version, units had no levels and abilities no progression
+    const V0_SAVE: &str = r#"(
+        player: (
+            name: "You",
+            money: 120,
+            decks: [(
+                name: "Deck 1",
+                cards: [(card_data_id: Solider), (card_data_id: Archer)],
+                character_card: 0,
+            )],
+            cards: [(card_data_id: Spare)],
+            characters: [(card_data_id: You)],
+        ),
+    )"#;
+
+    /// Abilities were saved in the order of the unit data, without their id
+    const V4_SAVE: &str = r#"(
+        version: 4,
+        player: (
+            name: "You",
+            money: 80,
+            decks: [(
+                name: "Deck 1",
+                cards: [(
+                    card_data_id: Solider,
+                    level: 2,
+                    abilities: [
+                        (level: 3, use_count: 7),
+                        (level: 1, use_count: 2),
+                        (level: 1, use_count: 0),
+                    ],
+                )],
+                character_card: 0,
+            )],
+            cards: [],
+            characters: [(card_data_id: You, level: 1)],
+        ),
+        campaign: (cleared: [0, 1]),
+    )"#;
+
+    fn read(text: &str) -> Result<SaveData, String> {
+        ron::from_str::<SaveData>(text)
+            .map_err(|e| e.to_string())
+            .and_then(migrate)
+    }
+
+    fn to_ron(save: &SaveData) -> String {
+        ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    fn sample() -> SaveData {
+        let mut soldier = CardSave::new(CardDataId::Solider);
+        soldier.exp = 4;
+        soldier.level = 2;
+        soldier.wins = 3;
+        soldier.abilities = vec![AbilitySave {
+            ability_data_id: Some(AbilityDataId::Attack),
+            level: 2,
+            use_count: 9,
+        }];
+        SaveData {
+            version: SAVE_VERSION,
+            player: PlayerSave {
+                name: "You".into(),
+                money: 150,
+                decks: vec![DeckSave {
+                    name: "Main".into(),
+                    cards: vec![soldier, CardSave::new(CardDataId::Archer)],
+                    character_card: 0,
+                }],
+                selected_deck: 0,
+                cards: vec![CardSave::new(CardDataId::Spare)],
+                characters: vec![CardSave::new(CardDataId::You)],
+            },
+            campaign: CampaignSave {
+                cleared: vec![0, 2],
+            },
+        }
+    }
+
+    #[test]
+    fn migrates_an_unversioned_save() {
+        let save = read(V0_SAVE).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.player.money, 120);
+        assert_eq!(save.player.selected_deck, 0);
+        let deck = &save.player.decks[0];
+        assert_eq!(deck.cards[1].card_data_id, CardDataId::Archer);
+        let cards = deck
+            .cards
+            .iter()
+            .chain(save.player.cards.iter())
+            .chain(save.player.characters.iter());
+        for card in cards {
+            assert_eq!(card.level, 1);
+            assert!(card.abilities.is_empty());
+        }
+        assert!(save.campaign.cleared.is_empty());
+    }
+
+    #[test]
+    fn migrates_abilities_to_their_ids() {
+        let save = read(V4_SAVE).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.campaign.cleared, vec![0, 1]);
+        let soldier = &save.player.decks[0].cards[0];
+        assert_eq!(soldier.level, 2);
+        let abilities: Vec<_> = soldier
+            .abilities
+            .iter()
+            .map(|ability| (ability.ability_data_id, ability.level, ability.use_count))
+            .collect();
+        assert_eq!(
+            abilities,
+            vec![
+                (Some(AbilityDataId::Attack), 3, 7),
+                (Some(AbilityDataId::Counter), 1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn current_save_is_read_as_written() {
+        let save = sample();
+        assert_eq!(to_ron(&read(&to_ron(&save)).unwrap()), to_ron(&save));
+    }
+
+    #[test]
+    fn newer_save_is_rejected() {
+        let mut save = sample();
+        save.version = SAVE_VERSION + 1;
+        assert!(read(&to_ron(&save)).is_err());
+    }
+
+    #[test]
+    fn save_file_round_trip() {
+        let dir = env::temp_dir().join(format!("bevy_card_save_{}", std::process::id()));
+        let var = if cfg!(target_os = "windows") {
+            "APPDATA"
+        } else if cfg!(target_os = "macos") {
+            "HOME"
+        } else {
+            "XDG_DATA_HOME"
+        };
+        env::set_var(var, &dir);
+        let save = sample();
+        write_save(&save).unwrap();
+        let read = read_save();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(to_ron(&read.unwrap()), to_ron(&save));
+    }
+}
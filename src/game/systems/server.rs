@@ -0,0 +1,682 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{TcpListener, TcpStream},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        components::{
+            AbilityInstance, AbilityType, Board, CardDataId, CardInstance, Cell, CellType,
+            EncounterDataId, Player, PlayerInstance, PlayerInstanceRef, Position, PositionType,
+            UnitInstance, WinCondition,
+        },
+        events::{BattleEvent, PlayerAction, PlayerActionEvent},
+    },
+    AppState,
+};
+
+use super::{
+    deck_builder::card_rule_errors,
+    net::{
+        collect_ids, despawn_remote_player, spawn_remote_player, BattleEntityFilter, Connection,
+        NetIds,
+    },
+    save::CardSave,
+    spectator::Replay,
+    Battle, BattleConfig,
+};
+
+/// Bump when the messages change, clients must match
+pub const SERVER_VERSION: u32 = 3;
+pub const SERVER_ADDRESS: &str = "127.0.0.1:7878";
+/// Clients of one battle
+pub const SERVER_SEATS: usize = 2;
+
+/// Entities in messages are the server's, a client only sends them back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join {
+        version: u32,
+        name: String,
+        deck: Vec<CardSave>,
+        character: CardSave,
+    },
+    /// Watch the battle instead of playing, hidden cards only once it is over
+    Spectate { version: u32 },
+    /// Checked by the server like any other player action
+    Action(PlayerAction),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// Seat of the client, the battle starts once every seat has joined
+    Welcome {
+        seat: usize,
+    },
+    /// Resolved frames the client may see, in order
+    Events(Vec<BattleEvent>),
+    /// Everything the client may see, sent whenever it changes
+    View(BattleView),
+    /// Winning team, `None` for a draw
+    End {
+        winner: Option<usize>,
+        reason: WinCondition,
+    },
+    Error(String),
+    /// Spectators get the battle as a replay once it is over
+    Replay(Replay),
+}
+
+/// The battle as one client sees it, without the opponent's hand or any deck order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BattleView {
+    /// Player instance of the client, `None` for spectators
+    pub you: Option<Entity>,
+    pub turn: u32,
+    pub current_player: Entity,
+    /// An enemy ability waits for the client's response
+    pub respond: bool,
+    pub players: Vec<PlayerView>,
+    pub boards: Vec<BoardView>,
+    /// Cards on the boards, in graves and exile, and the client's hand
+    pub cards: Vec<CardView>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerView {
+    pub player: Entity,
+    pub name: String,
+    pub team: usize,
+    pub hand: usize,
+    pub deck: usize,
+    pub eliminated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardView {
+    pub player: Entity,
+    pub width: i32,
+    pub height: i32,
+    pub cells: Vec<(i32, i32, CellType)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardView {
+    pub card: Entity,
+    pub card_data_id: CardDataId,
+    pub name: String,
+    pub owner: Entity,
+    pub position: Position,
+    pub hp: i32,
+    pub atk: i32,
+    pub def: i32,
+    pub ap: i32,
+    pub abilities: Vec<AbilityView>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbilityView {
+    pub ability: Entity,
+    pub name: String,
+    pub ap: i32,
+    /// Can be used in response to an enemy ability
+    pub instant: bool,
+}
+
+pub struct SeatClient {
+    /// `None` once the client dropped
+    connection: Option<Connection>,
+    joined: Option<(String, Vec<CardSave>, CardSave)>,
+    /// The `Player` built from the client's deck
+    player: Option<Entity>,
+    /// Last view sent, only changes are sent
+    view: Option<BattleView>,
+}
+
+pub struct Watcher {
+    connection: Connection,
+    /// Last view sent, spectators see no hand
+    view: Option<BattleView>,
+}
+
+/// The battle server, owns the battle and streams it to the clients
+pub struct BattleServer {
+    listener: TcpListener,
+    /// Connections that did not say yet whether they join or spectate
+    pending: Vec<Connection>,
+    pub clients: Vec<SeatClient>,
+    pub watchers: Vec<Watcher>,
+    ids: NetIds,
+    /// Seconds per turn, the server's clock is the only one
+    pub turn_time: Option<f32>,
+    /// `Battle::log` already streamed
+    streamed: usize,
+}
+
+impl BattleServer {
+    pub fn bind(address: &str, turn_time: Option<f32>) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            pending: vec![],
+            clients: vec![],
+            watchers: vec![],
+            ids: NetIds::default(),
+            turn_time,
+            streamed: 0,
+        })
+    }
+
+    fn send_all(&mut self, message: &ServerMessage) {
+        for connection in self
+            .clients
+            .iter_mut()
+            .filter_map(|c| c.connection.as_mut())
+        {
+            connection.send(message);
+        }
+    }
+}
+
+fn seat_instance(
+    battle: &Battle,
+    instance_query: &Query<&PlayerInstance>,
+    player: Entity,
+) -> Option<Entity> {
+    battle.players.iter().copied().find(|&instance| {
+        instance_query
+            .get(instance)
+            .map(|instance| instance.player_id == player)
+            .unwrap_or(false)
+    })
+}
+
+/// Clients send entities without generation, find the battle entity with the same id
+fn resolve(action: PlayerAction, entities: &HashMap<u32, Entity>) -> Option<PlayerAction> {
+    let entity = |entity: Entity| entities.get(&entity.id()).copied();
+    let position = |position: Position| {
+        Some(Position {
+            player_id: entity(position.player_id)?,
+            ..position
+        })
+    };
+    Some(match action {
+        PlayerAction::EndTurn => PlayerAction::EndTurn,
+        PlayerAction::UseAbility {
+            ability,
+            source,
+            target,
+        } => PlayerAction::UseAbility {
+            ability: entity(ability)?,
+            source: entity(source)?,
+            target: entity(target)?,
+        },
+        PlayerAction::Attack { source, target } => PlayerAction::Attack {
+            source: entity(source)?,
+            target: entity(target)?,
+        },
+        PlayerAction::SummonUnit { card, position: to } => PlayerAction::SummonUnit {
+            card: entity(card)?,
+            position: position(to)?,
+        },
+        PlayerAction::Move { unit, to } => PlayerAction::Move {
+            unit: entity(unit)?,
+            to: position(to)?,
+        },
+        PlayerAction::Pass => PlayerAction::Pass,
+        PlayerAction::Surrender => PlayerAction::Surrender,
+    })
+}
+
+/// Seat the clients and spectators, read their messages and start the battle once every seat joined
+pub fn server_connection_system(
+    mut commands: Commands,
+    mut server: ResMut<BattleServer>,
+    mut app_state: ResMut<State<AppState>>,
+    mut battle_config: ResMut<BattleConfig>,
+    battle: Res<Option<Battle>>,
+    instance_query: Query<&PlayerInstance>,
+    children_query: Query<&Children>,
+    net_query: Query<(), BattleEntityFilter>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+) {
+    let server = &mut *server;
+    let in_lobby = *app_state.current() == AppState::Lobby;
+    if in_lobby {
+        // a client that dropped before the battle gives up its seat
+        server.clients.retain(|client| client.connection.is_some());
+    }
+    loop {
+        let stream: TcpStream = match server.listener.accept() {
+            Ok((stream, address)) => {
+                info!("Client connected from {}", address);
+                stream
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("Failed to accept: {}", e);
+                break;
+            }
+        };
+        match Connection::new(stream) {
+            Ok(connection) => server.pending.push(connection),
+            Err(e) => warn!("Failed to set up connection: {}", e),
+        }
+    }
+
+    for mut connection in std::mem::take(&mut server.pending) {
+        let messages = match connection.poll::<ClientMessage>() {
+            Ok(messages) => messages,
+            Err(e) => {
+                info!("Client left: {}", e);
+                continue;
+            }
+        };
+        let message = match messages.into_iter().next() {
+            Some(message) => message,
+            None => {
+                server.pending.push(connection);
+                continue;
+            }
+        };
+        match message {
+            ClientMessage::Join { version, .. } | ClientMessage::Spectate { version }
+                if version != SERVER_VERSION =>
+            {
+                connection.send(&ServerMessage::Error(format!(
+                    "Client version {} does not match {}",
+                    version, SERVER_VERSION
+                )));
+                let _ = connection.finish();
+            }
+            ClientMessage::Join { .. } if !in_lobby || server.clients.len() >= SERVER_SEATS => {
+                connection.send(&ServerMessage::Error("The battle is full".into()));
+                let _ = connection.finish();
+            }
+            ClientMessage::Join {
+                name,
+                deck,
+                character,
+                ..
+            } => {
+                let cards: Vec<_> = deck.iter().map(|card| card.card_data_id).collect();
+                let mut errors = card_rule_errors(&cards);
+                if !character.card_data_id.is_character() {
+                    errors.push(format!(
+                        "{} is not a character card",
+                        character.card_data_id.card_data().name
+                    ));
+                }
+                if !errors.is_empty() {
+                    connection.send(&ServerMessage::Error(errors.join(", ")));
+                    let _ = connection.finish();
+                    continue;
+                }
+                info!("{} joined", name);
+                // everyone plays with fresh cards, progression is not trusted
+                let deck = cards.into_iter().map(CardSave::new).collect();
+                let character = CardSave::new(character.card_data_id);
+                connection.send(&ServerMessage::Welcome {
+                    seat: server.clients.len(),
+                });
+                server.clients.push(SeatClient {
+                    connection: Some(connection),
+                    joined: Some((name, deck, character)),
+                    player: None,
+                    view: None,
+                });
+            }
+            ClientMessage::Spectate { .. } => {
+                info!("Spectator joined");
+                server.watchers.push(Watcher {
+                    connection,
+                    view: None,
+                });
+            }
+            ClientMessage::Action(_) => {
+                connection.send(&ServerMessage::Error("Not in the battle".into()));
+                server.pending.push(connection);
+            }
+        }
+    }
+
+    let battle = battle.as_ref();
+    let mut entities = None;
+    for client in server.clients.iter_mut() {
+        let connection = match client.connection.as_mut() {
+            Some(connection) => connection,
+            None => continue,
+        };
+        let messages = match connection.poll::<ClientMessage>() {
+            Ok(messages) => messages,
+            Err(e) => {
+                info!("Client left: {}", e);
+                client.connection = None;
+                // leaving during the battle is giving up
+                if let (Some(battle), Some(player)) = (battle, client.player) {
+                    if let Some(instance) = seat_instance(battle, &instance_query, player) {
+                        ev_player.send(PlayerActionEvent {
+                            player_id: instance,
+                            action: PlayerAction::Surrender,
+                        });
+                    }
+                }
+                continue;
+            }
+        };
+        for message in messages {
+            match message {
+                ClientMessage::Join { .. } | ClientMessage::Spectate { .. } => (),
+                ClientMessage::Action(action) => {
+                    let (battle, player) = match (battle, client.player) {
+                        (Some(battle), Some(player)) => (battle, player),
+                        _ => continue,
+                    };
+                    let entities = entities.get_or_insert_with(|| {
+                        let mut ids = vec![];
+                        for &board in battle.boards.iter() {
+                            collect_ids(board, &children_query, &net_query, &mut ids);
+                        }
+                        ids.into_iter()
+                            .map(|entity| (entity.id(), entity))
+                            .collect::<HashMap<_, _>>()
+                    });
+                    // the seat decides the player, the rules decide the rest
+                    match (
+                        seat_instance(battle, &instance_query, player),
+                        resolve(action, entities),
+                    ) {
+                        (Some(instance), Some(action)) => ev_player.send(PlayerActionEvent {
+                            player_id: instance,
+                            action,
+                        }),
+                        _ => connection.send(&ServerMessage::Error("Unknown entity".into())),
+                    }
+                }
+            }
+        }
+    }
+
+    if in_lobby
+        && server.clients.len() == SERVER_SEATS
+        && server.clients.iter().all(|client| client.joined.is_some())
+    {
+        battle_config.players = server
+            .clients
+            .iter_mut()
+            .map(|client| {
+                let (name, deck, character) = client.joined.take().unwrap();
+                let player = spawn_remote_player(&mut commands, name, &deck, &character);
+                client.player = Some(player);
+                player
+            })
+            .collect();
+        battle_config.encounter = EncounterDataId::Server;
+        battle_config.turn_time = server.turn_time;
+        server.streamed = 0;
+        server.ids.clear();
+        info!("Battle starts");
+        app_state.set(AppState::Battle).unwrap();
+    }
+
+    for client in server.clients.iter_mut() {
+        if let Some(Err(e)) = client.connection.as_mut().map(Connection::flush) {
+            info!("Client left: {}", e);
+            client.connection = None;
+        }
+    }
+    for connection in server.pending.iter_mut() {
+        // a failed flush shows up on the next poll
+        let _ = connection.flush();
+    }
+    // spectators only listen, polling notices when they leave
+    server.watchers = std::mem::take(&mut server.watchers)
+        .into_iter()
+        .filter_map(|mut watcher| {
+            match watcher
+                .connection
+                .poll::<ClientMessage>()
+                .and_then(|_| watcher.connection.flush())
+            {
+                Ok(_) => Some(watcher),
+                Err(e) => {
+                    info!("Spectator left: {}", e);
+                    None
+                }
+            }
+        })
+        .collect();
+}
+
+fn battle_view(
+    battle: &Battle,
+    you: Option<Entity>,
+    instance_query: &Query<&PlayerInstance>,
+    board_query: &Query<(&Board, &PlayerInstanceRef)>,
+    cell_query: &Query<(&Cell, &Position)>,
+    card_query: &Query<(Entity, &CardInstance, &UnitInstance, &Position)>,
+    ability_query: &Query<&AbilityInstance>,
+) -> BattleView {
+    let count = |player: Entity, position_type: PositionType| {
+        card_query
+            .iter()
+            .filter(|(_, _, _, position)| {
+                position.player_id == player && position.position_type == position_type
+            })
+            .count()
+    };
+    let players = battle
+        .players
+        .iter()
+        .filter_map(|&player| {
+            let instance = instance_query.get(player).ok()?;
+            Some(PlayerView {
+                player,
+                name: instance.name.clone(),
+                team: instance.team,
+                hand: count(player, PositionType::Hand),
+                deck: count(player, PositionType::Deck),
+                eliminated: battle.eliminated.contains(&player),
+            })
+        })
+        .collect();
+    let boards = battle
+        .boards
+        .iter()
+        .filter_map(|&board| {
+            let (board, player) = board_query.get(board).ok()?;
+            Some(BoardView {
+                player: player.0,
+                width: board.width,
+                height: board.height,
+                cells: cell_query
+                    .iter()
+                    .filter(|(_, position)| position.player_id == player.0)
+                    .map(|(cell, position)| (position.x, position.y, cell.cell_type))
+                    .collect(),
+            })
+        })
+        .collect();
+    let mut cards: Vec<_> = card_query
+        .iter()
+        .filter(|(_, _, _, position)| position.is_visible_to(you))
+        .map(|(entity, card, unit, &position)| CardView {
+            card: entity,
+            card_data_id: card.card_data_id,
+            name: card.name.clone(),
+            owner: unit.owner,
+            position,
+            hp: unit.hp,
+            atk: unit.atk,
+            def: unit.def,
+            ap: unit.ap,
+            abilities: unit
+                .abilities
+                .iter()
+                .filter_map(|&ability| {
+                    let instance = ability_query.get(ability).ok()?;
+                    let instant = match instance.ability_type {
+                        AbilityType::Active(_) => false,
+                        AbilityType::Instant(_) => true,
+                        AbilityType::Trigger(_) => return None,
+                    };
+                    Some(AbilityView {
+                        ability,
+                        name: instance.name.clone(),
+                        ap: instance.ap,
+                        instant,
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+    // query order differs between runs, keep the view stable
+    cards.sort_by_key(|card| card.card.id());
+    BattleView {
+        you,
+        turn: battle.turn,
+        current_player: battle.players[battle.current_player_index],
+        respond: you.is_some() && battle.response.map(|window| window.player) == you,
+        players,
+        boards,
+        cards,
+    }
+}
+
+/// Events the viewer may see, those naming a hidden card are left out like the card itself
+fn visible_events(
+    events: &[BattleEvent],
+    viewer: Option<Entity>,
+    card_query: &Query<(Entity, &CardInstance, &UnitInstance, &Position)>,
+) -> Vec<BattleEvent> {
+    events
+        .iter()
+        .filter(|event| {
+            event.cards().into_iter().all(|card| {
+                card_query
+                    .get(card)
+                    .map(|(_, _, _, position)| position.is_visible_to(viewer))
+                    .unwrap_or(true)
+            })
+        })
+        .copied()
+        .collect()
+}
+
+/// Stream resolved frames and views, end the match once the battle is over
+pub fn server_stream_system(
+    mut commands: Commands,
+    mut server: ResMut<BattleServer>,
+    mut app_state: ResMut<State<AppState>>,
+    battle: Res<Option<Battle>>,
+    player_query: Query<&Player>,
+    instance_query: Query<&PlayerInstance>,
+    board_query: Query<(&Board, &PlayerInstanceRef)>,
+    cell_query: Query<(&Cell, &Position)>,
+    card_query: Query<(Entity, &CardInstance, &UnitInstance, &Position)>,
+    ability_query: Query<&AbilityInstance>,
+    children_query: Query<&Children>,
+    net_query: Query<(), BattleEntityFilter>,
+) {
+    let server = &mut *server;
+    let battle = match battle.as_ref() {
+        Some(battle) => battle,
+        None => return,
+    };
+    if server.ids.is_empty() {
+        server.ids.collect(battle, &children_query, &net_query);
+    }
+    let events = &battle.log[server.streamed.min(battle.log.len())..];
+    server.streamed = battle.log.len();
+    for client in server.clients.iter_mut() {
+        let you = match client
+            .player
+            .and_then(|player| seat_instance(battle, &instance_query, player))
+        {
+            Some(you) => you,
+            None => continue,
+        };
+        let seen = visible_events(events, Some(you), &card_query);
+        if let (false, Some(connection)) = (seen.is_empty(), client.connection.as_mut()) {
+            connection.send(&ServerMessage::Events(seen));
+        }
+        let view = battle_view(
+            battle,
+            Some(you),
+            &instance_query,
+            &board_query,
+            &cell_query,
+            &card_query,
+            &ability_query,
+        );
+        if client.view.as_ref() != Some(&view) {
+            if let Some(connection) = client.connection.as_mut() {
+                connection.send(&ServerMessage::View(view.clone()));
+            }
+            client.view = Some(view);
+        }
+    }
+    // the hands and decks stay hidden until the battle is over
+    let mut spectator_view = None;
+    for watcher in server.watchers.iter_mut() {
+        let seen = visible_events(events, None, &card_query);
+        if !seen.is_empty() {
+            watcher.connection.send(&ServerMessage::Events(seen));
+        }
+        let view = spectator_view.get_or_insert_with(|| {
+            battle_view(
+                battle,
+                None,
+                &instance_query,
+                &board_query,
+                &cell_query,
+                &card_query,
+                &ability_query,
+            )
+        });
+        if watcher.view.as_ref() != Some(view) {
+            watcher.connection.send(&ServerMessage::View(view.clone()));
+            watcher.view = Some(view.clone());
+        }
+    }
+
+    if *app_state.current() == AppState::BattleEnd {
+        let replay = Replay::record(battle, &server.ids);
+        let end = battle.result.as_ref().map(|result| {
+            info!("Battle over: {:?}", result.reason);
+            ServerMessage::End {
+                winner: result.winner,
+                reason: result.reason,
+            }
+        });
+        if let Some(end) = end.as_ref() {
+            server.send_all(end);
+        }
+        // the next clients get a new battle
+        for client in server.clients.drain(..) {
+            if let Some(Err(e)) = client.connection.map(Connection::finish) {
+                info!("Client left: {}", e);
+            }
+            if let Some(player) = client.player {
+                despawn_remote_player(&mut commands, player, &player_query);
+            }
+        }
+        for mut watcher in server.watchers.drain(..) {
+            if let Some(replay) = replay.clone() {
+                watcher.connection.send(&ServerMessage::Replay(replay));
+            }
+            if let Some(end) = end.as_ref() {
+                watcher.connection.send(end);
+            }
+            if let Err(e) = watcher.connection.finish() {
+                info!("Spectator left: {}", e);
+            }
+        }
+        app_state.set(AppState::Lobby).unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+use std::{
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use crate::{
+    game::{
+        components::{Ability, Card, CellType, Player, Position, PositionType, Unit},
+        events::PlayerAction,
+    },
+    AppState,
+};
+
+use super::{
+    net::{deck_saves, Connection},
+    save::CardSave,
+    server::{BattleView, CardView, ClientMessage, ServerMessage, SERVER_ADDRESS, SERVER_VERSION},
+    spectator::{Replay, Spectator},
+};
+
+/// Battle events kept for the log window
+const LOG_SIZE: usize = 20;
+
+/// What a click on the board does next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selected {
+    /// Own unit on the board: attack or move
+    Unit(Entity),
+    /// Own card in hand: summon
+    Card(Entity),
+    /// Ability of an own unit waiting for a target
+    Ability(Entity, Entity),
+}
+
+/// Client of the battle server, only shows what the server sends
+pub struct ServerClient {
+    pub address: String,
+    connection: Option<Connection>,
+    pub seat: Option<usize>,
+    pub view: Option<BattleView>,
+    pub log: Vec<String>,
+    /// Set by the server's end message
+    pub result: Option<String>,
+    /// The finished battle, spectators get it once it is over
+    pub replay: Option<Replay>,
+    pub error: Option<String>,
+    selected: Option<Selected>,
+}
+
+impl Default for ServerClient {
+    fn default() -> Self {
+        Self {
+            address: SERVER_ADDRESS.into(),
+            connection: None,
+            seat: None,
+            view: None,
+            log: vec![],
+            result: None,
+            replay: None,
+            error: None,
+            selected: None,
+        }
+    }
+}
+
+impl ServerClient {
+    pub fn connect(&mut self, name: String, deck: Vec<CardSave>, character: CardSave) {
+        self.open(ClientMessage::Join {
+            version: SERVER_VERSION,
+            name,
+            deck,
+            character,
+        });
+    }
+
+    /// Watch the server's battle, as a view without hands and as a replay once it is over
+    pub fn spectate(&mut self) {
+        self.open(ClientMessage::Spectate {
+            version: SERVER_VERSION,
+        });
+    }
+
+    /// Drop the connection and everything the server sent, keeps the address
+    pub fn close(&mut self) {
+        *self = Self {
+            address: self.address.clone(),
+            ..Default::default()
+        };
+    }
+
+    fn open(&mut self, hello: ClientMessage) {
+        self.close();
+        let connection = self
+            .address
+            .parse::<SocketAddr>()
+            .map_err(|e| e.to_string())
+            .and_then(|address| {
+                TcpStream::connect_timeout(&address, Duration::from_secs(1))
+                    .and_then(Connection::new)
+                    .map_err(|e| e.to_string())
+            });
+        match connection {
+            Ok(mut connection) => {
+                connection.send(&hello);
+                self.connection = Some(connection);
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    fn send(&mut self, action: PlayerAction) {
+        if let Some(connection) = &mut self.connection {
+            connection.send(&ClientMessage::Action(action));
+        }
+        self.selected = None;
+    }
+}
+
+/// Read what the server sent
+pub fn server_client_system(mut client: ResMut<ServerClient>) {
+    let client = &mut *client;
+    let connection = match client.connection.as_mut() {
+        Some(connection) => connection,
+        None => return,
+    };
+    let messages = connection
+        .poll::<ServerMessage>()
+        .and_then(|messages| connection.flush().map(|_| messages));
+    let messages = match messages {
+        Ok(messages) => messages,
+        Err(e) => {
+            client.connection = None;
+            // keep the server's reason for turning the client away
+            if client.result.is_none() && client.error.is_none() {
+                client.error = Some(format!("Disconnected: {}", e));
+            }
+            return;
+        }
+    };
+    for message in messages {
+        match message {
+            ServerMessage::Welcome { seat } => client.seat = Some(seat),
+            ServerMessage::Events(events) => {
+                client
+                    .log
+                    .extend(events.iter().map(|event| format!("{:?}", event)));
+                let len = client.log.len();
+                if len > LOG_SIZE {
+                    client.log.drain(..len - LOG_SIZE);
+                }
+            }
+            ServerMessage::View(view) => client.view = Some(view),
+            ServerMessage::End { winner, reason } => {
+                let team = client.view.as_ref().and_then(|view| {
+                    view.players
+                        .iter()
+                        .find(|player| Some(player.player) == view.you)
+                        .map(|player| player.team)
+                });
+                client.result = Some(match (winner, team) {
+                    (Some(winner), Some(team)) if winner == team => {
+                        format!("Victory ({:?})", reason)
+                    }
+                    (Some(_), Some(_)) => format!("Defeat ({:?})", reason),
+                    (Some(winner), None) => format!("Team {} wins ({:?})", winner + 1, reason),
+                    (None, _) => format!("Draw ({:?})", reason),
+                });
+            }
+            ServerMessage::Error(e) => client.error = Some(e),
+            ServerMessage::Replay(replay) => client.replay = Some(replay),
+        }
+    }
+}
+
+fn card_label(card: &CardView) -> String {
+    format!(
+        "{}\n{}/{}/{} {}AP",
+        card.name, card.hp, card.atk, card.def, card.ap
+    )
+}
+
+/// Boards, hand and actions of a battle run by the server
+pub fn server_battle_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut client: ResMut<ServerClient>,
+    mut spectator: ResMut<Spectator>,
+) {
+    let client = &mut *client;
+    let mut action = None;
+    let mut selected = client.selected;
+    let mut leave = None;
+    let mut watch = false;
+    egui::Window::new("Battle Server").show(egui_context.ctx(), |ui| {
+        if let Some(e) = &client.error {
+            ui.label(e);
+        }
+        if let Some(result) = &client.result {
+            ui.heading(result);
+        }
+        let view = match &client.view {
+            Some(view) => view,
+            None => {
+                if client.is_connected() {
+                    ui.label(match client.seat {
+                        Some(seat) => format!("Seat {}, waiting for the other player", seat + 1),
+                        None => "Connecting".to_string(),
+                    });
+                }
+                if ui.button("Leave").clicked() {
+                    leave = Some(AppState::Online);
+                }
+                return;
+            }
+        };
+        let your_turn = Some(view.current_player) == view.you && client.result.is_none();
+
+        ui.label(format!("Turn {}", view.turn));
+        for player in view.players.iter() {
+            ui.label(format!(
+                "{}{}: hand {}, deck {}{}",
+                if player.player == view.current_player {
+                    "> "
+                } else {
+                    ""
+                },
+                player.name,
+                player.hand,
+                player.deck,
+                if player.eliminated {
+                    ", eliminated"
+                } else {
+                    ""
+                }
+            ));
+        }
+        ui.separator();
+
+        for board in view.boards.iter() {
+            let name = view
+                .players
+                .iter()
+                .find(|player| player.player == board.player)
+                .map(|player| player.name.clone())
+                .unwrap_or_default();
+            ui.label(name);
+            egui::Grid::new(board.player.id()).show(ui, |ui| {
+                for y in (0..board.height).rev() {
+                    for x in 0..board.width {
+                        let position = Position {
+                            x,
+                            y,
+                            z: 0,
+                            face_up: true,
+                            player_id: board.player,
+                            position_type: PositionType::Board,
+                        };
+                        let unit = view
+                            .cards
+                            .iter()
+                            .find(|card| card.position.same_cell(&position));
+                        let cell_type = board
+                            .cells
+                            .iter()
+                            .find(|&&(cx, cy, _)| cx == x && cy == y)
+                            .map(|&(_, _, cell_type)| cell_type);
+                        let label = match unit {
+                            Some(unit) => card_label(unit),
+                            None => format!("{:?}", cell_type.unwrap_or(CellType::Normal)),
+                        };
+                        let is_selected = matches!(
+                            (unit, selected),
+                            (Some(unit), Some(Selected::Unit(s))) if unit.card == s
+                        );
+                        if !ui.selectable_label(is_selected, label).clicked() {
+                            continue;
+                        }
+                        let own = unit.map(|unit| Some(unit.owner) == view.you);
+                        match (selected, unit) {
+                            (Some(Selected::Ability(ability, source)), Some(target)) => {
+                                action = Some(PlayerAction::UseAbility {
+                                    ability,
+                                    source,
+                                    target: target.card,
+                                });
+                            }
+                            (_, Some(unit)) if own == Some(true) => {
+                                selected = Some(Selected::Unit(unit.card));
+                            }
+                            (Some(Selected::Unit(source)), Some(target)) if your_turn => {
+                                action = Some(PlayerAction::Attack {
+                                    source,
+                                    target: target.card,
+                                });
+                            }
+                            (Some(Selected::Unit(unit)), None) if your_turn => {
+                                action = Some(PlayerAction::Move { unit, to: position });
+                            }
+                            (Some(Selected::Card(card)), None) if your_turn => {
+                                action = Some(PlayerAction::SummonUnit { card, position });
+                            }
+                            _ => selected = None,
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+        ui.separator();
+
+        // spectators have no hand and no actions
+        if view.you.is_none() {
+            ui.horizontal(|ui| {
+                if client.replay.is_some() && ui.button("Watch Replay").clicked() {
+                    watch = true;
+                }
+                if ui.button("Stop Watching").clicked() {
+                    leave = Some(AppState::Spectate);
+                }
+            });
+            return;
+        }
+        ui.label("Hand:");
+        ui.horizontal(|ui| {
+            for card in view.cards.iter().filter(|card| {
+                card.position.position_type == PositionType::Hand
+                    && Some(card.position.player_id) == view.you
+            }) {
+                let is_selected = selected == Some(Selected::Card(card.card));
+                if ui.selectable_label(is_selected, card_label(card)).clicked() {
+                    selected = Some(Selected::Card(card.card));
+                }
+            }
+        });
+
+        if let Some(Selected::Unit(source)) | Some(Selected::Ability(_, source)) = selected {
+            if let Some(unit) = view.cards.iter().find(|card| card.card == source) {
+                ui.horizontal(|ui| {
+                    for ability in unit.abilities.iter() {
+                        let usable = if view.respond {
+                            ability.instant
+                        } else {
+                            your_turn
+                        };
+                        let button =
+                            egui::Button::new(format!("{} ({} AP)", ability.name, ability.ap));
+                        if ui
+                            .add_enabled(usable && unit.ap >= ability.ap, button)
+                            .clicked()
+                        {
+                            selected = Some(Selected::Ability(ability.ability, source));
+                        }
+                    }
+                });
+                if let Some(Selected::Ability(..)) = selected {
+                    ui.label("Pick a target");
+                }
+            }
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if view.respond {
+                ui.label("Respond to the enemy ability");
+                if ui.button("Pass").clicked() {
+                    action = Some(PlayerAction::Pass);
+                }
+            }
+            if ui
+                .add_enabled(your_turn, egui::Button::new("End Turn"))
+                .clicked()
+            {
+                action = Some(PlayerAction::EndTurn);
+            }
+            if client.result.is_none() && ui.button("Surrender").clicked() {
+                action = Some(PlayerAction::Surrender);
+            }
+            if client.result.is_some() && ui.button("Back to Lobby").clicked() {
+                leave = Some(AppState::Lobby);
+            }
+        });
+    });
+
+    if client.view.is_some() {
+        egui::Window::new("Battle Log").show(egui_context.ctx(), |ui| {
+            for line in client.log.iter() {
+                ui.label(line);
+            }
+        });
+    }
+
+    client.selected = selected;
+    if let Some(action) = action {
+        client.send(action);
+    }
+    if watch {
+        spectator.watch(client.replay.take(), false);
+        client.close();
+    }
+    if let Some(state) = leave {
+        client.close();
+        app_state.set(state).unwrap();
+    }
+}
+
+/// Join the battle server with the selected deck
+pub fn join_battle_server(
+    client: &mut ServerClient,
+    player: &Player,
+    card_query: &Query<&Card>,
+    unit_query: &Query<&Unit>,
+    ability_query: &Query<&Ability>,
+) {
+    match deck_saves(player, card_query, unit_query, ability_query) {
+        Some((deck, character)) => client.connect(player.name.clone(), deck, character),
+        None => client.error = Some("No deck selected".into()),
+    }
+}
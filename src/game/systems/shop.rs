@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use rand::Rng;
+
+use crate::{
+    game::{
+        components::{BoosterData, BoosterDataId, Card, CardDataId, LocalPlayer, Player, Rarity},
+        events::SaveGameEvent,
+    },
+    AppState,
+};
+
+use super::game::{spawn_card, GameRng};
+
+#[derive(Debug, Default)]
+pub struct Shop {
+    /// Cards pulled from the last opened booster
+    pub pulls: Vec<CardDataId>,
+    /// Opened from a campaign shop node
+    pub back_to_campaign: bool,
+}
+
+/// Pick a rarity by weight, then a card of that rarity.
+/// Falls back to a lower rarity when no shop card has the picked one.
+pub fn pull_card(rng: &mut impl Rng, booster: &BoosterData) -> CardDataId {
+    let total: u32 = booster.weights.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.gen_range(0..total.max(1));
+    let mut rarity = Rarity::Common;
+    for &(r, weight) in booster.weights.iter() {
+        if roll < weight {
+            rarity = r;
+            break;
+        }
+        roll -= weight;
+    }
+    let rarities = [
+        Rarity::Legendary,
+        Rarity::Rare,
+        Rarity::Uncommon,
+        Rarity::Common,
+    ];
+    let start = rarities.iter().position(|&r| r == rarity).unwrap();
+    for &rarity in rarities[start..].iter() {
+        let cards: Vec<_> = CardDataId::shop()
+            .into_iter()
+            .filter(|card| card.card_data().rarity == rarity)
+            .collect();
+        if !cards.is_empty() {
+            return cards[rng.gen_range(0..cards.len())];
+        }
+    }
+    CardDataId::shop()[0]
+}
+
+pub fn open_shop(mut shop: ResMut<Shop>) {
+    shop.pulls.clear();
+}
+
+pub fn shop_ui_system(
+    mut commands: Commands,
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut shop: ResMut<Shop>,
+    mut rng: ResMut<GameRng>,
+    mut ev_save: EventWriter<SaveGameEvent>,
+    mut player_query: Query<(&mut Player, Option<&Parent>), With<LocalPlayer>>,
+    card_query: Query<&Card>,
+) {
+    let (mut player, game) = match player_query.single_mut() {
+        Ok((player, game)) => (player, game.map(|parent| parent.0)),
+        Err(_) => return,
+    };
+    // owned cards live under the game entity, like the loaded ones
+    let spawn_owned_card = |commands: &mut Commands, card_data_id| {
+        let card = spawn_card(commands, card_data_id);
+        if let Some(game) = game {
+            commands.entity(game).push_children(&[card]);
+        }
+        card
+    };
+    let mut changed = false;
+    egui::Window::new("Shop").show(egui_context.ctx(), |ui| {
+        ui.label(format!("Money: {}", player.money));
+        ui.separator();
+
+        ui.heading("Cards");
+        egui::Grid::new("shop_cards").striped(true).show(ui, |ui| {
+            for card_data_id in CardDataId::shop() {
+                let data = card_data_id.card_data();
+                let price = data.rarity.price();
+                ui.label(&data.name);
+                ui.label(format!("{:?}", data.rarity));
+                ui.label(format!("{}", price));
+                let buy = egui::Button::new("Buy");
+                if ui.add_enabled(player.money >= price, buy).clicked() {
+                    player.money -= price;
+                    let card = spawn_owned_card(&mut commands, card_data_id);
+                    player.cards.push(card);
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+
+        ui.heading("Boosters");
+        egui::Grid::new("shop_boosters")
+            .striped(true)
+            .show(ui, |ui| {
+                for booster_data_id in BoosterDataId::all() {
+                    let booster = booster_data_id.booster_data();
+                    ui.label(&booster.name);
+                    ui.label(format!("{} cards", booster.size));
+                    ui.label(format!("{}", booster.price));
+                    let open = egui::Button::new("Open");
+                    if ui
+                        .add_enabled(player.money >= booster.price, open)
+                        .clicked()
+                    {
+                        player.money -= booster.price;
+                        shop.pulls = (0..booster.size)
+                            .map(|_| pull_card(&mut rng.0, &booster))
+                            .collect();
+                        for &card_data_id in shop.pulls.iter() {
+                            let card = spawn_owned_card(&mut commands, card_data_id);
+                            player.cards.push(card);
+                        }
+                        changed = true;
+                    }
+                    ui.end_row();
+                }
+            });
+        if !shop.pulls.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Pulled:");
+                for card_data_id in shop.pulls.iter() {
+                    let data = card_data_id.card_data();
+                    ui.label(format!("{} ({:?})", data.name, data.rarity));
+                }
+            });
+        }
+        ui.separator();
+
+        ui.heading("Sell");
+        let owned: Vec<CardDataId> = player
+            .decks
+            .iter()
+            .flat_map(|deck| deck.cards.iter())
+            .chain(player.cards.iter())
+            .filter_map(|&card| card_query.get(card).ok())
+            .map(|card| card.card_data_id)
+            .collect();
+        let mut sell = None;
+        egui::Grid::new("shop_sell").striped(true).show(ui, |ui| {
+            for card_data_id in CardDataId::shop() {
+                let copies = owned.iter().filter(|&&id| id == card_data_id).count();
+                if copies == 0 {
+                    continue;
+                }
+                // only duplicates outside of decks can be sold
+                let spare = player.cards.iter().copied().find(|&card| {
+                    card_query
+                        .get(card)
+                        .map(|card| card.card_data_id == card_data_id)
+                        .unwrap_or(false)
+                });
+                let data = card_data_id.card_data();
+                ui.label(&data.name);
+                ui.label(format!("x{}", copies));
+                ui.label(format!("{}", data.rarity.sell_price()));
+                let button = egui::Button::new("Sell");
+                if ui
+                    .add_enabled(copies > 1 && spare.is_some(), button)
+                    .clicked()
+                {
+                    sell = spare.map(|card| (card, data.rarity.sell_price()));
+                }
+                ui.end_row();
+            }
+        });
+        if let Some((card, price)) = sell {
+            player.cards.retain(|&c| c != card);
+            player.money += price;
+            commands.entity(card).despawn_recursive();
+            changed = true;
+        }
+        ui.separator();
+
+        if shop.back_to_campaign {
+            if ui.button("Back to Map").clicked() {
+                shop.back_to_campaign = false;
+                app_state.set(AppState::Campaign).unwrap();
+            }
+        } else if ui.button("Back to Lobby").clicked() {
+            app_state.set(AppState::Lobby).unwrap();
+        }
+    });
+    if changed {
+        ev_save.send(SaveGameEvent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn booster(weights: Vec<(Rarity, u32)>) -> BoosterData {
+        BoosterData {
+            weights,
+            ..BoosterDataId::Basic.booster_data()
+        }
+    }
+
+    fn rarity(card: CardDataId) -> Rarity {
+        card.card_data().rarity
+    }
+
+    #[test]
+    fn pulls_only_the_weighted_rarity() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let booster = booster(vec![(Rarity::Common, 0), (Rarity::Uncommon, 1)]);
+        for _ in 0..100 {
+            assert_eq!(rarity(pull_card(&mut rng, &booster)), Rarity::Uncommon);
+        }
+    }
+
+    #[test]
+    fn missing_rarity_falls_back_to_a_lower_one() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let booster = booster(vec![(Rarity::Legendary, 1)]);
+        assert!(CardDataId::shop()
+            .iter()
+            .all(|&card| rarity(card) != Rarity::Legendary));
+        for _ in 0..100 {
+            assert_eq!(rarity(pull_card(&mut rng, &booster)), Rarity::Rare);
+        }
+    }
+
+    #[test]
+    fn no_weights_pull_commons() {
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(
+            rarity(pull_card(&mut rng, &booster(vec![]))),
+            Rarity::Common
+        );
+    }
+
+    #[test]
+    fn pulls_follow_the_weights() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let booster = booster(vec![(Rarity::Common, 3), (Rarity::Rare, 1)]);
+        let pulls = 4000;
+        let rares = (0..pulls)
+            .filter(|_| rarity(pull_card(&mut rng, &booster)) == Rarity::Rare)
+            .count();
+        // a quarter, give or take
+        assert!((800..1200).contains(&rares), "{} rares", rares);
+    }
+}
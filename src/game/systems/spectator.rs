@@ -0,0 +1,383 @@
+use std::{fs, path::PathBuf};
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        components::{EncounterDataId, Player, PlayerInstance},
+        events::PlayerActionEvent,
+    },
+    AppState,
+};
+
+use super::{
+    net::{
+        despawn_remote_player, is_ready, spawn_remote_player, BattleEntityFilter, NetAction, NetIds,
+    },
+    save::{save_path, CardSave},
+    Battle, BattleConfig, ServerClient,
+};
+
+/// Seconds between the actions of a recorded battle
+const REPLAY_DELAY: f32 = 0.8;
+/// Frames a released action may take to be accepted before the replay gives up
+const ACCEPT_FRAMES: u32 = 10;
+/// Battle events kept for the combat log
+const LOG_SIZE: usize = 20;
+
+/// Everything needed to play a battle again: the encounter, the decks and the accepted actions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub encounter: EncounterDataId,
+    /// By participant index
+    pub players: Vec<ReplayPlayer>,
+    pub actions: Vec<ReplayAction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayPlayer {
+    pub name: String,
+    pub deck: Vec<CardSave>,
+    pub character: CardSave,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplayAction {
+    /// Participant index
+    pub player: usize,
+    pub action: NetAction,
+}
+
+impl Replay {
+    /// The battle's replay with its accepted actions so far
+    pub fn record(battle: &Battle, ids: &NetIds) -> Option<Replay> {
+        let mut replay = battle.replay.clone()?;
+        replay.actions = battle
+            .actions
+            .iter()
+            .filter_map(|(player, action)| {
+                Some(ReplayAction {
+                    player: battle.players.iter().position(|p| p == player)?,
+                    action: ids.to_net(action),
+                })
+            })
+            .collect();
+        Some(replay)
+    }
+}
+
+pub fn replay_path() -> Option<PathBuf> {
+    save_path().map(|path| path.with_file_name("replay.ron"))
+}
+
+pub fn read_replay() -> Result<Replay, String> {
+    let path = replay_path().ok_or("No data directory")?;
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    ron::from_str(&text).map_err(|e| e.to_string())
+}
+
+pub fn write_replay(replay: &Replay) -> Result<(), String> {
+    let path = replay_path().ok_or("No data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let text = ron::ser::to_string(replay).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| e.to_string())
+}
+
+/// Read-only observer of a replayed or live battle, nobody plays and the input is off
+pub struct Spectator {
+    /// The battle being watched, `None` when not spectating
+    pub replay: Option<Replay>,
+    /// Watching the battle server, its views arrive in the `ServerClient`
+    pub live: bool,
+    /// Show every card face up, hands and decks included
+    pub show_hidden: bool,
+    pub paused: bool,
+    pub error: Option<String>,
+    /// `Player`s spawned from the replay's decks
+    players: Vec<Entity>,
+    ids: NetIds,
+    /// Replay actions handed to the battle
+    released: usize,
+    /// Frames the last released action is waiting to be accepted
+    waiting: Option<u32>,
+    timer: f32,
+}
+
+impl Default for Spectator {
+    fn default() -> Self {
+        Self {
+            replay: None,
+            live: false,
+            show_hidden: false,
+            paused: false,
+            error: None,
+            players: vec![],
+            ids: NetIds::default(),
+            released: 0,
+            waiting: None,
+            timer: 0.0,
+        }
+    }
+}
+
+impl Spectator {
+    pub fn is_active(&self) -> bool {
+        self.replay.is_some() || self.live
+    }
+
+    /// Start watching, the battle starts once the replay is there
+    pub fn watch(&mut self, replay: Option<Replay>, live: bool) {
+        *self = Self {
+            replay,
+            live,
+            show_hidden: self.show_hidden,
+            ..Default::default()
+        };
+    }
+
+    /// Stop watching, returns the `Player`s to despawn
+    pub fn close(&mut self) -> Vec<Entity> {
+        let players = std::mem::take(&mut self.players);
+        self.watch(None, false);
+        players
+    }
+}
+
+/// Run criteria, nothing may act for the players while spectating
+pub fn not_spectating(spectator: Res<Spectator>) -> ShouldRun {
+    if spectator.is_active() {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+/// Keep the finished battle for the spectator, spectated battles are not recorded again
+pub fn save_replay(
+    battle: Res<Option<Battle>>,
+    spectator: Res<Spectator>,
+    children_query: Query<&Children>,
+    net_query: Query<(), BattleEntityFilter>,
+) {
+    let battle = match battle.as_ref() {
+        Some(battle) if !spectator.is_active() => battle,
+        _ => return,
+    };
+    let mut ids = NetIds::default();
+    ids.collect(battle, &children_query, &net_query);
+    if let Some(replay) = Replay::record(battle, &ids) {
+        if let Err(e) = write_replay(&replay) {
+            error!("Failed to save replay: {}", e);
+        }
+    }
+}
+
+/// Start the watched battle and hand it the replay's actions one by one
+pub fn spectator_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spectator: ResMut<Spectator>,
+    mut app_state: ResMut<State<AppState>>,
+    mut battle_config: ResMut<BattleConfig>,
+    battle: Res<Option<Battle>>,
+    children_query: Query<&Children>,
+    net_query: Query<(), BattleEntityFilter>,
+    mut ev_player: EventWriter<PlayerActionEvent>,
+) {
+    let spectator = &mut *spectator;
+    let replay = match spectator.replay.as_ref() {
+        Some(replay) => replay,
+        None => return,
+    };
+    if spectator.players.is_empty() {
+        // replays start from the spectator menu or the end of a watched server battle
+        if !matches!(
+            app_state.current(),
+            AppState::Spectate | AppState::ServerBattle
+        ) {
+            return;
+        }
+        spectator.players = replay
+            .players
+            .iter()
+            .map(|player| {
+                spawn_remote_player(
+                    &mut commands,
+                    player.name.clone(),
+                    &player.deck,
+                    &player.character,
+                )
+            })
+            .collect();
+        battle_config.encounter = replay.encounter;
+        battle_config.players = spectator.players.clone();
+        battle_config.turn_time = None;
+        battle_config.you = None;
+        battle_config.spectate = true;
+        app_state.set(AppState::Battle).unwrap();
+        return;
+    }
+    let battle = match battle.as_ref() {
+        Some(battle) => battle,
+        None => return,
+    };
+    if spectator.ids.is_empty() {
+        spectator.ids.collect(battle, &children_query, &net_query);
+    }
+
+    if spectator.waiting.is_some() {
+        if battle.actions.len() >= spectator.released {
+            spectator.waiting = None;
+        } else if let Some(frames) = spectator.waiting.as_mut() {
+            *frames += 1;
+            if *frames > ACCEPT_FRAMES {
+                spectator.waiting = None;
+                spectator.paused = true;
+                spectator.error = Some(format!("Replay does not match at turn {}", battle.turn));
+            }
+        }
+    } else if !spectator.paused {
+        spectator.timer -= time.delta_seconds();
+        if let Some(next) = replay.actions.get(spectator.released) {
+            let player = battle.players.get(next.player).copied();
+            match (player, spectator.ids.from_net(&next.action)) {
+                (Some(player), Some(action)) => {
+                    if spectator.timer <= 0.0 && is_ready(battle, player, &next.action) {
+                        ev_player.send(PlayerActionEvent {
+                            player_id: player,
+                            action,
+                        });
+                        spectator.released += 1;
+                        spectator.waiting = Some(0);
+                        spectator.timer = REPLAY_DELAY;
+                    }
+                }
+                _ => {
+                    spectator.paused = true;
+                    spectator.error = Some(format!("Unknown entity at turn {}", battle.turn));
+                }
+            }
+        }
+    }
+}
+
+/// Leave the watched battle, runs whenever the spectator menu opens
+pub fn close_spectator(
+    mut commands: Commands,
+    mut spectator: ResMut<Spectator>,
+    mut battle_config: ResMut<BattleConfig>,
+    mut server_client: ResMut<ServerClient>,
+    player_query: Query<&Player>,
+) {
+    if !spectator.is_active() {
+        return;
+    }
+    for player in spectator.close() {
+        despawn_remote_player(&mut commands, player, &player_query);
+    }
+    battle_config.players.clear();
+    battle_config.spectate = false;
+    server_client.close();
+}
+
+/// Pick a recorded battle or a battle server to watch
+pub fn spectate_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut spectator: ResMut<Spectator>,
+    mut server_client: ResMut<ServerClient>,
+) {
+    egui::Window::new("Spectate").show(egui_context.ctx(), |ui| {
+        if let Some(e) = spectator
+            .error
+            .as_ref()
+            .or_else(|| server_client.error.as_ref())
+        {
+            ui.label(format!("Failed: {}", e));
+        }
+        if ui.button("Watch Last Battle").clicked() {
+            match read_replay() {
+                Ok(replay) => spectator.watch(Some(replay), false),
+                Err(e) => spectator.error = Some(e),
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Battle server:");
+            ui.text_edit_singleline(&mut server_client.address);
+        });
+        if spectator.live {
+            ui.label("Waiting for the next battle");
+            if server_client.view.is_some() {
+                app_state.set(AppState::ServerBattle).unwrap();
+            }
+        } else if ui.button("Watch Server").clicked() {
+            server_client.spectate();
+            if server_client.is_connected() {
+                spectator.watch(None, true);
+            }
+        }
+        ui.separator();
+        if ui.button("Back to Lobby").clicked() {
+            spectator.close();
+            server_client.close();
+            app_state.set(AppState::Lobby).unwrap();
+        }
+    });
+}
+
+/// Spectator controls and the combat log
+pub fn spectator_ui_system(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut spectator: ResMut<Spectator>,
+    battle: Res<Option<Battle>>,
+    player_query: Query<&PlayerInstance>,
+) {
+    let battle = match battle.as_ref() {
+        Some(battle) if spectator.is_active() => battle,
+        _ => return,
+    };
+    let spectator = &mut *spectator;
+    egui::Window::new("Spectator").show(egui_context.ctx(), |ui| {
+        let name = |player: Entity| {
+            player_query
+                .get(player)
+                .map(|player| player.name.clone())
+                .unwrap_or_default()
+        };
+        ui.label(format!(
+            "Turn {}, {} to act",
+            battle.turn,
+            name(battle.players[battle.current_player_index])
+        ));
+        if let Some(replay) = &spectator.replay {
+            ui.label(format!(
+                "Replay, action {} of {}",
+                spectator.released,
+                replay.actions.len()
+            ));
+        }
+        if let Some(e) = &spectator.error {
+            ui.label(e);
+        }
+        ui.checkbox(&mut spectator.show_hidden, "Show hidden information");
+        if spectator.error.is_none() {
+            ui.checkbox(&mut spectator.paused, "Paused");
+        }
+        if ui.button("Stop Watching").clicked() {
+            app_state.set(AppState::Spectate).unwrap();
+        }
+    });
+    egui::Window::new("Combat Log").show(egui_context.ctx(), |ui| {
+        let skip = battle.log.len().saturating_sub(LOG_SIZE);
+        for event in battle.log.iter().skip(skip) {
+            ui.label(format!("{:?}", event));
+        }
+    });
+}
@@ -0,0 +1,20 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod animation;
+pub mod game;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum AppState {
+    MainMenu,
+    Lobby,
+    Campaign,
+    Run,
+    Online,
+    Spectate,
+    ServerBattle,
+    DeckBuilder,
+    DeckSelect,
+    Shop,
+    Battle,
+    BattleEnd,
+}
@@ -5,20 +5,10 @@ use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext, EguiPlugin};
 use bevy_inspector_egui::{InspectableRegistry, WorldInspectorPlugin};
 
-use animation::animation::AnimationPlugin;
-use animation::ui_animation::UiAnimationPlugin;
-use game::GamePlugin;
-
-mod animation;
-mod game;
-
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum AppState {
-    MainMenu,
-    Lobby,
-    Battle,
-    BattleEnd,
-}
+use bevy_card::animation::animation::AnimationPlugin;
+use bevy_card::animation::ui_animation::UiAnimationPlugin;
+use bevy_card::game::{self, components::EncounterDataId, GamePlugin};
+use bevy_card::AppState;
 
 fn main() {
     App::build()
@@ -50,14 +40,58 @@ fn main() {
         .add_startup_system(game::load_game.system())
         .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(main_menu.system()))
         .add_system_set(SystemSet::on_update(AppState::Lobby).with_system(lobby_menu.system()))
+        .add_system_set(
+            SystemSet::on_update(AppState::Campaign).with_system(game::campaign_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Run).with_system(game::run_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Online).with_system(game::online_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Spectate).with_system(game::close_spectator.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Spectate).with_system(game::spectate_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::ServerBattle)
+                .with_system(game::server_battle_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::DeckBuilder)
+                .with_system(game::open_deck_builder.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::DeckBuilder)
+                .with_system(game::deck_builder_ui_system.system()),
+        )
+        .add_system_set(SystemSet::on_enter(AppState::Shop).with_system(game::open_shop.system()))
+        .add_system_set(
+            SystemSet::on_update(AppState::Shop).with_system(game::shop_ui_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::DeckSelect)
+                .with_system(game::deck_select_ui_system.system()),
+        )
         .add_system_set(
             SystemSet::on_enter(AppState::Battle).with_system(game::start_battle.system()),
         )
         .add_system_set(
-            SystemSet::on_exit(AppState::Battle).with_system(game::cleanup_battle.system()),
+            SystemSet::on_exit(AppState::Battle)
+                .with_system(game::cleanup_battle.system())
+                .with_system(game::save_replay.system()),
         )
         .add_system_set(
-            SystemSet::on_enter(AppState::BattleEnd).with_system(back_to_lobby.system()),
+            SystemSet::on_enter(AppState::BattleEnd)
+                .with_system(game::give_battle_rewards.system())
+                .with_system(game::campaign_battle_end.system())
+                .with_system(game::run_battle_end.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::BattleEnd)
+                .with_system(game::battle_end_ui_system.system()),
         )
         .add_plugin(AnimationPlugin)
         .add_plugin(UiAnimationPlugin)
@@ -103,21 +137,55 @@ fn main_menu(egui_context: ResMut<EguiContext>, mut app_state: ResMut<State<AppS
     });
 }
 
-fn lobby_menu(egui_context: ResMut<EguiContext>, mut app_state: ResMut<State<AppState>>) {
+fn lobby_menu(
+    egui_context: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut battle_config: ResMut<game::BattleConfig>,
+) {
     egui::Window::new("Lobby Menu").show(egui_context.ctx(), |ui| {
+        if let Some(e) = &battle_config.error {
+            ui.label(format!("Battle failed: {}", e));
+        }
+        ui.horizontal(|ui| {
+            for encounter in EncounterDataId::all() {
+                let name = encounter.encounter_data().name;
+                ui.selectable_value(&mut battle_config.encounter, encounter, name);
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut timer = battle_config.turn_time.is_some();
+            ui.checkbox(&mut timer, "Turn timer");
+            battle_config.turn_time = if timer {
+                let mut seconds = battle_config.turn_time.unwrap_or(30.0);
+                ui.add(egui::Slider::new(&mut seconds, 5.0..=120.0).text("seconds"));
+                Some(seconds)
+            } else {
+                None
+            };
+        });
         if ui.button("Battle").clicked() {
-            app_state.set(AppState::Battle).unwrap();
+            app_state.set(AppState::DeckSelect).unwrap();
+        }
+        if ui.button("Campaign").clicked() {
+            app_state.set(AppState::Campaign).unwrap();
+        }
+        if ui.button("Run").clicked() {
+            app_state.set(AppState::Run).unwrap();
+        }
+        if ui.button("Online").clicked() {
+            app_state.set(AppState::Online).unwrap();
+        }
+        if ui.button("Spectate").clicked() {
+            app_state.set(AppState::Spectate).unwrap();
         }
         if ui.button("Deck").clicked() {
-            println!("Deck");
+            app_state.set(AppState::DeckBuilder).unwrap();
+        }
+        if ui.button("Shop").clicked() {
+            app_state.set(AppState::Shop).unwrap();
         }
         if ui.button("Back to Main Menu").clicked() {
             app_state.set(AppState::MainMenu).unwrap();
         }
     });
 }
-
-fn back_to_lobby(mut app_state: ResMut<State<AppState>>) {
-    println!("Back to lobby");
-    app_state.set(AppState::Lobby).unwrap();
-}
@@ -0,0 +1,416 @@
+use bevy::{app::Events, prelude::*};
+
+use bevy_card::{
+    game::{
+        self,
+        components::{
+            AbilityDataId, AbilityInstance, CardDataId, CardInstance, Cell, Deck, EncounterDataId,
+            Player, PlayerInstance, Position, PositionType, UnitInstance, UnitInstanceRef,
+        },
+        events::{BattleEvent, PlayerAction, PlayerActionEvent},
+        Battle, BattleConfig, BattleFlow,
+    },
+    AppState,
+};
+
+/// Frames a step may take before the test gives up
+const MAX_FRAMES: usize = 1000;
+
+/// Decks of the two players, drawn in this order
+struct Decks(Vec<Vec<CardDataId>>);
+
+fn spawn_players(mut commands: Commands, decks: Res<Decks>, mut config: ResMut<BattleConfig>) {
+    config.encounter = EncounterDataId::Server;
+    config.players = decks
+        .0
+        .iter()
+        .enumerate()
+        .map(|(i, cards)| {
+            let cards = cards
+                .iter()
+                .map(|&card| game::spawn_card(&mut commands, card))
+                .collect();
+            let character_card = game::spawn_card(&mut commands, CardDataId::You);
+            commands
+                .spawn()
+                .insert(Player {
+                    name: format!("Player {}", i + 1),
+                    money: 0,
+                    decks: vec![Deck {
+                        name: "Test".into(),
+                        cards,
+                        character_card,
+                    }],
+                    selected_deck: 0,
+                    cards: vec![],
+                    characters: vec![character_card],
+                })
+                .id()
+        })
+        .collect();
+}
+
+/// Headless battle of two human players, the test acts for both and passes every response
+struct TestBattle {
+    app: App,
+}
+
+impl TestBattle {
+    fn new(first: &[CardDataId], second: &[CardDataId]) -> Self {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(game::BattleLogicPlugin)
+            .insert_resource(Decks(vec![first.to_vec(), second.to_vec()]))
+            .add_state(AppState::Lobby)
+            .add_startup_system(spawn_players.system())
+            .add_system_set(
+                SystemSet::on_enter(AppState::Battle).with_system(game::start_battle.system()),
+            );
+        let mut battle = Self { app: builder.app };
+        battle.app.update();
+        battle
+            .app
+            .world
+            .get_resource_mut::<State<AppState>>()
+            .unwrap()
+            .set(AppState::Battle)
+            .unwrap();
+        battle.settle();
+        battle
+    }
+
+    fn battle(&self) -> &Battle {
+        self.app
+            .world
+            .get_resource::<Option<Battle>>()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+    }
+
+    fn player(&self, index: usize) -> Entity {
+        self.battle().players[index]
+    }
+
+    fn send(&mut self, player_id: Entity, action: PlayerAction) {
+        self.app
+            .world
+            .get_resource_mut::<Events<PlayerActionEvent>>()
+            .unwrap()
+            .send(PlayerActionEvent { player_id, action });
+    }
+
+    /// Run until the current player may act again
+    fn settle(&mut self) {
+        // events sent by the last frame are handled in the next one
+        let mut idle = 0;
+        for _ in 0..MAX_FRAMES {
+            self.app.update();
+            let battle = self.battle();
+            if let Some(window) = battle.response {
+                self.send(window.player, PlayerAction::Pass);
+                idle = 0;
+            } else if battle.stacks.is_empty()
+                && matches!(
+                    battle.stage,
+                    BattleFlow::PlayerAction | BattleFlow::BattleEnd
+                )
+            {
+                idle += 1;
+                if idle > 1 {
+                    return;
+                }
+            } else {
+                idle = 0;
+            }
+        }
+        panic!("battle did not settle");
+    }
+
+    fn act(&mut self, index: usize, action: PlayerAction) {
+        let player = self.player(index);
+        self.send(player, action);
+        self.settle();
+    }
+
+    fn position(&self, entity: Entity) -> Position {
+        *self.app.world.get::<Position>(entity).unwrap()
+    }
+
+    fn unit(&self, entity: Entity) -> &UnitInstance {
+        self.app.world.get::<UnitInstance>(entity).unwrap()
+    }
+
+    fn unit_mut(&mut self, entity: Entity) -> Mut<UnitInstance> {
+        self.app.world.get_mut::<UnitInstance>(entity).unwrap()
+    }
+
+    fn character(&self, index: usize) -> Entity {
+        self.app
+            .world
+            .get::<PlayerInstance>(self.player(index))
+            .unwrap()
+            .player_card_instance_id
+    }
+
+    fn ability(&mut self, unit: Entity, id: AbilityDataId) -> Entity {
+        self.app
+            .world
+            .query::<(Entity, &AbilityInstance, &UnitInstanceRef)>()
+            .iter(&self.app.world)
+            .find(|(_, ability, owner)| owner.0 == unit && ability.ability_data_id == id)
+            .map(|(entity, _, _)| entity)
+            .unwrap()
+    }
+
+    /// First open cell without a unit, front row first
+    fn free_cell(&mut self, index: usize) -> Position {
+        let player = self.player(index);
+        let world = &mut self.app.world;
+        let taken: Vec<Position> = world
+            .query::<(&CardInstance, &Position)>()
+            .iter(world)
+            .map(|(_, &position)| position)
+            .collect();
+        let mut cells: Vec<Position> = world
+            .query::<(&Cell, &Position)>()
+            .iter(world)
+            .filter(|(cell, position)| {
+                position.player_id == player
+                    && cell.cell_type.is_open()
+                    && !taken.iter().any(|card| card.same_cell(position))
+            })
+            .map(|(_, &position)| position)
+            .collect();
+        cells.sort_by_key(|position| (position.y, position.x));
+        cells[0]
+    }
+
+    /// Cell of the player's board
+    fn cell(&mut self, index: usize, x: i32, y: i32) -> Position {
+        let player = self.player(index);
+        let world = &mut self.app.world;
+        world
+            .query::<(&Cell, &Position)>()
+            .iter(world)
+            .map(|(_, &position)| position)
+            .find(|position| position.player_id == player && position.x == x && position.y == y)
+            .unwrap()
+    }
+
+    /// Summon the card from the player's hand, returns the unit
+    fn summon(&mut self, index: usize, card: CardDataId) -> Entity {
+        let position = self.free_cell(index);
+        self.summon_at(index, card, position)
+    }
+
+    fn summon_at(&mut self, index: usize, card: CardDataId, position: Position) -> Entity {
+        let player = self.player(index);
+        let world = &mut self.app.world;
+        let card = world
+            .query::<(Entity, &CardInstance, &Position)>()
+            .iter(world)
+            .find(|(_, instance, position)| {
+                instance.card_data_id == card
+                    && position.player_id == player
+                    && position.position_type == PositionType::Hand
+            })
+            .map(|(entity, _, _)| entity)
+            .expect("card not in hand");
+        self.act(index, PlayerAction::SummonUnit { card, position });
+        assert_eq!(self.position(card).position_type, PositionType::Board);
+        card
+    }
+
+    /// Kill the unit with the character of the player
+    fn kill(&mut self, index: usize, target: Entity) {
+        let source = self.character(index);
+        self.act(index, PlayerAction::Attack { source, target });
+        assert!(self
+            .battle()
+            .log
+            .iter()
+            .any(|event| matches!(event, BattleEvent::UnitDie(die) if die.dead == target)));
+    }
+}
+
+#[test]
+fn phoenix_rises_from_the_grave() {
+    let mut battle = TestBattle::new(
+        &[
+            CardDataId::Phoenix,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+    );
+    let phoenix = battle.summon(0, CardDataId::Phoenix);
+    battle.act(0, PlayerAction::EndTurn);
+    battle.kill(1, phoenix);
+
+    assert_eq!(battle.position(phoenix).position_type, PositionType::Board);
+    let unit = battle.unit(phoenix);
+    assert_eq!(unit.hp, 1);
+    assert_eq!(unit.ap, 0);
+}
+
+#[test]
+fn recall_returns_the_dead_card_to_hand() {
+    let mut battle = TestBattle::new(
+        &[CardDataId::Wisp, CardDataId::Solider, CardDataId::Solider],
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+    );
+    let wisp = battle.summon(0, CardDataId::Wisp);
+    battle.act(0, PlayerAction::EndTurn);
+    battle.kill(1, wisp);
+
+    let position = battle.position(wisp);
+    assert_eq!(position.position_type, PositionType::Hand);
+    assert!(!position.face_up);
+    assert_eq!(battle.unit(wisp).hp, 3);
+}
+
+#[test]
+fn banish_exiles_units_but_not_characters() {
+    let mut battle = TestBattle::new(
+        &[
+            CardDataId::Exorcist,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+    );
+    let exorcist = battle.summon(0, CardDataId::Exorcist);
+    battle.act(0, PlayerAction::EndTurn);
+    let soldier = battle.summon(1, CardDataId::Solider);
+    battle.act(1, PlayerAction::EndTurn);
+    battle.unit_mut(exorcist).ap = 2;
+    let banish = battle.ability(exorcist, AbilityDataId::Banish);
+
+    let character = battle.character(1);
+    let actions = battle.battle().actions.len();
+    battle.act(
+        0,
+        PlayerAction::UseAbility {
+            ability: banish,
+            source: exorcist,
+            target: character,
+        },
+    );
+    assert_eq!(battle.battle().actions.len(), actions);
+    assert_eq!(
+        battle.position(character).position_type,
+        PositionType::Board
+    );
+
+    battle.act(
+        0,
+        PlayerAction::UseAbility {
+            ability: banish,
+            source: exorcist,
+            target: soldier,
+        },
+    );
+    assert_eq!(battle.position(soldier).position_type, PositionType::Exile);
+    assert_eq!(battle.unit(exorcist).ap, 0);
+}
+
+#[test]
+fn sentry_hurts_enemies_entering_the_board() {
+    let mut battle = TestBattle::new(
+        &[
+            CardDataId::Sentinel,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+    );
+    let sentinel = battle.summon(0, CardDataId::Sentinel);
+    assert_eq!(battle.unit(sentinel).hp, 8);
+    battle.act(0, PlayerAction::EndTurn);
+    let soldier = battle.summon(1, CardDataId::Solider);
+
+    assert_eq!(battle.unit(soldier).hp, 9);
+    assert!(battle.battle().log.iter().any(|event| matches!(
+        event,
+        BattleEvent::UnitHurt(hurt) if hurt.source == sentinel && hurt.target == soldier
+    )));
+}
+
+#[test]
+fn attacks_need_an_own_unit_in_reach() {
+    let mut battle = TestBattle::new(
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+        &[
+            CardDataId::Solider,
+            CardDataId::Solider,
+            CardDataId::Solider,
+        ],
+    );
+    let attacker = battle.summon(0, CardDataId::Solider);
+    battle.act(0, PlayerAction::EndTurn);
+    // in front of the character
+    let cell = battle.cell(1, 1, 0);
+    let guard = battle.summon_at(1, CardDataId::Solider, cell);
+    battle.act(1, PlayerAction::EndTurn);
+    battle.unit_mut(attacker).ap = 1;
+    battle.unit_mut(guard).ap = 1;
+
+    // the attack is answered by a pass, count the attacks only
+    let attacks = |battle: &TestBattle| {
+        battle
+            .battle()
+            .actions
+            .iter()
+            .filter(|(_, action)| matches!(action, PlayerAction::Attack { .. }))
+            .count()
+    };
+    let covered = battle.character(1);
+    battle.act(
+        0,
+        PlayerAction::Attack {
+            source: attacker,
+            target: covered,
+        },
+    );
+    let own = battle.character(0);
+    battle.act(
+        0,
+        PlayerAction::Attack {
+            source: guard,
+            target: own,
+        },
+    );
+    assert_eq!(attacks(&battle), 0);
+
+    battle.act(
+        0,
+        PlayerAction::Attack {
+            source: attacker,
+            target: guard,
+        },
+    );
+    assert_eq!(attacks(&battle), 1);
+    assert!(battle.unit(guard).hp < 10);
+}
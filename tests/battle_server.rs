@@ -0,0 +1,272 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy_card::game::{
+    components::{CardDataId, PositionType, UnitDataId, WinCondition},
+    events::PlayerAction,
+    BattleView, CardSave, ClientMessage, Entity, ServerMessage, SERVER_VERSION,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The server binary on a free localhost port, killed when dropped
+struct Server {
+    child: Child,
+    address: String,
+}
+
+impl Server {
+    fn start() -> Self {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .to_string();
+        let child = Command::new(env!("CARGO_BIN_EXE_battle-server"))
+            .arg(&address)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Self { child, address }
+    }
+
+    fn connect(&self) -> Client {
+        let start = Instant::now();
+        loop {
+            match TcpStream::connect(&self.address) {
+                Ok(stream) => return Client::new(stream),
+                Err(e) if start.elapsed() > TIMEOUT => panic!("server did not start: {}", e),
+                Err(_) => thread::sleep(Duration::from_millis(50)),
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn new(stream: TcpStream) -> Self {
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        Self {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn send(&mut self, message: &ClientMessage) {
+        let text = ron::to_string(message).unwrap();
+        writeln!(self.writer, "{}", text).unwrap();
+    }
+
+    /// `None` once the server closed the connection
+    fn receive(&mut self) -> Option<ServerMessage> {
+        let mut line = String::new();
+        match self
+            .reader
+            .read_line(&mut line)
+            .expect("no message in time")
+        {
+            0 => None,
+            _ => Some(ron::from_str(line.trim()).unwrap()),
+        }
+    }
+
+    fn join(&mut self, name: &str, deck: Vec<CardSave>, character: CardSave) {
+        self.send(&ClientMessage::Join {
+            version: SERVER_VERSION,
+            name: name.into(),
+            deck,
+            character,
+        });
+    }
+
+    fn view(&mut self) -> BattleView {
+        loop {
+            match self.receive() {
+                Some(ServerMessage::View(view)) => return view,
+                Some(ServerMessage::Error(e)) => panic!("server error: {}", e),
+                Some(_) => (),
+                None => panic!("server closed the connection"),
+            }
+        }
+    }
+}
+
+fn deck() -> Vec<CardSave> {
+    vec![CardSave::new(CardDataId::Solider); 3]
+}
+
+#[test]
+fn rejects_decks_breaking_the_rules() {
+    let server = Server::start();
+    let mut client = server.connect();
+    client.join(
+        "Cheater",
+        vec![CardSave::new(CardDataId::Solider); 4],
+        CardSave::new(CardDataId::You),
+    );
+    match client.receive() {
+        Some(ServerMessage::Error(e)) => assert!(e.contains("copies"), "{}", e),
+        message => panic!("expected an error, got {:?}", message),
+    }
+    assert!(client.receive().is_none());
+}
+
+#[test]
+fn rejects_a_character_that_is_not_one() {
+    let server = Server::start();
+    let mut client = server.connect();
+    client.join("Cheater", deck(), CardSave::new(CardDataId::Demon));
+    match client.receive() {
+        Some(ServerMessage::Error(e)) => assert!(e.contains("not a character"), "{}", e),
+        message => panic!("expected an error, got {:?}", message),
+    }
+    assert!(client.receive().is_none());
+}
+
+#[test]
+fn plays_a_battle_between_two_clients() {
+    let server = Server::start();
+    let mut first = server.connect();
+    let mut second = server.connect();
+    let character = CardSave {
+        level: i32::MAX,
+        exp: i32::MAX,
+        ..CardSave::new(CardDataId::You)
+    };
+    first.join("First", deck(), character);
+    second.join("Second", deck(), CardSave::new(CardDataId::You));
+    assert!(matches!(
+        first.receive(),
+        Some(ServerMessage::Welcome { seat: 0 })
+    ));
+    assert!(matches!(
+        second.receive(),
+        Some(ServerMessage::Welcome { seat: 1 })
+    ));
+
+    let view = first.view();
+    let characters: Vec<_> = view
+        .cards
+        .iter()
+        .filter(|card| card.card_data_id == CardDataId::You)
+        .collect();
+    assert_eq!(characters.len(), 2);
+    // progression from the client is not trusted
+    let data = UnitDataId::You.unit_data();
+    for character in characters.iter() {
+        assert_eq!(character.hp, data.hp);
+    }
+    let mine = characters
+        .iter()
+        .find(|card| Some(card.owner) == view.you)
+        .unwrap();
+    let theirs = characters
+        .iter()
+        .find(|card| Some(card.owner) != view.you)
+        .unwrap();
+
+    // crafted actions are rejected without taking the server down
+    let crafted = [
+        PlayerAction::Attack {
+            source: theirs.card,
+            target: mine.card,
+        },
+        PlayerAction::Move {
+            unit: view.you.unwrap(),
+            to: mine.position,
+        },
+        PlayerAction::Attack {
+            source: Entity::new(u32::MAX),
+            target: mine.card,
+        },
+    ];
+    for action in crafted {
+        first.send(&ClientMessage::Action(action));
+    }
+    second.send(&ClientMessage::Action(PlayerAction::Surrender));
+
+    let mut end = None;
+    while let Some(message) = first.receive() {
+        match message {
+            ServerMessage::View(view) => {
+                let hp = view
+                    .cards
+                    .iter()
+                    .find(|card| card.card == mine.card)
+                    .map(|card| card.hp);
+                assert_eq!(hp, Some(data.hp));
+            }
+            ServerMessage::End { winner, reason } => {
+                end = Some((winner, reason));
+                break;
+            }
+            _ => (),
+        }
+    }
+    let first_team = view
+        .players
+        .iter()
+        .find(|player| Some(player.player) == view.you)
+        .unwrap()
+        .team;
+    assert_eq!(end, Some((Some(first_team), WinCondition::Surrender)));
+}
+
+#[test]
+fn spectators_see_no_hidden_cards_before_the_end() {
+    let server = Server::start();
+    let mut spectator = server.connect();
+    spectator.send(&ClientMessage::Spectate {
+        version: SERVER_VERSION,
+    });
+    let mut first = server.connect();
+    let mut second = server.connect();
+    first.join("First", deck(), CardSave::new(CardDataId::You));
+    second.join("Second", deck(), CardSave::new(CardDataId::You));
+
+    let view = spectator.view();
+    assert_eq!(view.you, None);
+    assert!(!view.respond);
+    assert!(view
+        .cards
+        .iter()
+        .all(|card| card.position.position_type != PositionType::Hand
+            && card.position.position_type != PositionType::Deck));
+    second.send(&ClientMessage::Action(PlayerAction::Surrender));
+
+    let mut replay = None;
+    let mut end = None;
+    while let Some(message) = spectator.receive() {
+        match message {
+            ServerMessage::View(view) => assert_eq!(view.you, None),
+            ServerMessage::Replay(battle) => {
+                assert!(end.is_none(), "replay sent after the end");
+                replay = Some(battle);
+            }
+            ServerMessage::End { winner, reason } => end = Some((winner, reason)),
+            ServerMessage::Error(e) => panic!("server error: {}", e),
+            _ => (),
+        }
+    }
+    // the decks are only revealed once the battle is over
+    let replay = replay.expect("no replay after the end");
+    assert_eq!(replay.players.len(), 2);
+    assert!(replay.players.iter().all(|player| player.deck.len() == 3));
+    assert!(matches!(end, Some((Some(_), WinCondition::Surrender))));
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use bevy_card::{
+    game::{
+        self,
+        components::{CardDataId, Deck, EncounterDataId, Player},
+        Battle, BattleConfig,
+    },
+    AppState,
+};
+
+/// One player with a deck, the other without any
+fn spawn_players(mut commands: Commands, mut config: ResMut<BattleConfig>) {
+    config.encounter = EncounterDataId::Server;
+    let cards = vec![CardDataId::Solider; 3]
+        .into_iter()
+        .map(|card| game::spawn_card(&mut commands, card))
+        .collect();
+    let character_card = game::spawn_card(&mut commands, CardDataId::You);
+    let ready = commands
+        .spawn()
+        .insert(Player {
+            name: "Ready".into(),
+            money: 0,
+            decks: vec![Deck {
+                name: "Test".into(),
+                cards,
+                character_card,
+            }],
+            selected_deck: 0,
+            cards: vec![],
+            characters: vec![character_card],
+        })
+        .id();
+    let empty = commands
+        .spawn()
+        .insert(Player {
+            name: "Empty".into(),
+            money: 0,
+            decks: vec![],
+            selected_deck: 0,
+            cards: vec![],
+            characters: vec![],
+        })
+        .id();
+    config.players = vec![ready, empty];
+}
+
+#[test]
+fn a_battle_missing_a_team_goes_back_to_the_lobby() {
+    let mut builder = App::build();
+    builder
+        .add_plugins(MinimalPlugins)
+        .add_plugin(game::BattleLogicPlugin)
+        .add_state(AppState::Lobby)
+        .add_startup_system(spawn_players.system())
+        .add_system_set(
+            SystemSet::on_enter(AppState::Battle).with_system(game::start_battle.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Battle).with_system(game::cleanup_battle.system()),
+        );
+    let mut app = builder.app;
+    app.update();
+    app.world
+        .get_resource_mut::<State<AppState>>()
+        .unwrap()
+        .set(AppState::Battle)
+        .unwrap();
+    for _ in 0..10 {
+        app.update();
+    }
+
+    assert_eq!(
+        *app.world
+            .get_resource::<State<AppState>>()
+            .unwrap()
+            .current(),
+        AppState::Lobby
+    );
+    assert!(app
+        .world
+        .get_resource::<Option<Battle>>()
+        .unwrap()
+        .is_none());
+    let error = app
+        .world
+        .get_resource::<BattleConfig>()
+        .unwrap()
+        .error
+        .clone();
+    assert!(error.unwrap().contains("two teams"));
+}
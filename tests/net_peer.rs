@@ -0,0 +1,134 @@
+use std::{
+    env,
+    io::{BufRead, BufReader},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver},
+        Once,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The peer is an example, cargo only hands binaries to integration tests
+fn peer_path() -> PathBuf {
+    static BUILD: Once = Once::new();
+    BUILD.call_once(|| {
+        let status = Command::new(env!("CARGO"))
+            .args(&["build", "--example", "net_peer"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "net_peer example did not build");
+    });
+    // tests run from target/<profile>/deps, examples are built next to them
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    path.pop();
+    path.push("examples");
+    path.push(format!("net_peer{}", env::consts::EXE_SUFFIX));
+    path
+}
+
+/// The peer example, its stdout lines come through `lines`, killed when dropped
+struct Peer {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Peer {
+    fn start(role: &str, address: &str, battles: u32) -> Self {
+        let mut child = Command::new(peer_path())
+            .args(&[role, address, &battles.to_string()])
+            .env("RUST_LOG", "error")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().flatten() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { child, lines }
+    }
+
+    /// Report lines until the peer quits
+    fn report(&self) -> Vec<String> {
+        let start = Instant::now();
+        let mut report = vec![];
+        loop {
+            let left = TIMEOUT
+                .checked_sub(start.elapsed())
+                .expect("peer did not finish its battles");
+            match self.lines.recv_timeout(left) {
+                Ok(line) if line.starts_with("result ") || line.starts_with("opponent ") => {
+                    report.push(line)
+                }
+                Ok(_) => (),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return report,
+                Err(mpsc::RecvTimeoutError::Timeout) => panic!("peer did not finish its battles"),
+            }
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn lines<'a>(report: &'a [String], prefix: &str) -> Vec<&'a str> {
+    report
+        .iter()
+        .filter(|line| line.starts_with(prefix))
+        .map(|line| line.as_str())
+        .collect()
+}
+
+#[test]
+fn two_peers_play_the_same_battles() {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .to_string();
+    let host = Peer::start("host", &address, 2);
+    let guest = Peer::start("join", &address, 2);
+    let host_report = host.report();
+    let guest_report = guest.report();
+
+    let results = lines(&host_report, "result ");
+    assert_eq!(results.len(), 2, "{:?}", host_report);
+    assert_eq!(results, lines(&guest_report, "result "));
+    assert!(
+        results.iter().all(|line| line.ends_with(" None")),
+        "{:?}",
+        results
+    );
+
+    // the guest keeps its first deck, the host switches decks after the first battle
+    assert_eq!(
+        lines(&host_report, "opponent "),
+        vec![
+            "opponent 1 Soldier,Soldier,Archer",
+            "opponent 2 Soldier,Soldier,Archer"
+        ]
+    );
+    assert_eq!(
+        lines(&guest_report, "opponent "),
+        vec![
+            "opponent 1 Soldier,Soldier,Archer",
+            "opponent 2 Soldier,Soldier,Archer,Spare"
+        ]
+    );
+}